@@ -1,25 +1,46 @@
-precision mediump float;
+// Blinn-Phong shading, appended to lighting.glsl
 
-varying vec3 position;
-varying vec4 color;
-varying vec3 normal;
-varying vec2 uv;
-
-uniform vec4 select_color;
-uniform sampler2D tex_sampler;
-uniform vec3 light_color;
-uniform vec3 light_position;
+uniform sampler2D specular_sampler;
+uniform float shininess;
+uniform vec3 ambient_color;
 
 void main() {
-    vec3 light_direction = light_position - position;
-    float n_dot_l = max(
-        dot(
-            normalize(light_direction),
-            normalize(normal)
-        ),
-        0.0
-    );
-    vec3 diffuse = light_color * vec3(color) * n_dot_l;
-    vec3 ambient = light_color * vec3(color) * 0.1;
-    gl_FragColor = select_color + vec4(diffuse + ambient, color.a) * texture2D(tex_sampler, uv);
+    vec4 albedo = base_color * color * texture2D(diffuse_sampler, uv);
+    if (albedo.a < alpha_cutoff) {
+        discard;
+    }
+
+    vec3 n = surface_normal();
+    vec3 view_direction = normalize(-position);
+
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+
+    for (int i = 0; i < MAX_LIGHTS; ++i) {
+        if (i >= light_count) {
+            break;
+        }
+
+        vec3 l;
+        float attenuation = attenuate(
+            light_position[i], light_direction[i], light_attenuation[i], light_cone[i], l);
+
+        float n_dot_l = max(dot(n, l), 0.0);
+        if (i == shadow_light && n_dot_l > 0.0) {
+            attenuation *= shadow_factor(n_dot_l);
+        }
+        diffuse += light_color[i] * n_dot_l * attenuation;
+
+        // Blinn-Phong
+        if (n_dot_l > 0.0) {
+            vec3 h = normalize(l + view_direction);
+            specular += light_color[i] * pow(max(dot(n, h), 0.0), shininess) * attenuation;
+        }
+    }
+
+    specular *= texture2D(specular_sampler, uv).r;
+    vec3 emissive = emissive_factor * texture2D(emissive_sampler, uv).rgb;
+
+    vec3 lit = (ambient_color + diffuse) * albedo.rgb + specular + emissive;
+    gl_FragColor = select_color + vec4(lit, mix(albedo.a, 1.0, opaque));
 }
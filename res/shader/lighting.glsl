@@ -0,0 +1,133 @@
+precision mediump float;
+
+// Shared by the fragment shaders of the default pipelines, which append their own main.
+// MAX_LIGHTS is defined by the pipeline when compiling, HAS_DERIVATIVES
+// when OES_standard_derivatives is available for normal mapping
+
+varying vec3 position;
+varying vec4 color;
+varying vec3 normal;
+varying vec2 uv;
+
+uniform vec4 select_color;
+
+// Material
+uniform vec4 base_color;
+uniform sampler2D diffuse_sampler;
+uniform sampler2D normal_sampler;
+uniform sampler2D emissive_sampler;
+uniform vec3 emissive_factor;
+// 0.0 when the material has no normal texture
+uniform float normal_scale;
+// Fragments with a lower alpha are discarded
+uniform float alpha_cutoff;
+// 1.0 when the material is not blended, forcing the output alpha to one
+uniform float opaque;
+
+uniform int light_count;
+// View space position, w is 0.0 for directional lights
+uniform vec4 light_position[MAX_LIGHTS];
+// View space direction the light is travelling to
+uniform vec3 light_direction[MAX_LIGHTS];
+// Color already multiplied by intensity
+uniform vec3 light_color[MAX_LIGHTS];
+// Constant, linear, quadratic
+uniform vec3 light_attenuation[MAX_LIGHTS];
+// Cosines of the inner and outer angles, outer 1.0 when not a spot
+uniform vec2 light_cone[MAX_LIGHTS];
+
+// Index of the light casting shadows, -1 when none does
+uniform int shadow_light;
+// Unpacking the shadow map needs more than the 10 bits of mantissa mediump guarantees,
+// which would drop the lower bytes of the packed depth and bring back shadow acne
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+#define SHADOW_PRECISION highp
+#else
+#define SHADOW_PRECISION mediump
+#endif
+
+// View space to light clip space
+uniform SHADOW_PRECISION mat4 shadow_matrix;
+// Depth packed into RGBA by the shadow pass
+uniform sampler2D shadow_sampler;
+uniform float shadow_bias;
+// Size of a shadow map texel in texture coordinates
+uniform float shadow_texel;
+
+SHADOW_PRECISION float unpack_depth(SHADOW_PRECISION vec4 rgba) {
+    return dot(rgba, vec4(1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0));
+}
+
+// Returns the fraction of light reaching the fragment, averaging a 3x3 area
+// of shadow map comparisons (percentage closer filtering)
+float shadow_factor(float n_dot_l) {
+    SHADOW_PRECISION vec4 coord = shadow_matrix * vec4(position, 1.0);
+    SHADOW_PRECISION vec3 ndc = coord.xyz / coord.w * 0.5 + 0.5;
+    if (ndc.x < 0.0 || ndc.x > 1.0 || ndc.y < 0.0 || ndc.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    // Grazing surfaces need a larger offset
+    SHADOW_PRECISION float bias = shadow_bias * (2.0 - n_dot_l);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; ++x) {
+        for (int y = -1; y <= 1; ++y) {
+            vec2 offset = vec2(float(x), float(y)) * shadow_texel;
+            SHADOW_PRECISION float depth = unpack_depth(texture2D(shadow_sampler, ndc.xy + offset));
+            lit += ndc.z - bias > depth ? 0.0 : 1.0;
+        }
+    }
+    return lit / 9.0;
+}
+
+// Computes the direction to a light and returns its distance and cone attenuation
+float attenuate(vec4 light_pos, vec3 light_dir, vec3 k, vec2 cone, out vec3 l) {
+    if (light_pos.w == 0.0) {
+        l = normalize(-light_dir);
+        return 1.0;
+    }
+
+    vec3 to_light = light_pos.xyz - position;
+    float distance = length(to_light);
+    l = to_light / distance;
+
+    float attenuation = 1.0 / (k.x + k.y * distance + k.z * distance * distance);
+    if (cone.y < 1.0) {
+        float cos_angle = dot(-l, normalize(light_dir));
+        attenuation *= smoothstep(cone.y, cone.x, cos_angle);
+    }
+    return attenuation;
+}
+
+vec3 perturb_normal(vec3 n) {
+#ifdef HAS_DERIVATIVES
+    if (normal_scale > 0.0) {
+        // Cotangent frame from screen space derivatives, as vertices have no tangents
+        vec3 dp1 = dFdx(position);
+        vec3 dp2 = dFdy(position);
+        vec2 duv1 = dFdx(uv);
+        vec2 duv2 = dFdy(uv);
+
+        vec3 dp2perp = cross(dp2, n);
+        vec3 dp1perp = cross(n, dp1);
+        vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+        vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+        float inv_max = inversesqrt(max(dot(t, t), dot(b, b)));
+        mat3 tbn = mat3(t * inv_max, b * inv_max, n);
+
+        vec3 mapped = texture2D(normal_sampler, uv).xyz * 2.0 - 1.0;
+        mapped.xy *= normal_scale;
+        return normalize(tbn * mapped);
+    }
+#endif
+    return n;
+}
+
+// Returns the shading normal facing the viewer
+vec3 surface_normal() {
+    vec3 n = normalize(normal);
+    if (!gl_FrontFacing) {
+        n = -n;
+    }
+    return perturb_normal(n);
+}
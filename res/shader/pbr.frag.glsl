@@ -0,0 +1,128 @@
+// Metallic-roughness physically based shading (Cook-Torrance with GGX),
+// appended to lighting.glsl. Textures follow the glTF layout
+
+const float PI = 3.14159265;
+
+uniform float metallic;
+uniform float roughness;
+// Roughness in the green channel, metalness in the blue one
+uniform sampler2D metallic_roughness_sampler;
+// Ambient occlusion in the red channel
+uniform sampler2D occlusion_sampler;
+uniform float occlusion_strength;
+
+// Environment radiance as L2 spherical harmonics in world space,
+// ordered L00, L1-1, L10, L11, L2-2, L2-1, L20, L21, L22
+uniform vec3 sh_coefficients[9];
+// Rotates view space directions to world space
+uniform mat3 view_to_world;
+
+// Irradiance for the specified world space normal (Ramamoorthi and Hanrahan)
+vec3 sh_irradiance(vec3 n) {
+    const float c1 = 0.429043;
+    const float c2 = 0.511664;
+    const float c3 = 0.743125;
+    const float c4 = 0.886227;
+    const float c5 = 0.247708;
+
+    return c1 * sh_coefficients[8] * (n.x * n.x - n.y * n.y)
+        + c3 * sh_coefficients[6] * n.z * n.z
+        + c4 * sh_coefficients[0]
+        - c5 * sh_coefficients[6]
+        + 2.0 * c1 * (sh_coefficients[4] * n.x * n.y
+            + sh_coefficients[7] * n.x * n.z
+            + sh_coefficients[5] * n.y * n.z)
+        + 2.0 * c2 * (sh_coefficients[3] * n.x
+            + sh_coefficients[1] * n.y
+            + sh_coefficients[2] * n.z);
+}
+
+// Analytical fit of the split-sum environment BRDF (Karis)
+vec2 env_brdf(float rough, float n_dot_v) {
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = rough * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    return vec2(-1.04, 1.04) * a004 + r.zw;
+}
+
+float distribution_ggx(float n_dot_h, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+// Smith visibility with the Schlick-GGX approximation, including the 1 / (4 n.l n.v) term
+float visibility_smith(float n_dot_l, float n_dot_v, float rough) {
+    float k = (rough + 1.0) * (rough + 1.0) / 8.0;
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    return g_l * g_v / max(4.0 * n_dot_l * n_dot_v, 0.0001);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+void main() {
+    vec4 albedo = base_color * color * texture2D(diffuse_sampler, uv);
+    if (albedo.a < alpha_cutoff) {
+        discard;
+    }
+
+    vec4 metal_rough = texture2D(metallic_roughness_sampler, uv);
+    float metalness = clamp(metallic * metal_rough.b, 0.0, 1.0);
+    float rough = clamp(roughness * metal_rough.g, 0.04, 1.0);
+    float alpha = rough * rough;
+
+    vec3 n = surface_normal();
+    vec3 v = normalize(-position);
+    float n_dot_v = max(dot(n, v), 0.0001);
+
+    vec3 f0 = mix(vec3(0.04), albedo.rgb, metalness);
+    vec3 diffuse_color = albedo.rgb * (1.0 - metalness);
+
+    vec3 lit = vec3(0.0);
+
+    for (int i = 0; i < MAX_LIGHTS; ++i) {
+        if (i >= light_count) {
+            break;
+        }
+
+        vec3 l;
+        float attenuation = attenuate(
+            light_position[i], light_direction[i], light_attenuation[i], light_cone[i], l);
+
+        float n_dot_l = max(dot(n, l), 0.0);
+        if (n_dot_l <= 0.0) {
+            continue;
+        }
+        if (i == shadow_light) {
+            attenuation *= shadow_factor(n_dot_l);
+        }
+
+        vec3 h = normalize(l + v);
+        float n_dot_h = max(dot(n, h), 0.0);
+        float v_dot_h = max(dot(v, h), 0.0);
+
+        vec3 f = fresnel_schlick(v_dot_h, f0);
+        vec3 specular = f * distribution_ggx(n_dot_h, alpha) * visibility_smith(n_dot_l, n_dot_v, rough);
+        vec3 diffuse = (1.0 - f) * diffuse_color / PI;
+
+        // Scaled by PI so a light lights a white surface facing it as in Blinn-Phong
+        lit += (diffuse + specular) * light_color[i] * PI * n_dot_l * attenuation;
+    }
+
+    // Image based ambient, the blurry environment of the SH standing for all roughness levels
+    vec3 world_n = view_to_world * n;
+    vec3 world_r = view_to_world * reflect(-v, n);
+    vec2 brdf = env_brdf(rough, n_dot_v);
+    vec3 ambient = sh_irradiance(world_n) / PI * diffuse_color
+        + sh_irradiance(world_r) / PI * (f0 * brdf.x + brdf.y);
+
+    float occlusion = 1.0 + occlusion_strength * (texture2D(occlusion_sampler, uv).r - 1.0);
+    vec3 emissive = emissive_factor * texture2D(emissive_sampler, uv).rgb;
+
+    lit += ambient * occlusion + emissive;
+    gl_FragColor = select_color + vec4(lit, mix(albedo.a, 1.0, opaque));
+}
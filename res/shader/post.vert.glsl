@@ -0,0 +1,8 @@
+attribute vec2 in_position;
+
+varying vec2 uv;
+
+void main() {
+    uv = in_position * 0.5 + 0.5;
+    gl_Position = vec4(in_position, 0.0, 1.0);
+}
@@ -0,0 +1,23 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D tex_sampler;
+uniform vec2 resolution;
+
+void main() {
+    vec2 texel = 1.0 / resolution;
+
+    // 3x3 gaussian kernel
+    vec4 sum = texture2D(tex_sampler, uv) * 4.0;
+    sum += texture2D(tex_sampler, uv + vec2(-texel.x, 0.0)) * 2.0;
+    sum += texture2D(tex_sampler, uv + vec2(texel.x, 0.0)) * 2.0;
+    sum += texture2D(tex_sampler, uv + vec2(0.0, -texel.y)) * 2.0;
+    sum += texture2D(tex_sampler, uv + vec2(0.0, texel.y)) * 2.0;
+    sum += texture2D(tex_sampler, uv + vec2(-texel.x, -texel.y));
+    sum += texture2D(tex_sampler, uv + vec2(texel.x, -texel.y));
+    sum += texture2D(tex_sampler, uv + vec2(-texel.x, texel.y));
+    sum += texture2D(tex_sampler, uv + vec2(texel.x, texel.y));
+
+    gl_FragColor = sum / 16.0;
+}
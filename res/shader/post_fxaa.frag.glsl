@@ -0,0 +1,57 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D tex_sampler;
+uniform vec2 resolution;
+
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_SPAN_MAX = 8.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    vec2 texel = 1.0 / resolution;
+
+    float luma_nw = luma(texture2D(tex_sampler, uv + vec2(-1.0, -1.0) * texel).rgb);
+    float luma_ne = luma(texture2D(tex_sampler, uv + vec2(1.0, -1.0) * texel).rgb);
+    float luma_sw = luma(texture2D(tex_sampler, uv + vec2(-1.0, 1.0) * texel).rgb);
+    float luma_se = luma(texture2D(tex_sampler, uv + vec2(1.0, 1.0) * texel).rgb);
+    vec4 center = texture2D(tex_sampler, uv);
+    float luma_m = luma(center.rgb);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Edge direction from the luma gradient
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+
+    float dir_reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN
+    );
+    float dir_scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * dir_scale, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+    vec3 rgb_a = 0.5 * (
+        texture2D(tex_sampler, uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture2D(tex_sampler, uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+        texture2D(tex_sampler, uv + dir * -0.5).rgb +
+        texture2D(tex_sampler, uv + dir * 0.5).rgb
+    );
+
+    float luma_b = luma(rgb_b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        gl_FragColor = vec4(rgb_a, center.a);
+    } else {
+        gl_FragColor = vec4(rgb_b, center.a);
+    }
+}
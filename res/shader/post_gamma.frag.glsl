@@ -0,0 +1,11 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D tex_sampler;
+uniform float gamma;
+
+void main() {
+    vec4 color = texture2D(tex_sampler, uv);
+    gl_FragColor = vec4(pow(color.rgb, vec3(1.0 / gamma)), color.a);
+}
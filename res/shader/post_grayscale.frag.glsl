@@ -0,0 +1,11 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D tex_sampler;
+
+void main() {
+    vec4 color = texture2D(tex_sampler, uv);
+    float luma = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+    gl_FragColor = vec4(vec3(luma), color.a);
+}
@@ -0,0 +1,14 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D tex_sampler;
+uniform float exposure;
+
+void main() {
+    vec4 color = texture2D(tex_sampler, uv);
+    // Reinhard operator
+    vec3 mapped = color.rgb * exposure;
+    mapped = mapped / (mapped + vec3(1.0));
+    gl_FragColor = vec4(mapped, color.a);
+}
@@ -0,0 +1,13 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D tex_sampler;
+uniform float strength;
+
+void main() {
+    vec4 color = texture2D(tex_sampler, uv);
+    float distance = length(uv - vec2(0.5));
+    float vignette = smoothstep(0.8, 0.25, distance * strength);
+    gl_FragColor = vec4(color.rgb * vignette, color.a);
+}
@@ -0,0 +1,13 @@
+precision highp float;
+
+// Packs depth in [0, 1] into the 8-bit channels of a color,
+// as WebGL 1 cannot render to depth textures without extensions
+vec4 pack_depth(float depth) {
+    vec4 rgba = fract(depth * vec4(1.0, 255.0, 65025.0, 16581375.0));
+    rgba -= rgba.yzww * vec4(1.0 / 255.0, 1.0 / 255.0, 1.0 / 255.0, 0.0);
+    return rgba;
+}
+
+void main() {
+    gl_FragColor = pack_depth(gl_FragCoord.z);
+}
@@ -0,0 +1,9 @@
+attribute vec3 in_position;
+
+uniform mat4 transform;
+// World to light clip space
+uniform mat4 light_matrix;
+
+void main() {
+    gl_Position = light_matrix * transform * vec4(in_position, 1.0);
+}
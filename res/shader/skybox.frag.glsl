@@ -0,0 +1,9 @@
+precision mediump float;
+
+varying vec3 direction;
+
+uniform samplerCube cubemap;
+
+void main() {
+    gl_FragColor = textureCube(cubemap, direction);
+}
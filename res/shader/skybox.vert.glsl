@@ -0,0 +1,12 @@
+attribute vec3 in_position;
+
+varying vec3 direction;
+
+// Projection times the view rotation, ignoring the camera position
+uniform mat4 view_proj;
+
+void main() {
+    direction = in_position;
+    // Always on the far plane, behind everything else
+    gl_Position = (view_proj * vec4(in_position, 1.0)).xyww;
+}
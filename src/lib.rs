@@ -1,19 +1,17 @@
 mod utils;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::Cell, cell::RefCell, rc::Rc};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use na::{Isometry3, Point3, Translation3, UnitQuaternion, Vector2, Vector3};
+use na::{Isometry3, Matrix4, Point3, Translation3, UnitQuaternion, Vector2, Vector3};
 use nalgebra as na;
 use web_sys::WebGlRenderingContext as GL;
 use web_sys::*;
 use rand::Rng;
 
 use crate::model::{*};
-mod model;
-
-use crate::gui::*;
+pub mod model;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
@@ -40,67 +38,164 @@ extern "C" {
 }
 
 // Wrap web-sys console log function in a println! style macro
+#[allow(unused_macros)]
 macro_rules! log {
     ( $( $t:tt )* ) => {
         log(&format!( $( $t )* ));
     }
 }
 
+/// Camera controller receiving the user input
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode {
+    /// Rotates around a target with mouse drag, zooms with the wheel
+    Orbit,
+    /// Moves with WASD keys and looks around with the mouse
+    Fly,
+}
+
+/// Shading model used to draw the scene
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadingModel {
+    /// Blinn-Phong lighting with a constant ambient color
+    BlinnPhong,
+    /// Metallic-roughness Cook-Torrance lighting with an environment ambient
+    Pbr,
+}
+
 #[wasm_bindgen]
 pub struct Context {
     performance: web_sys::Performance,
     canvas: HtmlCanvasElement,
     gl: WebGlRenderingContext,
-    view: Rc<RefCell<Isometry3<f32>>>,
+    camera: RefCell<Camera>,
+    orbit_camera: Rc<RefCell<OrbitCamera>>,
+    fly_camera: Rc<RefCell<FlyCamera>>,
+    camera_mode: Cell<CameraMode>,
+    keyboard: Rc<RefCell<Keyboard>>,
+    /// Animation time, following the page clock unless a fixed time step is set
+    timer: RefCell<Timer>,
     mouse: Rc<RefCell<Mouse>>,
-    offscreen_framebuffer: Framebuffer,
+    #[allow(dead_code)]
+    offscreen_framebuffer: RefCell<Framebuffer>,
+    #[allow(dead_code)]
     point_pipeline: model::PointPipeline,
     default_pipeline: model::DefaultPipeline,
+    pbr_pipeline: model::DefaultPipeline,
+    shading_model: Cell<ShadingModel>,
+    #[allow(dead_code)]
     select_pipeline: SelectPipeline,
     nodes: Vec<model::Node>,
-    texture: Texture,
+    /// Materials referenced by the nodes of a loaded scene
+    materials: Vec<Rc<Material>>,
+    /// Used by nodes without a material
+    default_material: Rc<Material>,
+    /// Textures created from JavaScript, updated with pixels or page elements
+    textures: Vec<Rc<Texture>>,
+    /// Procedural meshes referenced by the nodes, with their uploaded primitives
+    meshes: Vec<(SceneMesh, Rc<Primitive>)>,
+    lights: Vec<Light>,
+    ambient: Vector3<f32>,
+    /// Ambient environment of the physically based pipeline, derived from `ambient` when `None`
+    environment: Option<SphericalHarmonics>,
+    post_process: RefCell<PostProcess>,
+    shadow_map: RefCell<ShadowMap>,
+    /// Environment drawn behind the scene, the background is white without it
+    skybox: Option<Skybox>,
+    /// Offscreen views of the scene, each shown in its own GUI window
+    render_targets: Vec<RenderTarget>,
     gui: Rc<RefCell<Gui>>,
+
+    /// Number of primitives drawn in the main view during the last frame
+    drawn_count: Cell<u32>,
+    /// Number of primitives skipped by frustum culling in the main view during the last frame
+    culled_count: Cell<u32>,
+    /// Frame timings, rendering work and calls from JavaScript
+    stats: RefCell<Stats>,
+}
+
+/// Counts a call to an exported method in the stats, named after the method itself
+/// so a misspelled or renamed one does not compile
+macro_rules! count_call {
+    ($context:expr, $method:ident) => {{
+        let _ = Context::$method;
+        $context.stats.borrow_mut().count_call(stringify!($method));
+    }};
 }
 
 #[wasm_bindgen]
 impl Context {
 
-    fn draw_node(&self, now: f32, node: &model::Node, parent_trs: &Isometry3<f32>) {
-        node.primitive.bind();
-        self.default_pipeline.bind_attribs();
+    /// Collects the visible primitives of the subtree, counting the culled ones
+    fn collect_node<'a>(
+        &'a self,
+        node: &'a model::Node,
+        parent_trs: &Isometry3<f32>,
+        frustum: &Frustum,
+        items: &mut Vec<DrawItem<'a>>,
+        culled: &mut u32,
+    ) {
+        let transform = parent_trs * node.transform;
+
+        // Skip the whole subtree when its bounds are out of view
+        if let Some(bounds) = node.bounds.as_ref() {
+            if !frustum.intersects(&bounds.transform(&transform)) {
+                *culled += node.primitive_count();
+                return;
+            }
+        }
+
+        // Nodes without geometry only contribute their transform to the children
+        if let Some(primitive) = node.primitive.as_ref() {
+            let visible = primitive
+                .aabb
+                .is_none_or(|aabb| frustum.intersects(&aabb.transform(&transform)));
+
+            if visible {
+                items.push(DrawItem {
+                    node,
+                    primitive,
+                    material: node.material.as_ref().unwrap_or(&self.default_material),
+                    transform,
+                });
+            } else {
+                *culled += 1;
+            }
+        }
 
+        for child in &node.children {
+            self.collect_node(child, &transform, frustum, items, culled);
+        }
+    }
+
+    fn draw_primitive(&self, item: &DrawItem, view: &Isometry3<f32>) {
         // Select color
-        let select_color_loc = self
-            .default_pipeline
-            .program
-            .get_uniform_loc("select_color");
+        let pipeline = self.pipeline();
+        let select_color_loc = pipeline.program.get_uniform_loc("select_color");
         let select_color = match self.mouse.borrow().selected_node {
-            Some(node_id) if node_id == node.id => [0.4f32, 0.4, 0.1, 0.0],
+            Some(node_id) if node_id == item.node.id => [0.4f32, 0.4, 0.1, 0.0],
             _ => [0.0f32, 0.0, 0.0, 0.0],
         };
         self.gl
             .uniform4fv_with_f32_array(select_color_loc.as_ref(), &select_color);
 
-        let transform = parent_trs * node.transform;
-
         self.gl.uniform_matrix4fv_with_f32_array(
-            self.default_pipeline.transform_loc.as_ref(),
+            pipeline.transform_loc.as_ref(),
             false,
-            transform.to_homogeneous().as_slice(),
+            item.transform.to_homogeneous().as_slice(),
         );
 
-        let normal_transform = transform.inverse().to_homogeneous().transpose();
+        // Lighting is computed in view space
+        let normal_transform = (view * item.transform).inverse().to_homogeneous().transpose();
         self.gl.uniform_matrix4fv_with_f32_array(
-            self.default_pipeline.normal_transform_loc.as_ref(),
+            pipeline.normal_transform_loc.as_ref(),
             false,
             normal_transform.as_slice(),
         );
 
-        node.primitive.draw();
-
-        for child in &node.children {
-            self.draw_node(now, child, &transform);
-        }
+        item.primitive.draw();
     }
 
     pub fn new() -> Result<Context, JsValue> {
@@ -113,27 +208,42 @@ impl Context {
         //let offscreen_framebuffer =
         //create_offscreen_framebuffer(&gl, canvas.width() as i32, canvas.height() as i32);
 
-        let select_framebuffer =
-            create_select_framebuffer(&gl, canvas.width() as i32, canvas.height() as i32);
+        let select_framebuffer = Framebuffer::new(gl.clone(), canvas.width(), canvas.height())?;
+
+        let post_process = PostProcess::new(&gl, canvas.width(), canvas.height())?;
+        let shadow_map = ShadowMap::new(&gl, ShadowMap::DEFAULT_SIZE)?;
 
         let point_pipeline = create_point_program(&gl);
-        let default_pipeline = create_default_program(&gl);
+        let max_lights = DefaultPipeline::DEFAULT_MAX_LIGHTS
+            .min(DefaultPipeline::max_supported_lights(&gl))
+            .max(1);
+        let default_pipeline = create_default_program(&gl, max_lights);
+        let pbr_pipeline = create_pbr_program(&gl, max_lights);
         let mut select_pipeline = SelectPipeline::new(&gl);
 
-        // OpenGL uses a right-handed coordinate system
-        let view = Rc::new(RefCell::new(Isometry3::look_at_rh(
-            &Point3::new(0.0, 0.0, 3.0),
-            &Point3::origin(),
-            &Vector3::y_axis(),
-        )));
+        let camera = Camera::new(canvas.width(), canvas.height());
+        let orbit_camera = Rc::new(RefCell::new(OrbitCamera::new(Point3::origin(), 3.0)));
+        let fly_camera = Rc::new(RefCell::new(FlyCamera::new(Point3::new(0.0, 0.0, 3.0))));
+
+        let mouse = Rc::new(RefCell::new(Mouse::new()));
+        register_mouse_events(&canvas, &mouse)?;
+
+        let keyboard = Rc::new(RefCell::new(Keyboard::new()));
+        register_keyboard_events(&keyboard)?;
+
+        let timer = Timer::new(Box::new(RealTimeClock::new(performance.clone())));
 
         let mut nodes = vec![];
 
         let cube = Geometry::cube();
 
-        let mut root = model::Node::new(model::Primitive::new(gl.clone(), &cube));
+        let cube_primitive = Rc::new(model::Primitive::new(gl.clone(), &cube));
+        let meshes = vec![(SceneMesh::Cube, cube_primitive.clone())];
+
+        let mut root = model::Node::new(cube_primitive);
         root.transform
             .append_translation_mut(&Translation3::new(0.0, 0.0, 0.0));
+        root.update_bounds();
 
         // Create select color for each node
         let mut rng = rand::thread_rng();
@@ -141,7 +251,8 @@ impl Context {
 
         nodes.push(root);
 
-        let texture = model::Texture::new(gl.clone());
+        let texture = Rc::new(model::Texture::new(gl.clone()));
+        let default_material = Rc::new(Material::from_texture(texture));
 
         // @todo Extract to function: Create GUI
         let gui = Gui::new(&gl, canvas.width(), canvas.height());
@@ -150,16 +261,37 @@ impl Context {
             performance,
             canvas,
             gl,
-            view,
-            mouse: Rc::new(RefCell::new(Mouse::new())),
-            offscreen_framebuffer: select_framebuffer,
+            camera: RefCell::new(camera),
+            orbit_camera,
+            fly_camera,
+            camera_mode: Cell::new(CameraMode::Orbit),
+            keyboard,
+            timer: RefCell::new(timer),
+            mouse,
+            offscreen_framebuffer: RefCell::new(select_framebuffer),
             point_pipeline,
             default_pipeline,
+            pbr_pipeline,
+            shading_model: Cell::new(ShadingModel::BlinnPhong),
             select_pipeline,
             nodes,
-            texture,
+            materials: vec![],
+            default_material,
+            textures: vec![],
+            meshes,
+            lights: vec![Light::default()],
+            ambient: Vector3::new(0.1, 0.1, 0.1),
+            environment: None,
+            post_process: RefCell::new(post_process),
+            shadow_map: RefCell::new(shadow_map),
+            skybox: None,
+            render_targets: vec![],
 
             gui: Rc::new(RefCell::new(gui)),
+
+            drawn_count: Cell::new(0),
+            culled_count: Cell::new(0),
+            stats: RefCell::new(Stats::default()),
         };
 
         Ok(ret)
@@ -167,77 +299,1118 @@ impl Context {
 
     /// Draws the scene
     pub fn draw(&self) -> Result<(), JsValue> {
+        count_call!(self, draw);
+        self.update_and_draw()
+    }
 
-        // After using input, reset its state
-        self.mouse.borrow_mut().reset();
+    /// Returns frame timings over the latest frames, the work of the last frame,
+    /// the GPU memory allocated and the calls made from JavaScript
+    pub fn get_stats(&self) -> Result<JsValue, JsValue> {
+        count_call!(self, get_stats);
+        let json = self.stats.borrow().to_json().map_err(|e| JsValue::from_str(&e))?;
+        js_sys::JSON::parse(&json)
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.borrow_mut().reset();
+        count_call!(self, reset_stats);
+    }
+
+    /// Sets the number of latest frames the timings are computed over
+    pub fn set_stats_window(&self, frames: usize) {
+        count_call!(self, set_stats_window);
+        self.stats.borrow_mut().set_window(frames);
+    }
+
+    /// Freezes the animations, the camera can still be moved
+    pub fn pause(&self) {
+        count_call!(self, pause);
+        self.timer.borrow_mut().pause();
+    }
+
+    pub fn resume(&self) {
+        count_call!(self, resume);
+        self.timer.borrow_mut().resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        count_call!(self, is_paused);
+        self.timer.borrow().is_paused()
+    }
+
+    /// Pauses and advances the animations by a single step on the next frame
+    pub fn step_frame(&self) {
+        count_call!(self, step_frame);
+        self.timer.borrow_mut().step_frame();
+    }
+
+    /// Sets the animation speed, below `1.0` for slow motion
+    pub fn set_time_scale(&self, scale: f64) {
+        count_call!(self, set_time_scale);
+        self.timer.borrow_mut().scale = scale;
+    }
+
+    pub fn get_time_scale(&self) -> f64 {
+        count_call!(self, get_time_scale);
+        self.timer.borrow().scale
+    }
+
+    /// Advances the animations by `step` milliseconds every frame whatever the frame rate,
+    /// or follows the page clock again with `None`
+    pub fn set_fixed_time_step(&self, step: Option<f64>) {
+        count_call!(self, set_fixed_time_step);
+        let mut timer = self.timer.borrow_mut();
+        match step {
+            Some(step) => {
+                timer.step = step;
+                timer.max_delta = timer.max_delta.max(step);
+                timer.set_clock(Box::new(ManualClock::new(0.0, step)));
+            }
+            None => timer.set_clock(Box::new(RealTimeClock::new(self.performance.clone()))),
+        }
+    }
+
+    /// Returns the animation time in milliseconds
+    pub fn get_time(&self) -> f64 {
+        count_call!(self, get_time);
+        self.timer.borrow().elapsed()
+    }
+
+    pub fn set_time(&self, time: f64) {
+        count_call!(self, set_time);
+        self.timer.borrow_mut().set_elapsed(time);
+    }
+
+    /// Draws `count` frames at the simulated times `start`, `start + step`, ... in milliseconds
+    /// and returns them as a list of PNGs
+    pub fn record_frames(
+        &self,
+        count: u32,
+        start: f64,
+        step: f64,
+    ) -> Result<js_sys::Array, JsValue> {
+        count_call!(self, record_frames);
+        let frames = js_sys::Array::new();
+        for frame in self.record(count, start, step)? {
+            frames.push(&js_sys::Uint8Array::from(frame.to_png()?.as_slice()));
+        }
+        Ok(frames)
+    }
+
+    /// Draws frames like `record_frames` and returns them as a looping animated PNG
+    /// showing each frame for `step` milliseconds
+    pub fn record_apng(
+        &self,
+        count: u32,
+        start: f64,
+        step: f64,
+    ) -> Result<js_sys::Uint8Array, JsValue> {
+        count_call!(self, record_apng);
+        let frames = self.record(count, start, step)?;
+        let apng = encode_apng(&frames, step)?;
+        Ok(js_sys::Uint8Array::from(apng.as_slice()))
+    }
+
+    /// Returns a description of the current scene as JSON
+    pub fn export_scene(&self) -> Result<String, JsValue> {
+        count_call!(self, export_scene);
+        self.scene().to_json().map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Returns a description of the current scene as RON
+    pub fn export_scene_ron(&self) -> Result<String, JsValue> {
+        count_call!(self, export_scene_ron);
+        self.scene().to_ron().map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Replaces the current scene with the one described by the JSON or RON source
+    pub fn load_scene(&mut self, src: &str) -> Result<(), JsValue> {
+        count_call!(self, load_scene);
+        let scene = Scene::parse(src).map_err(|e| JsValue::from_str(&e))?;
+
+        self.meshes = scene
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let primitive = Primitive::new(self.gl.clone(), &mesh.geometry());
+                (*mesh, Rc::new(primitive))
+            })
+            .collect();
+
+        self.materials = scene
+            .materials
+            .iter()
+            .map(|material| Rc::new(Material::from(material)))
+            .collect();
+
+        self.nodes = scene.nodes.iter().map(|node| self.create_node(node)).collect();
+
+        let mut rng = rand::thread_rng();
+        self.select_pipeline.node_colors.clear();
+        for node in &mut self.nodes {
+            node.update_bounds();
+            generate_node_colors(&mut self.select_pipeline, &mut rng, node);
+        }
+
+        self.lights = scene.lights.iter().map(Light::from).collect();
+        self.ambient = Vector3::from(scene.ambient);
+
+        // Already validated when parsed
+        self.camera
+            .borrow_mut()
+            .set_projection(scene.camera.projection, scene.camera.near, scene.camera.far)?;
+
+        let eye = Point3::from(scene.camera.eye);
+        let target = Point3::from(scene.camera.target);
+        self.orbit_camera.borrow_mut().look_at(&eye, &target);
+        self.fly_camera.borrow_mut().look_at(&eye, &target);
+
+        // Render targets are only shown by the windows being removed
+        self.render_targets.clear();
+        let mut gui = self.gui.borrow_mut();
+        gui.clear_windows();
+        for window in &scene.windows {
+            gui.add_window(model::Window::from(window));
+        }
+
+        Ok(())
+    }
+
+    /// Resizes the canvas drawing buffer to the size of the canvas element,
+    /// updating everything depending on it
+    pub fn resize(&self) -> Result<(), JsValue> {
+        count_call!(self, resize);
+        self.resize_canvas()
+    }
+
+    /// Uses a perspective projection with the specified vertical field of view in radians
+    pub fn set_perspective(&self, fov: f32, near: f32, far: f32) -> Result<(), JsValue> {
+        count_call!(self, set_perspective);
+        self.camera
+            .borrow_mut()
+            .set_projection(Projection::Perspective { fov }, near, far)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Uses an orthographic projection showing the specified height in world units
+    pub fn set_orthographic(&self, height: f32, near: f32, far: f32) -> Result<(), JsValue> {
+        count_call!(self, set_orthographic);
+        self.camera
+            .borrow_mut()
+            .set_projection(Projection::Orthographic { height }, near, far)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Sets how close and far the orbit camera can get from its target,
+    /// and how low and high it can rotate, in radians
+    pub fn set_orbit_limits(
+        &self,
+        min_distance: f32,
+        max_distance: f32,
+        min_pitch: f32,
+        max_pitch: f32,
+    ) {
+        count_call!(self, set_orbit_limits);
+        let mut camera = self.orbit_camera.borrow_mut();
+        camera.min_distance = min_distance;
+        camera.max_distance = max_distance;
+        camera.min_pitch = min_pitch;
+        camera.max_pitch = max_pitch;
+    }
+
+    /// Sets the fraction of the orbit camera motion kept after a sixtieth of a second,
+    /// clamped to `[0, OrbitCamera::MAX_DAMPING]`
+    pub fn set_orbit_damping(&self, damping: f32) {
+        count_call!(self, set_orbit_damping);
+        self.orbit_camera.borrow_mut().set_damping(damping);
+    }
+
+    /// Switches the camera driven by the input, keeping the current point of view
+    pub fn set_camera_mode(&self, mode: CameraMode) {
+        count_call!(self, set_camera_mode);
+        if mode == self.camera_mode.get() {
+            return;
+        }
+
+        let (eye, target) = self.eye_and_target();
+        match mode {
+            CameraMode::Orbit => self.orbit_camera.borrow_mut().look_at(&eye, &target),
+            CameraMode::Fly => self.fly_camera.borrow_mut().look_at(&eye, &target),
+        }
+        self.camera_mode.set(mode);
+    }
+
+    pub fn get_camera_mode(&self) -> CameraMode {
+        count_call!(self, get_camera_mode);
+        self.camera_mode.get()
+    }
+
+    /// Sets the fly camera speed in units per second
+    pub fn set_fly_speed(&self, speed: f32) {
+        count_call!(self, set_fly_speed);
+        self.fly_camera.borrow_mut().speed = speed;
+    }
+
+    /// Locks the pointer to the canvas, so that mouse look is not limited by the screen borders
+    pub fn request_pointer_lock(&self) {
+        count_call!(self, request_pointer_lock);
+        self.canvas.request_pointer_lock();
+    }
+
+    /// Adds a light with default parameters, returning its index
+    pub fn add_light(&mut self, kind: LightKind) -> usize {
+        count_call!(self, add_light);
+        self.lights.push(Light::new(kind));
+        self.lights.len() - 1
+    }
+
+    pub fn remove_light(&mut self, index: usize) -> Result<(), JsValue> {
+        count_call!(self, remove_light);
+        self.get_light(index)?;
+        self.lights.remove(index);
+        Ok(())
+    }
+
+    pub fn clear_lights(&mut self) {
+        count_call!(self, clear_lights);
+        self.lights.clear();
+    }
+
+    pub fn light_count(&self) -> usize {
+        count_call!(self, light_count);
+        self.lights.len()
+    }
+
+    pub fn set_light_position(&mut self, index: usize, x: f32, y: f32, z: f32) -> Result<(), JsValue> {
+        count_call!(self, set_light_position);
+        self.get_light(index)?.position = Point3::new(x, y, z);
+        Ok(())
+    }
+
+    /// Sets the direction the light is travelling to
+    pub fn set_light_direction(&mut self, index: usize, x: f32, y: f32, z: f32) -> Result<(), JsValue> {
+        count_call!(self, set_light_direction);
+        let direction = Vector3::new(x, y, z)
+            .try_normalize(f32::EPSILON)
+            .ok_or_else(|| JsValue::from_str("Invalid light direction"))?;
+        self.get_light(index)?.direction = direction;
+        Ok(())
+    }
+
+    pub fn set_light_color(
+        &mut self,
+        index: usize,
+        r: f32,
+        g: f32,
+        b: f32,
+        intensity: f32,
+    ) -> Result<(), JsValue> {
+        count_call!(self, set_light_color);
+        let light = self.get_light(index)?;
+        light.color = Vector3::new(r, g, b);
+        light.intensity = intensity;
+        Ok(())
+    }
+
+    /// Sets the constant, linear and quadratic distance attenuation factors
+    pub fn set_light_attenuation(
+        &mut self,
+        index: usize,
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    ) -> Result<(), JsValue> {
+        count_call!(self, set_light_attenuation);
+        self.get_light(index)?.attenuation = Vector3::new(constant, linear, quadratic);
+        Ok(())
+    }
+
+    /// Sets the spot light cone, angles in radians from its axis
+    pub fn set_light_cone(&mut self, index: usize, inner_angle: f32, outer_angle: f32) -> Result<(), JsValue> {
+        count_call!(self, set_light_cone);
+        let light = self.get_light(index)?;
+        light.inner_angle = inner_angle.min(outer_angle);
+        light.outer_angle = outer_angle;
+        Ok(())
+    }
+
+    pub fn set_ambient(&mut self, r: f32, g: f32, b: f32) {
+        count_call!(self, set_ambient);
+        self.ambient = Vector3::new(r, g, b);
+    }
+
+    /// Sets the ambient environment of the physically based pipeline
+    /// from 27 values, the RGB radiance of each L2 spherical harmonics coefficient.
+    /// An empty array goes back to the constant ambient color
+    pub fn set_environment_sh(&mut self, coefficients: &[f32]) -> Result<(), JsValue> {
+        count_call!(self, set_environment_sh);
+        self.environment = if coefficients.is_empty() {
+            None
+        } else {
+            let sh = SphericalHarmonics::from_slice(coefficients)
+                .ok_or_else(|| JsValue::from_str("Expected 27 spherical harmonics values"))?;
+            Some(sh)
+        };
+        Ok(())
+    }
+
+    /// Draws a skybox behind the scene from six encoded image faces: +X, -X, +Y, -Y, +Z, -Z
+    pub fn set_skybox(
+        &mut self,
+        px: &[u8],
+        nx: &[u8],
+        py: &[u8],
+        ny: &[u8],
+        pz: &[u8],
+        nz: &[u8],
+    ) -> Result<(), JsValue> {
+        count_call!(self, set_skybox);
+        let faces = [px, nx, py, ny, pz, nz]
+            .iter()
+            .map(|data| Image::decode(data))
+            .collect::<Result<Vec<Image>, ImageError>>()?;
+        let cubemap =
+            Cubemap::from_images(self.gl.clone(), &faces).map_err(|e| JsValue::from_str(&e))?;
+        self.skybox = Some(Skybox::new(&self.gl, cubemap));
+        Ok(())
+    }
+
+    /// Draws a skybox behind the scene from an equirectangular encoded image panorama,
+    /// resampled into cubemap faces of the specified size
+    pub fn set_skybox_equirectangular(&mut self, data: &[u8], size: u32) -> Result<(), JsValue> {
+        count_call!(self, set_skybox_equirectangular);
+        let panorama = Image::decode(data)?;
+        let cubemap = Cubemap::from_equirectangular(self.gl.clone(), &panorama, size)
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.skybox = Some(Skybox::new(&self.gl, cubemap));
+        Ok(())
+    }
+
+    /// Goes back to the white background
+    pub fn clear_skybox(&mut self) {
+        count_call!(self, clear_skybox);
+        self.skybox = None;
+    }
+
+    /// Recompiles the default shaders for the specified maximum number of lights,
+    /// lights past this count are ignored. Fails without recompiling when the lights
+    /// do not fit in the fragment uniforms of the device
+    pub fn set_max_lights(&mut self, max_lights: usize) -> Result<(), JsValue> {
+        count_call!(self, set_max_lights);
+        let max_lights = max_lights.max(1);
+        let supported = DefaultPipeline::max_supported_lights(&self.gl);
+        if max_lights > supported {
+            return Err(JsValue::from_str(&format!(
+                "Only {} lights fit in the fragment shader uniforms, {} requested",
+                supported, max_lights
+            )));
+        }
+        if max_lights != self.default_pipeline.max_lights {
+            self.default_pipeline = create_default_program(&self.gl, max_lights);
+            self.pbr_pipeline = create_pbr_program(&self.gl, max_lights);
+        }
+        Ok(())
+    }
+
+    pub fn set_shading_model(&self, model: ShadingModel) {
+        count_call!(self, set_shading_model);
+        self.shading_model.set(model);
+    }
+
+    pub fn get_shading_model(&self) -> ShadingModel {
+        count_call!(self, get_shading_model);
+        self.shading_model.get()
+    }
+
+    /// Enables or disables a post-processing effect.
+    /// Tone mapping only runs when the scene is rendered in HDR, see `is_post_hdr`
+    pub fn set_post_effect(&self, effect: PostEffect, enabled: bool) {
+        count_call!(self, set_post_effect);
+        self.post_process.borrow_mut().set_enabled(effect, enabled);
+    }
+
+    pub fn is_post_effect_enabled(&self, effect: PostEffect) -> bool {
+        count_call!(self, is_post_effect_enabled);
+        self.post_process.borrow().is_enabled(effect)
+    }
+
+    /// Returns whether the scene is rendered to half floats before post-processing,
+    /// keeping colors brighter than one for tone mapping
+    pub fn is_post_hdr(&self) -> bool {
+        count_call!(self, is_post_hdr);
+        self.post_process.borrow().is_hdr()
+    }
 
+    /// Sets the exposure of tone mapping, the gamma of gamma correction or the vignette strength
+    pub fn set_post_effect_param(&self, effect: PostEffect, value: f32) {
+        count_call!(self, set_post_effect_param);
+        self.post_process.borrow_mut().set_param(effect, value);
+    }
+
+    /// Adds a GUI window showing the scene rendered from another point of view,
+    /// looking down at the origin by default. Returns the index of the render target
+    pub fn add_render_window(&mut self, name: &str, width: u32, height: u32) -> Result<usize, JsValue> {
+        count_call!(self, add_render_window);
+        let mut window = model::Window::new(width, height);
+        window.name = String::from(name);
+
+        let mut gui = self.gui.borrow_mut();
+        let size = gui.get_content_size(&window);
+        if size.x == 0 || size.y == 0 {
+            return Err(JsValue::from_str(&format!(
+                "Render window of {}x{} is too small to show anything",
+                width, height
+            )));
+        }
+        let target = RenderTarget::new(self.gl.clone(), size.x, size.y)?;
+
+        window.image = Some(GuiImage::new(target.texture().handle.clone()));
+        gui.add_window(window);
+
+        self.render_targets.push(target);
+        Ok(self.render_targets.len() - 1)
+    }
+
+    pub fn get_render_target_count(&self) -> usize {
+        count_call!(self, get_render_target_count);
+        self.render_targets.len()
+    }
+
+    /// Creates a mipmapped texture from an encoded PNG, JPEG, BMP, TGA, PPM or PGM image,
+    /// returning its index
+    pub fn load_texture(&mut self, data: &[u8]) -> Result<usize, JsValue> {
+        count_call!(self, load_texture);
+        let image = Image::decode(data)?;
+        let texture = Texture::from_image_with_sampler(self.gl.clone(), &image, Sampler::mipmapped());
+        self.textures.push(Rc::new(texture));
+        Ok(self.textures.len() - 1)
+    }
+
+    /// Creates an RGBA texture JavaScript can update, returning its index
+    pub fn create_texture(&mut self, width: u32, height: u32) -> Result<usize, JsValue> {
+        count_call!(self, create_texture);
+        let texture = Texture::empty(
+            self.gl.clone(),
+            PixelFormat::Rgba,
+            TexelType::UnsignedByte,
+            width,
+            height,
+            Sampler::new(),
+        )?;
+        self.textures.push(Rc::new(texture));
+        Ok(self.textures.len() - 1)
+    }
+
+    /// Replaces a region of a texture with RGBA pixels
+    pub fn update_texture_region(
+        &self,
+        index: usize,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), JsValue> {
+        count_call!(self, update_texture_region);
+        self.get_texture(index)?
+            .update_region(x, y, width, height, pixels)?;
+        Ok(())
+    }
+
+    /// Copies the content of a canvas to the top-left corner of a texture
+    pub fn update_texture_from_canvas(&self, index: usize, canvas: &HtmlCanvasElement) -> Result<(), JsValue> {
+        count_call!(self, update_texture_from_canvas);
+        self.update_texture_from_source(index, &TextureSource::Canvas(canvas))
+    }
+
+    /// Copies an image element to the top-left corner of a texture
+    pub fn update_texture_from_image(&self, index: usize, image: &HtmlImageElement) -> Result<(), JsValue> {
+        count_call!(self, update_texture_from_image);
+        self.update_texture_from_source(index, &TextureSource::Image(image))
+    }
+
+    /// Copies image data to the top-left corner of a texture
+    pub fn update_texture_from_image_data(&self, index: usize, data: &ImageData) -> Result<(), JsValue> {
+        count_call!(self, update_texture_from_image_data);
+        self.update_texture_from_source(index, &TextureSource::ImageData(data))
+    }
+
+    /// Copies the current frame of a video to the top-left corner of a texture
+    pub fn update_texture_from_video(&self, index: usize, video: &HtmlVideoElement) -> Result<(), JsValue> {
+        count_call!(self, update_texture_from_video);
+        self.update_texture_from_source(index, &TextureSource::Video(video))
+    }
+
+    /// Draws a root node with a new material using the texture as diffuse
+    pub fn set_node_texture(&mut self, node: usize, texture: usize) -> Result<(), JsValue> {
+        count_call!(self, set_node_texture);
+        let texture = self.get_texture(texture)?.clone();
+        let node = self
+            .nodes
+            .get_mut(node)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid node: {}", node)))?;
+        node.material = Some(Rc::new(Material::from_texture(texture)));
+        Ok(())
+    }
+
+    /// Sets the texture of one slot of a root node material, clearing it when `texture` is `None`.
+    /// A node without a material gets a copy of the default one first
+    pub fn set_node_material_texture(
+        &mut self,
+        node: usize,
+        slot: TextureSlot,
+        texture: Option<usize>,
+    ) -> Result<(), JsValue> {
+        count_call!(self, set_node_material_texture);
+        let texture = match texture {
+            Some(index) => Some(self.get_texture(index)?.clone()),
+            None => None,
+        };
+        let default_material = &self.default_material;
+        let node = self
+            .nodes
+            .get_mut(node)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid node: {}", node)))?;
+        let mut material = node.material.as_deref().unwrap_or(default_material.as_ref()).clone();
+        material.set_texture(slot, texture);
+        node.material = Some(Rc::new(material));
+        Ok(())
+    }
+
+    /// Adds a GUI window showing a texture
+    pub fn add_texture_window(&mut self, texture: usize, name: &str, width: u32, height: u32) -> Result<(), JsValue> {
+        count_call!(self, add_texture_window);
+        let handle = self.get_texture(texture)?.handle.clone();
+
+        let mut window = model::Window::new(width, height);
+        window.name = String::from(name);
+        window.image = Some(GuiImage::new(handle));
+        self.gui.borrow_mut().add_window(window);
+        Ok(())
+    }
+
+    /// Adds an encoded image to the GUI atlas, which windows can show in their title bar
+    pub fn add_gui_icon(&self, name: &str, data: &[u8]) -> Result<(), JsValue> {
+        count_call!(self, add_gui_icon);
+        let image = Image::decode(data)?;
+        self.gui.borrow_mut().add_icon(name, image)?;
+        Ok(())
+    }
+
+    /// Shows an icon of the GUI atlas in the title bar of a window, or none
+    pub fn set_window_icon(&self, window: usize, icon: Option<String>) -> Result<(), JsValue> {
+        count_call!(self, set_window_icon);
+        let mut gui = self.gui.borrow_mut();
+        if let Some(icon) = icon.as_ref() {
+            if !gui.has_icon(icon) {
+                return Err(JsValue::from_str(&format!("Invalid icon: {}", icon)));
+            }
+        }
+        let window = gui
+            .windows
+            .get_mut(window)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid window: {}", window)))?;
+        window.icon = icon;
+        Ok(())
+    }
+
+    /// Moves the point of view of a render target
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_render_target_view(
+        &mut self,
+        index: usize,
+        eye_x: f32,
+        eye_y: f32,
+        eye_z: f32,
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+    ) -> Result<(), JsValue> {
+        count_call!(self, set_render_target_view);
+        let target = self.get_render_target(index)?;
+        target
+            .look_at(
+                Point3::new(eye_x, eye_y, eye_z),
+                Point3::new(target_x, target_y, target_z),
+            )
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Uses a perspective projection for a render target
+    pub fn set_render_target_perspective(&mut self, index: usize, fov: f32) -> Result<(), JsValue> {
+        count_call!(self, set_render_target_perspective);
+        let camera = &mut self.get_render_target(index)?.camera;
+        let (near, far) = (camera.near, camera.far);
+        camera
+            .set_projection(Projection::Perspective { fov }, near, far)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Uses an orthographic projection for a render target, useful for minimaps
+    pub fn set_render_target_orthographic(&mut self, index: usize, height: f32) -> Result<(), JsValue> {
+        count_call!(self, set_render_target_orthographic);
+        let camera = &mut self.get_render_target(index)?.camera;
+        let (near, far) = (camera.near, camera.far);
+        camera
+            .set_projection(Projection::Orthographic { height }, near, far)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Draws a frame at the current animation time, without advancing it, and returns it
+    /// encoded as PNG. The canvas is read back right away, as the browser clears it
+    /// once the frame is presented
+    pub fn screenshot(&self) -> Result<js_sys::Uint8Array, JsValue> {
+        count_call!(self, screenshot);
+        self.follow_canvas_size()?;
+        let image = self.read_frame(self.timer.borrow().elapsed())?;
+        Ok(js_sys::Uint8Array::from(image.to_png()?.as_slice()))
+    }
+
+    /// Returns the last frame drawn by a render target encoded as PNG
+    pub fn screenshot_render_target(&self, index: usize) -> Result<js_sys::Uint8Array, JsValue> {
+        count_call!(self, screenshot_render_target);
+        let target = self
+            .render_targets
+            .get(index)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid render target: {}", index)))?;
+        let image = target.framebuffer.read_image()?;
+        Ok(js_sys::Uint8Array::from(image.to_png()?.as_slice()))
+    }
+
+    /// Returns the number of primitives drawn in the main view during the last frame,
+    /// render targets excluded
+    pub fn drawn_count(&self) -> u32 {
+        count_call!(self, drawn_count);
+        self.drawn_count.get()
+    }
+
+    /// Returns the number of primitives culled from the main view during the last frame,
+    /// render targets excluded
+    pub fn culled_count(&self) -> u32 {
+        count_call!(self, culled_count);
+        self.culled_count.get()
+    }
+
+    /// Enables or disables shadows cast by the first directional or spot light
+    pub fn set_shadows_enabled(&self, enabled: bool) {
+        count_call!(self, set_shadows_enabled);
+        self.shadow_map.borrow_mut().enabled = enabled;
+    }
+
+    /// Sets the depth offset applied when comparing with the shadow map,
+    /// higher values avoid shadow acne but detach shadows from their casters
+    pub fn set_shadow_bias(&self, bias: f32) {
+        count_call!(self, set_shadow_bias);
+        self.shadow_map.borrow_mut().bias = bias;
+    }
+
+    /// Sets the width and height of the shadow map in texels
+    pub fn set_shadow_resolution(&self, size: u32) -> Result<(), JsValue> {
+        count_call!(self, set_shadow_resolution);
+        self.shadow_map.borrow_mut().resize(size)?;
+        Ok(())
+    }
+
+    /// Returns the number of materials of the loaded scene
+    pub fn material_count(&self) -> usize {
+        count_call!(self, material_count);
+        self.materials.len()
+    }
+}
+
+impl Context {
+    /// Follows the canvas size, handles input and draws a frame at the current time
+    fn update_and_draw(&self) -> Result<(), JsValue> {
+        let start = self.performance.now();
+        self.follow_canvas_size()?;
+
+        let (time, real_delta) = {
+            let mut timer = self.timer.borrow_mut();
+            timer.tick();
+            (timer.elapsed(), timer.real_delta())
+        };
+
+        // The camera follows the clock, so the scene can be looked around while paused
+        self.handle_input((real_delta / 1000.0) as f32);
+
+        self.draw_frame(time);
+
+        self.stats.borrow_mut().end_frame(start, self.performance.now());
+        Ok(())
+    }
+
+    /// Follows the size of the canvas element set by the page layout
+    fn follow_canvas_size(&self) -> Result<(), JsValue> {
+        if self.canvas.client_width() as u32 != self.canvas.width()
+            || self.canvas.client_height() as u32 != self.canvas.height()
+        {
+            self.resize_canvas()?;
+        }
+        Ok(())
+    }
+
+    fn resize_canvas(&self) -> Result<(), JsValue> {
+        let width = (self.canvas.client_width() as u32).max(1);
+        let height = (self.canvas.client_height() as u32).max(1);
+
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+        self.gl.viewport(0, 0, width as i32, height as i32);
+
+        self.camera.borrow_mut().resize(width, height);
+        self.gui.borrow_mut().resize(width, height);
+
+        self.offscreen_framebuffer
+            .borrow_mut()
+            .resize(width, height)?;
+        self.post_process.borrow_mut().resize(width, height)?;
+
+        Ok(())
+    }
+
+    /// Draws every pass of a frame with the animations at the specified time in milliseconds
+    fn draw_frame(&self, time: f64) {
+        let mut transform = Isometry3::<f32>::identity();
+        let rotation =
+            UnitQuaternion::<f32>::from_axis_angle(&Vector3::z_axis(), time as f32 / 4096.0);
+        transform.append_rotation_mut(&rotation);
+        let rotation =
+            UnitQuaternion::<f32>::from_axis_angle(&Vector3::y_axis(), time as f32 / 4096.0);
+        transform.append_rotation_mut(&rotation);
+
+        self.draw_shadow_map(&transform);
+
+        // Offscreen passes first, so their textures are ready for the GUI
+        for target in &self.render_targets {
+            let _binding = target.framebuffer.bind();
+            self.draw_scene(&target.view(), &target.camera.proj(), &transform);
+        }
+
+        let proj = self.camera.borrow().proj();
+        let post_process = self.post_process.borrow();
+        // Only the main view is counted, render targets show the same objects again
+        let (drawn, culled) = if post_process.is_active() {
+            let counts = {
+                let _binding = post_process.begin();
+                self.draw_scene(&self.view(), &proj, &transform)
+            };
+            post_process.apply();
+            counts
+        } else {
+            self.draw_scene(&self.view(), &proj, &transform)
+        };
+        self.drawn_count.set(drawn);
+        self.culled_count.set(culled);
+
+        self.gui.borrow().draw();
+    }
+
+    /// Draws frames at a fixed time step, reading each one back before drawing the next
+    fn record(&self, count: u32, start: f64, step: f64) -> Result<Vec<Image>, JsValue> {
+        (0..count)
+            .map(|i| self.read_frame(start + i as f64 * step))
+            .collect()
+    }
+
+    /// Draws a frame at the specified time and reads it back. It is not one of the frames
+    /// timed by the stats, so its render counters are dropped instead of adding to the next one
+    fn read_frame(&self, time: f64) -> Result<Image, JsValue> {
+        self.draw_frame(time);
+        let image = read_pixels(&self.gl, self.canvas.width(), self.canvas.height());
+        stats::take_render_counters();
+        Ok(image?)
+    }
+
+    fn get_light(&mut self, index: usize) -> Result<&mut Light, JsValue> {
+        self.lights
+            .get_mut(index)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid light: {}", index)))
+    }
+
+    fn get_texture(&self, index: usize) -> Result<&Rc<Texture>, JsValue> {
+        self.textures
+            .get(index)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid texture: {}", index)))
+    }
+
+    fn update_texture_from_source(&self, index: usize, source: &TextureSource) -> Result<(), JsValue> {
+        self.get_texture(index)?.update_from_source(0, 0, source)?;
+        Ok(())
+    }
+
+    fn get_render_target(&mut self, index: usize) -> Result<&mut RenderTarget, JsValue> {
+        self.render_targets
+            .get_mut(index)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid render target: {}", index)))
+    }
+
+    /// Returns the pipeline of the active shading model
+    fn pipeline(&self) -> &DefaultPipeline {
+        match self.shading_model.get() {
+            ShadingModel::BlinnPhong => &self.default_pipeline,
+            ShadingModel::Pbr => &self.pbr_pipeline,
+        }
+    }
+
+    /// Renders the depth of the scene from the main light into the shadow map
+    fn draw_shadow_map(&self, transform: &Isometry3<f32>) {
+        let bounds = self
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let node_transform = transform * node.transform;
+                node.bounds.map(|bounds| bounds.transform(&node_transform))
+            })
+            .reduce(|a, b| a.merge(&b))
+            .unwrap_or_else(|| {
+                Aabb::new(Point3::new(-8.0, -8.0, -8.0), Point3::new(8.0, 8.0, 8.0))
+            });
+
+        let mut shadow_map = self.shadow_map.borrow_mut();
+        let max_lights = self.default_pipeline.max_lights;
+        let _binding = match shadow_map.begin(&self.lights, max_lights, &bounds) {
+            Some(binding) => binding,
+            None => return,
+        };
+
+        let frustum = Frustum::from_matrix(&shadow_map.light_matrix);
+        for node in &self.nodes {
+            draw_shadow_node(&shadow_map, node, transform, &frustum);
+        }
+    }
+
+    /// Draws all the nodes from the specified point of view into the bound framebuffer,
+    /// returning the number of primitives drawn and culled
+    fn draw_scene(
+        &self,
+        view: &Isometry3<f32>,
+        proj: &Matrix4<f32>,
+        transform: &Isometry3<f32>,
+    ) -> (u32, u32) {
         // Set graphics state
-        self.gl.enable(GL::DEPTH_TEST);
-        self.gl.enable(GL::BLEND);
-        self.gl.blend_func(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA);
+        gl_state::enable(&self.gl, GL::DEPTH_TEST);
+        gl_state::enable(&self.gl, GL::BLEND);
+        gl_state::blend_func(&self.gl, GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA);
 
-        self.default_pipeline.program.bind();
+        let pipeline = self.pipeline();
+        pipeline.program.bind();
 
         // View
-        let view_loc = self.default_pipeline.program.get_uniform_loc("view");
+        let view_loc = pipeline.program.get_uniform_loc("view");
 
         self.gl.uniform_matrix4fv_with_f32_array(
             view_loc.as_ref(),
             false,
-            self.view.borrow().to_homogeneous().as_slice(),
+            view.to_homogeneous().as_slice(),
         );
 
         // Proj
-        let proj_loc = self.default_pipeline.program.get_uniform_loc("proj");
+        let proj_loc = pipeline.program.get_uniform_loc("proj");
 
-        let width = self.canvas.width() as f32;
-        let height = self.canvas.height() as f32;
-        let proj = nalgebra::Perspective3::new(width / height, 3.14 / 4.0, 0.125, 256.0);
-        self.gl.uniform_matrix4fv_with_f32_array(
-            proj_loc.as_ref(),
-            false,
-            proj.to_homogeneous().as_slice(),
-        );
-
-        // Lighting
-        let light_color_loc = self.default_pipeline.program.get_uniform_loc("light_color");
-        self.gl.uniform3f(light_color_loc.as_ref(), 1.0, 1.0, 1.0);
-
-        let light_position_loc = self
-            .default_pipeline
-            .program
-            .get_uniform_loc("light_position");
         self.gl
-            .uniform3f(light_position_loc.as_ref(), 4.0, 1.0, 1.0);
+            .uniform_matrix4fv_with_f32_array(proj_loc.as_ref(), false, proj.as_slice());
 
-        // Texture
-        self.texture.bind();
-        let sampler_loc = self.default_pipeline.program.get_uniform_loc("tex_sampler");
-        self.gl.uniform1i(sampler_loc.as_ref(), 0);
+        let frustum = Frustum::from_matrix(&(proj * view.to_homogeneous()));
+
+        // Lighting
+        pipeline.set_lights(&self.lights, view);
+        pipeline.set_ambient(&self.ambient);
+        let environment = self
+            .environment
+            .unwrap_or_else(|| SphericalHarmonics::from_ambient(&self.ambient));
+        pipeline.set_environment(&environment, view);
+        pipeline.set_shadow(&self.shadow_map.borrow(), view);
 
         self.gl.clear_color(1.0, 1.0, 1.0, 1.0);
         self.gl.clear(GL::COLOR_BUFFER_BIT);
         self.gl.clear(GL::DEPTH_BUFFER_BIT);
 
-        // Time
-        let now = self.performance.now();
+        let mut items = vec![];
+        let mut culled = 0;
+        for node in &self.nodes {
+            self.collect_node(node, transform, &frustum, &mut items, &mut culled);
+        }
 
-        let mut transform = Isometry3::<f32>::identity();
-        let rotation =
-            UnitQuaternion::<f32>::from_axis_angle(&Vector3::z_axis(), now as f32 / 4096.0);
-        transform.append_rotation_mut(&rotation);
-        let rotation =
-            UnitQuaternion::<f32>::from_axis_angle(&Vector3::y_axis(), now as f32 / 4096.0);
-        transform.append_rotation_mut(&rotation);
+        // Opaque primitives grouped by material and geometry to minimize state changes,
+        // then blended ones from back to front
+        items.sort_by(|a, b| {
+            let a_blended = a.material.is_blended();
+            let b_blended = b.material.is_blended();
+            a_blended.cmp(&b_blended).then_with(|| {
+                if a_blended {
+                    a.view_depth(view).total_cmp(&b.view_depth(view))
+                } else {
+                    (Rc::as_ptr(a.material), Rc::as_ptr(a.primitive))
+                        .cmp(&(Rc::as_ptr(b.material), Rc::as_ptr(b.primitive)))
+                }
+            })
+        });
+
+        let blended = items.partition_point(|item| !item.material.is_blended());
+        self.draw_items(&items[..blended], view);
+
+        // The skybox fills what opaque geometry left empty, and shows through blended one
+        if let Some(skybox) = self.skybox.as_ref() {
+            skybox.draw(view, proj);
+            pipeline.program.bind();
+        }
 
-        // Draw all nodes
-        for node in &self.nodes {
-            self.draw_node(now as f32, &node, &transform);
+        self.draw_items(&items[blended..], view);
+
+        // Leave the state expected by the GUI and post-processing
+        gl_state::disable(&self.gl, GL::CULL_FACE);
+        gl_state::depth_mask(&self.gl, true);
+        gl_state::enable(&self.gl, GL::BLEND);
+
+        (items.len() as u32, culled)
+    }
+
+    /// Draws the items in order, changing material and geometry only when they differ
+    fn draw_items(&self, items: &[DrawItem], view: &Isometry3<f32>) {
+        let pipeline = self.pipeline();
+        let mut material: Option<&Rc<Material>> = None;
+        let mut primitive: Option<&Rc<Primitive>> = None;
+        for item in items {
+            if material.is_none_or(|material| !Rc::ptr_eq(material, item.material)) {
+                pipeline.set_material(item.material);
+                material = Some(item.material);
+            }
+            if primitive.is_none_or(|primitive| !Rc::ptr_eq(primitive, item.primitive)) {
+                item.primitive.bind();
+                pipeline.bind_attribs();
+                primitive = Some(item.primitive);
+            }
+            self.draw_primitive(item, view);
         }
+    }
 
-        self.gui.borrow().draw();
+    /// Feeds mouse input to the GUI first, then to the active camera, `delta` being the frame
+    /// time in seconds
+    fn handle_input(&self, delta: f32) {
+        let mut mouse = self.mouse.borrow_mut();
+        let mut gui = self.gui.borrow_mut();
 
-        Ok(())
+        let pointer_locked = self.is_pointer_locked();
+        let handled = !pointer_locked && gui.handle_mouse(&mouse);
+
+        // Buttons pressed over the GUI do not move the camera until released
+        if mouse.pressed {
+            mouse.captured = handled || (!pointer_locked && gui.contains(&mouse));
+        }
+
+        match self.camera_mode.get() {
+            CameraMode::Orbit => {
+                let mut camera = self.orbit_camera.borrow_mut();
+                if !mouse.captured {
+                    let drag = Vector2::new(mouse.drag.x as f32, mouse.drag.y as f32);
+                    if mouse.left_down {
+                        camera.rotate(drag);
+                    } else if mouse.right_down || mouse.middle_down {
+                        camera.pan(drag);
+                    }
+                }
+                if !gui.contains(&mouse) {
+                    camera.zoom(mouse.wheel);
+                }
+                camera.update(delta);
+            }
+            CameraMode::Fly => {
+                let mut camera = self.fly_camera.borrow_mut();
+                if pointer_locked {
+                    let movement = Vector2::new(mouse.movement.x as f32, mouse.movement.y as f32);
+                    camera.look(movement);
+                } else if !mouse.captured && mouse.left_down {
+                    let drag = Vector2::new(mouse.drag.x as f32, mouse.drag.y as f32);
+                    camera.look(drag);
+                }
+                camera.update(&self.keyboard.borrow(), delta);
+            }
+        }
+
+        // After using input, reset its state
+        mouse.reset();
+    }
+
+    fn is_pointer_locked(&self) -> bool {
+        window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.pointer_lock_element())
+            .is_some_and(|element| element == **self.canvas)
+    }
+
+    /// Returns the world to view transform of the active camera
+    fn view(&self) -> Isometry3<f32> {
+        match self.camera_mode.get() {
+            CameraMode::Orbit => self.orbit_camera.borrow().view(),
+            CameraMode::Fly => self.fly_camera.borrow().view(),
+        }
+    }
+
+    fn eye_and_target(&self) -> (Point3<f32>, Point3<f32>) {
+        match self.camera_mode.get() {
+            CameraMode::Orbit => {
+                let camera = self.orbit_camera.borrow();
+                (camera.eye(), camera.target)
+            }
+            CameraMode::Fly => {
+                let camera = self.fly_camera.borrow();
+                (camera.position, camera.target())
+            }
+        }
+    }
+
+    fn scene(&self) -> Scene {
+        Scene {
+            version: Scene::VERSION,
+            meshes: self.meshes.iter().map(|(mesh, _)| *mesh).collect(),
+            materials: self.materials.iter().map(|material| SceneMaterial::from(&**material)).collect(),
+            nodes: self.nodes.iter().map(|node| self.scene_node(node)).collect(),
+            lights: self.lights.iter().map(SceneLight::from).collect(),
+            ambient: self.ambient.into(),
+            camera: {
+                let (eye, target) = self.eye_and_target();
+                SceneCamera::new(&self.camera.borrow(), &eye, &target)
+            },
+            windows: self
+                .gui
+                .borrow()
+                .windows
+                .iter()
+                .map(SceneWindow::from)
+                .collect(),
+        }
+    }
+
+    fn scene_node(&self, node: &model::Node) -> SceneNode {
+        let mesh = node.primitive.as_ref().and_then(|primitive| {
+            self.meshes
+                .iter()
+                .position(|(_, mesh_primitive)| Rc::ptr_eq(mesh_primitive, primitive))
+        });
+
+        let material = node.material.as_ref().and_then(|material| {
+            self.materials
+                .iter()
+                .position(|scene_material| Rc::ptr_eq(scene_material, material))
+        });
+
+        SceneNode {
+            transform: SceneTransform::from(&node.transform),
+            mesh,
+            material,
+            children: node.children.iter().map(|child| self.scene_node(child)).collect(),
+        }
+    }
+
+    fn create_node(&self, desc: &SceneNode) -> model::Node {
+        let mut node = match desc.mesh {
+            Some(mesh) => model::Node::new(self.meshes[mesh].1.clone()),
+            None => model::Node::empty(),
+        };
+        node.material = desc.material.map(|material| self.materials[material].clone());
+        node.transform = Isometry3::from(&desc.transform);
+        node.children = desc.children.iter().map(|child| self.create_node(child)).collect();
+        node
     }
 }
 
@@ -248,10 +1421,68 @@ fn create_point_program(gl: &WebGlRenderingContext) -> PointPipeline {
     PointPipeline::new(gl, vert_src, frag_src)
 }
 
-fn create_default_program(gl: &WebGlRenderingContext) -> DefaultPipeline {
+fn create_default_program(gl: &WebGlRenderingContext, max_lights: usize) -> DefaultPipeline {
+    let vert_src = include_str!("../res/shader/default.vert.glsl");
+    let frag_src = [
+        include_str!("../res/shader/lighting.glsl"),
+        include_str!("../res/shader/default.frag.glsl"),
+    ]
+    .concat();
+    DefaultPipeline::new(gl, vert_src, &frag_src, max_lights)
+}
+
+fn create_pbr_program(gl: &WebGlRenderingContext, max_lights: usize) -> DefaultPipeline {
     let vert_src = include_str!("../res/shader/default.vert.glsl");
-    let frag_src = include_str!("../res/shader/default.frag.glsl");
-    DefaultPipeline::new(gl, vert_src, frag_src)
+    let frag_src = [
+        include_str!("../res/shader/lighting.glsl"),
+        include_str!("../res/shader/pbr.frag.glsl"),
+    ]
+    .concat();
+    DefaultPipeline::new(gl, vert_src, &frag_src, max_lights)
+}
+
+/// Draws the subtree into the shadow map, skipping what the light cannot see
+/// and blended primitives which do not block light
+fn draw_shadow_node(
+    shadow_map: &ShadowMap,
+    node: &model::Node,
+    parent_trs: &Isometry3<f32>,
+    frustum: &Frustum,
+) {
+    let transform = parent_trs * node.transform;
+
+    if let Some(bounds) = node.bounds.as_ref() {
+        if !frustum.intersects(&bounds.transform(&transform)) {
+            return;
+        }
+    }
+
+    if let Some(primitive) = node.primitive.as_ref() {
+        if !node.material.as_ref().is_some_and(|material| material.is_blended()) {
+            shadow_map.draw(primitive, &transform);
+        }
+    }
+
+    for child in &node.children {
+        draw_shadow_node(shadow_map, child, &transform, frustum);
+    }
+}
+
+/// Visible primitive collected before drawing, so draws can be sorted
+struct DrawItem<'a> {
+    node: &'a model::Node,
+    primitive: &'a Rc<Primitive>,
+    material: &'a Rc<Material>,
+    /// Node to world transform
+    transform: Isometry3<f32>,
+}
+
+impl DrawItem<'_> {
+    /// Distance along the view direction, growing towards the camera
+    fn view_depth(&self, view: &Isometry3<f32>) -> f32 {
+        let center = self.primitive.aabb.map_or(Point3::origin(), |aabb| aabb.center());
+        (view * self.transform * center).z
+    }
 }
 
 type Color = [u8; 3];
@@ -269,50 +1500,78 @@ fn generate_node_colors(
     }
 }
 
-fn create_select_framebuffer(gl: &GL, width: i32, height: i32) -> Framebuffer {
-    // @todo Create a framebuffer object
-    let select_framebuffer = gl.create_framebuffer();
-    gl.bind_framebuffer(GL::FRAMEBUFFER, select_framebuffer.as_ref());
-
-    // @todo Create a texture object
-    let mut texture = Texture::new(gl.clone());
-    texture.upload(None, width as u32, height as u32);
-    gl.bind_texture(GL::TEXTURE_2D, None);
-
-    gl.framebuffer_texture_2d(
-        GL::FRAMEBUFFER,
-        GL::COLOR_ATTACHMENT0,
-        GL::TEXTURE_2D,
-        Some(&texture.handle),
-        0,
-    );
-
-    // @todo Check error checkframebuffer
-
-    let select_depthbuffer = gl.create_renderbuffer();
-    gl.bind_renderbuffer(GL::RENDERBUFFER, select_depthbuffer.as_ref());
-    gl.renderbuffer_storage(GL::RENDERBUFFER, GL::DEPTH_COMPONENT16, width, height);
-    gl.framebuffer_renderbuffer(
-        GL::FRAMEBUFFER,
-        GL::DEPTH_ATTACHMENT,
-        GL::RENDERBUFFER,
-        select_depthbuffer.as_ref(),
-    );
-
-    let e = gl.check_framebuffer_status(GL::FRAMEBUFFER);
-    if e != GL::FRAMEBUFFER_COMPLETE {
-        log("Framebuffer error");
-    }
-
-    // Unbind
-    gl.bind_framebuffer(GL::FRAMEBUFFER, None);
-
-    Framebuffer {
-        frame: select_framebuffer,
-        color: None,
-        depth: select_depthbuffer,
-        texture: Some(texture),
-    }
+/// Forwards the mouse events of the canvas to the mouse state
+fn register_mouse_events(canvas: &HtmlCanvasElement, mouse: &Rc<RefCell<Mouse>>) -> Result<(), JsValue> {
+    let on_button = {
+        let mouse = mouse.clone();
+        Closure::wrap(Box::new(move |event: MouseEvent| {
+            let mut mouse = mouse.borrow_mut();
+            mouse.move_to(event.offset_x(), event.offset_y());
+            mouse.movement += Vector2::new(event.movement_x(), event.movement_y());
+            mouse.set_buttons(event.buttons());
+        }) as Box<dyn FnMut(MouseEvent)>)
+    };
+    canvas.add_event_listener_with_callback("mousedown", on_button.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("mouseup", on_button.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("mousemove", on_button.as_ref().unchecked_ref())?;
+    on_button.forget();
+
+    let on_wheel = {
+        let mouse = mouse.clone();
+        Closure::wrap(Box::new(move |event: WheelEvent| {
+            event.prevent_default();
+            mouse.borrow_mut().wheel += event.delta_y() as f32;
+        }) as Box<dyn FnMut(WheelEvent)>)
+    };
+    canvas.add_event_listener_with_callback("wheel", on_wheel.as_ref().unchecked_ref())?;
+    on_wheel.forget();
+
+    // Right drag pans the camera instead of opening the menu
+    let on_context_menu = Closure::wrap(Box::new(move |event: Event| {
+        event.prevent_default();
+    }) as Box<dyn FnMut(Event)>);
+    canvas.add_event_listener_with_callback(
+        "contextmenu",
+        on_context_menu.as_ref().unchecked_ref(),
+    )?;
+    on_context_menu.forget();
+
+    Ok(())
+}
+
+/// Tracks the keys held down in the whole page
+fn register_keyboard_events(keyboard: &Rc<RefCell<Keyboard>>) -> Result<(), JsValue> {
+    let window = window().unwrap();
+
+    let on_key_down = {
+        let keyboard = keyboard.clone();
+        Closure::wrap(Box::new(move |event: KeyboardEvent| {
+            keyboard.borrow_mut().press(event.code());
+        }) as Box<dyn FnMut(KeyboardEvent)>)
+    };
+    window.add_event_listener_with_callback("keydown", on_key_down.as_ref().unchecked_ref())?;
+    on_key_down.forget();
+
+    let on_key_up = {
+        let keyboard = keyboard.clone();
+        Closure::wrap(Box::new(move |event: KeyboardEvent| {
+            keyboard.borrow_mut().release(&event.code());
+        }) as Box<dyn FnMut(KeyboardEvent)>)
+    };
+    window.add_event_listener_with_callback("keyup", on_key_up.as_ref().unchecked_ref())?;
+    on_key_up.forget();
+
+    // Key releases are not received while the page has no focus
+    let on_blur = {
+        let keyboard = keyboard.clone();
+        Closure::wrap(Box::new(move |_: Event| {
+            keyboard.borrow_mut().release_all();
+        }) as Box<dyn FnMut(Event)>)
+    };
+    window.add_event_listener_with_callback("blur", on_blur.as_ref().unchecked_ref())?;
+    on_blur.forget();
+
+    Ok(())
 }
 
 fn get_gl_context(canvas: &HtmlCanvasElement) -> Result<GL, JsValue> {
@@ -326,7 +1585,7 @@ fn get_canvas(id: &str) -> Result<HtmlCanvasElement, JsValue> {
     let doc = window().unwrap().document().unwrap();
     let canvas = doc
         .get_element_by_id(id)
-        .expect(&format!("Failed to get canvas: {}", id));
+        .unwrap_or_else(|| panic!("Failed to get canvas: {}", id));
     let canvas: HtmlCanvasElement = canvas.dyn_into::<HtmlCanvasElement>()?;
     canvas.set_width(canvas.client_width() as u32);
     canvas.set_height(canvas.client_height() as u32);
@@ -11,9 +11,32 @@ pub mod mouse;
 pub mod frame_buffer;
 pub mod gui;
 pub mod image;
+pub mod image_format;
+pub mod aabb;
+pub mod frustum;
+pub mod light;
+pub mod scene;
+pub mod orbit_camera;
+pub mod fly_camera;
+pub mod keyboard;
+pub mod camera;
+pub mod render_target;
+pub mod post_process;
+pub mod material;
+pub mod shadow_map;
+pub mod spherical_harmonics;
+pub mod cubemap;
+pub mod skybox;
+pub mod sampler;
+pub mod atlas;
+pub mod apng;
+pub mod clock;
+pub mod stats;
+pub mod gl_state;
+pub mod half_float;
 
 pub use self::node::Node;
-pub use self::texture::Texture;
+pub use self::texture::{TexelType, Texture, TextureError, TextureSource};
 pub use self::program::Program;
 pub use self::point_pipeline::PointPipeline;
 pub use self::default_pipeline::DefaultPipeline;
@@ -22,6 +45,28 @@ pub use self::select_pipeline::SelectPipeline;
 pub use self::vertex::Vertex;
 pub use self::geometry::Geometry;
 pub use self::mouse::Mouse;
-pub use self::frame_buffer::Framebuffer;
+pub use self::frame_buffer::{read_pixels, Framebuffer, FramebufferBinding, FramebufferError};
 pub use self::gui::{*};
-pub use self::image::Image;
\ No newline at end of file
+pub use self::image::{Image, ImageError, PixelFormat};
+pub use self::image_format::ImageFormat;
+pub use self::aabb::Aabb;
+pub use self::frustum::Frustum;
+pub use self::light::{Light, LightKind};
+pub use self::scene::{Scene, SceneCamera, SceneLight, SceneMaterial, SceneMesh, SceneNode, SceneTransform, SceneWindow};
+pub use self::orbit_camera::OrbitCamera;
+pub use self::fly_camera::FlyCamera;
+pub use self::keyboard::Keyboard;
+pub use self::camera::{Camera, Projection};
+pub use self::render_target::RenderTarget;
+pub use self::post_process::{PostEffect, PostProcess};
+pub use self::material::{AlphaMode, Material, TextureSlot};
+pub use self::shadow_map::ShadowMap;
+pub use self::spherical_harmonics::SphericalHarmonics;
+pub use self::cubemap::Cubemap;
+pub use self::skybox::Skybox;
+pub use self::sampler::{Filter, Sampler, Wrap};
+pub use self::atlas::{Atlas, AtlasBuilder, AtlasPacker, Rect, UvRect};
+pub use self::apng::encode_apng;
+pub use self::clock::{Clock, ManualClock, RealTimeClock, Timer};
+pub use self::stats::{CallCounts, GpuMemory, RenderCounters, Stats, StatsReport, TimeSummary, TimeWindow};
+pub use self::half_float::f32_to_f16;
\ No newline at end of file
@@ -0,0 +1,54 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the smallest box containing all the specified points,
+    /// or `None` when there are no points at all
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        let mut aabb = Self::new(first, first);
+        for point in points {
+            aabb.min = aabb.min.inf(&point);
+            aabb.max = aabb.max.sup(&point);
+        }
+
+        Some(aabb)
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    /// Half size of the box along each axis
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Returns the smallest box containing both this and the other box
+    pub fn merge(&self, other: &Aabb) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    /// Returns the box enclosing this one after being moved by the specified transform
+    pub fn transform(&self, transform: &Isometry3<f32>) -> Self {
+        let center = transform * self.center();
+
+        // Project the extents on the rotated axes (Arvo's method)
+        let rotation = transform.rotation.to_rotation_matrix();
+        let extents = rotation.matrix().abs() * self.extents();
+
+        Self::new(center - extents, center + extents)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,120 @@
+use crate::model::{Image, ImageError};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// Encodes frames of the same size as an animated PNG looping forever, each frame
+/// shown for the specified duration. The first frame is also the default image
+/// displayed by decoders without animation support
+pub fn encode_apng(frames: &[Image], frame_duration_ms: f64) -> Result<Vec<u8>, ImageError> {
+    let first = frames
+        .first()
+        .ok_or_else(|| ImageError::EncodingFailed(String::from("No frames to encode")))?;
+    let (width, height) = (first.width, first.height);
+    if let Some(frame) = frames.iter().find(|f| f.width != width || f.height != height) {
+        return Err(ImageError::EncodingFailed(format!(
+            "Frame of {}x{} in an animation of {}x{}",
+            frame.width, frame.height, width, height
+        )));
+    }
+
+    let (delay_num, delay_den) = frame_delay(frame_duration_ms);
+
+    let mut apng = SIGNATURE.to_vec();
+    let mut sequence = 0u32;
+
+    for (index, frame) in frames.iter().enumerate() {
+        // Every frame is encoded as RGBA so they all match the same header
+        let png = frame.to_rgba().to_png()?;
+        let chunks = read_chunks(&png)?;
+
+        if index == 0 {
+            let header = chunks
+                .iter()
+                .find(|(kind, _)| kind == b"IHDR")
+                .ok_or_else(|| ImageError::EncodingFailed(String::from("Missing IHDR")))?;
+            write_chunk(&mut apng, b"IHDR", header.1);
+
+            let mut control = vec![];
+            control.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+            // Zero plays means looping forever
+            control.extend_from_slice(&0u32.to_be_bytes());
+            write_chunk(&mut apng, b"acTL", &control);
+        }
+
+        let mut control = vec![];
+        control.extend_from_slice(&sequence.to_be_bytes());
+        control.extend_from_slice(&width.to_be_bytes());
+        control.extend_from_slice(&height.to_be_bytes());
+        // Offsets
+        control.extend_from_slice(&0u32.to_be_bytes());
+        control.extend_from_slice(&0u32.to_be_bytes());
+        control.extend_from_slice(&delay_num.to_be_bytes());
+        control.extend_from_slice(&delay_den.to_be_bytes());
+        // Frames cover the whole image, no disposal nor blending is needed
+        control.push(0);
+        control.push(0);
+        write_chunk(&mut apng, b"fcTL", &control);
+        sequence += 1;
+
+        for (_, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+            if index == 0 {
+                write_chunk(&mut apng, b"IDAT", data);
+            } else {
+                let mut frame_data = sequence.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(data);
+                write_chunk(&mut apng, b"fdAT", &frame_data);
+                sequence += 1;
+            }
+        }
+    }
+
+    write_chunk(&mut apng, b"IEND", &[]);
+    Ok(apng)
+}
+
+/// Converts a duration to the fraction of seconds stored in frame controls,
+/// keeping tenths of a millisecond when they fit in 16 bits
+fn frame_delay(duration_ms: f64) -> (u16, u16) {
+    let duration_ms = duration_ms.max(0.0);
+    let tenths = (duration_ms * 10.0).round();
+    if tenths <= u16::MAX as f64 {
+        (tenths as u16, 10000)
+    } else {
+        ((duration_ms.round()).min(u16::MAX as f64) as u16, 1000)
+    }
+}
+
+/// Type and data of a PNG chunk
+type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits an encoded PNG into its chunks
+fn read_chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>, ImageError> {
+    let invalid = || ImageError::EncodingFailed(String::from("Encoder wrote an invalid PNG"));
+    if !png.starts_with(&SIGNATURE) {
+        return Err(invalid());
+    }
+
+    let mut chunks = vec![];
+    let mut offset = SIGNATURE.len();
+    while offset < png.len() {
+        let header = png.get(offset..offset + 8).ok_or_else(invalid)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let data = png.get(offset + 8..offset + 8 + length).ok_or_else(invalid)?;
+        chunks.push((kind, data));
+        // Length, type, data and CRC
+        offset += 12 + length;
+    }
+    Ok(chunks)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    out.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
\ No newline at end of file
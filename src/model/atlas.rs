@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use crate::model::Image;
+
+/// Area of an image in pixels, the origin being its top-left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Area of a texture in normalized texture coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl UvRect {
+    /// Maps coordinates relative to this area, from `[0, 1]`, to texture coordinates
+    pub fn map(&self, uv: [f32; 2]) -> [f32; 2] {
+        [
+            self.min[0] + (self.max[0] - self.min[0]) * uv[0],
+            self.min[1] + (self.max[1] - self.min[1]) * uv[1],
+        ]
+    }
+}
+
+/// Rectangle bin packer placing each rectangle as low as possible on a skyline,
+/// the top edge of everything placed so far
+pub struct AtlasPacker {
+    width: u32,
+    height: u32,
+    /// Horizontal segments as `(x, y, width)`, sorted by `x` and covering the whole width
+    skyline: Vec<(u32, u32, u32)>,
+}
+
+impl AtlasPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            skyline: vec![(0, 0, width)],
+        }
+    }
+
+    /// Returns where a rectangle of the specified size fits, or `None` when it does not
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<Rect> {
+        let mut best: Option<(usize, u32, u32)> = None;
+
+        for i in 0..self.skyline.len() {
+            if let Some(y) = self.fit(i, width, height) {
+                let x = self.skyline[i].0;
+                if best.is_none_or(|(_, best_y, best_x)| (y, x) < (best_y, best_x)) {
+                    best = Some((i, y, x));
+                }
+            }
+        }
+
+        let (index, y, x) = best?;
+        self.place(index, x, y + height, width);
+        Some(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Returns the lowest position of a rectangle starting at the segment, if it fits
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].0;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = width as i64;
+        for &(_, segment_y, segment_width) in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(segment_y);
+            remaining -= segment_width as i64;
+        }
+
+        if y + height > self.height {
+            None
+        } else {
+            Some(y)
+        }
+    }
+
+    /// Raises the skyline where a rectangle has been placed
+    fn place(&mut self, index: usize, x: u32, top: u32, width: u32) {
+        self.skyline.insert(index, (x, top, width));
+
+        // Shrink or remove the segments now below the new one
+        let end = x + width;
+        let i = index + 1;
+        while i < self.skyline.len() {
+            let (segment_x, segment_y, segment_width) = self.skyline[i];
+            if segment_x >= end {
+                break;
+            }
+            let segment_end = segment_x + segment_width;
+            if segment_end <= end {
+                self.skyline.remove(i);
+            } else {
+                self.skyline[i] = (end, segment_y, segment_end - end);
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            let (a_x, a_y, a_width) = self.skyline[i];
+            let (_, b_y, b_width) = self.skyline[i + 1];
+            if a_y == b_y {
+                self.skyline[i] = (a_x, a_y, a_width + b_width);
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Single RGBA image made of many named images
+pub struct Atlas {
+    pub image: Image,
+    rects: HashMap<String, Rect>,
+}
+
+impl Atlas {
+    /// Largest width and height the builder tries before giving up
+    pub const MAX_SIZE: u32 = 4096;
+
+    pub fn get_rect(&self, name: &str) -> Option<Rect> {
+        self.rects.get(name).copied()
+    }
+
+    /// Returns the texture coordinates of an image in the atlas
+    pub fn get_uv(&self, name: &str) -> Option<UvRect> {
+        let rect = self.rects.get(name)?;
+        let width = self.image.width as f32;
+        let height = self.image.height as f32;
+        Some(UvRect {
+            min: [rect.x as f32 / width, rect.y as f32 / height],
+            max: [
+                (rect.x + rect.width) as f32 / width,
+                (rect.y + rect.height) as f32 / height,
+            ],
+        })
+    }
+
+    /// Returns the coordinates of the center of an image, sampling a single color
+    /// for images of one pixel whatever the filtering
+    pub fn get_center_uv(&self, name: &str) -> Option<UvRect> {
+        let center = self.get_uv(name)?.map([0.5, 0.5]);
+        Some(UvRect {
+            min: center,
+            max: center,
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.rects.keys()
+    }
+}
+
+/// Collects named images to pack into an atlas
+pub struct AtlasBuilder {
+    images: Vec<(String, Image)>,
+    /// Empty pixels between images, avoiding filtering bleeding from neighbours
+    pub padding: u32,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            images: vec![],
+            padding: 1,
+        }
+    }
+
+    /// Adds an image, replacing any previous image with the same name
+    pub fn add(&mut self, name: &str, image: Image) {
+        self.images.retain(|(other, _)| other != name);
+        self.images.push((String::from(name), image));
+    }
+
+    /// Packs all the images into the smallest power of two atlas it can find
+    pub fn build(&self) -> Result<Atlas, String> {
+        // Tall images first leave a flatter skyline
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| {
+            let image = &self.images[i].1;
+            std::cmp::Reverse((image.height, image.width))
+        });
+
+        let padded = |image: &Image| (image.width + self.padding, image.height + self.padding);
+        let area: u32 = self
+            .images
+            .iter()
+            .map(|(_, image)| {
+                let (width, height) = padded(image);
+                width * height
+            })
+            .sum();
+        let max_width = self.images.iter().map(|(_, image)| padded(image).0).max();
+        let max_height = self.images.iter().map(|(_, image)| padded(image).1).max();
+
+        let mut width = max_width.unwrap_or(1).next_power_of_two();
+        let mut height = max_height.unwrap_or(1).next_power_of_two();
+        while width * height < area {
+            if width <= height {
+                width *= 2;
+            } else {
+                height *= 2;
+            }
+        }
+
+        while width <= Atlas::MAX_SIZE && height <= Atlas::MAX_SIZE {
+            if let Some(rects) = self.pack(&order, width, height) {
+                return Ok(self.blit(rects, width, height));
+            }
+            if width <= height {
+                width *= 2;
+            } else {
+                height *= 2;
+            }
+        }
+
+        Err(format!(
+            "Images do not fit in a {}x{} atlas",
+            Atlas::MAX_SIZE,
+            Atlas::MAX_SIZE
+        ))
+    }
+
+    fn pack(&self, order: &[usize], width: u32, height: u32) -> Option<Vec<Rect>> {
+        let mut packer = AtlasPacker::new(width, height);
+        let mut rects = vec![
+            Rect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0
+            };
+            self.images.len()
+        ];
+
+        for &i in order {
+            let image = &self.images[i].1;
+            let rect = packer.pack(image.width + self.padding, image.height + self.padding)?;
+            rects[i] = Rect {
+                width: image.width,
+                height: image.height,
+                ..rect
+            };
+        }
+
+        Some(rects)
+    }
+
+    fn blit(&self, rects: Vec<Rect>, width: u32, height: u32) -> Atlas {
+        let mut data = vec![0u8; (width * height * 4) as usize];
+
+        for ((_, image), rect) in self.images.iter().zip(&rects) {
+            let image = image.to_rgba();
+            let row_len = (rect.width * 4) as usize;
+            for row in 0..rect.height {
+                let src = (row * rect.width * 4) as usize;
+                let dst = (((rect.y + row) * width + rect.x) * 4) as usize;
+                data[dst..dst + row_len].copy_from_slice(&image.data[src..src + row_len]);
+            }
+        }
+
+        let rects = self
+            .images
+            .iter()
+            .zip(rects)
+            .map(|((name, _), rect)| (name.clone(), rect))
+            .collect();
+
+        Atlas {
+            image: Image::from_vec(data, width, height),
+            rects,
+        }
+    }
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
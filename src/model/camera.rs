@@ -0,0 +1,105 @@
+use nalgebra::{Matrix4, Orthographic3, Perspective3};
+use serde::{Deserialize, Serialize};
+
+/// How the camera maps the view space onto the screen
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in radians
+    Perspective { fov: f32 },
+    /// Height of the visible area in world units
+    Orthographic { height: f32 },
+}
+
+impl Projection {
+    /// Checks the projection can be built with the specified depth range,
+    /// as nalgebra asserts on a degenerate one
+    pub fn validate(&self, near: f32, far: f32) -> Result<(), String> {
+        if !(near > 0.0 && near < far && far.is_finite()) {
+            return Err(format!("Invalid depth range: near {} and far {}", near, far));
+        }
+        match *self {
+            Projection::Perspective { fov } if !(fov > 0.0 && fov < std::f32::consts::PI) => {
+                Err(format!("Invalid field of view: {}", fov))
+            }
+            Projection::Orthographic { height } if !(height > 0.0 && height.is_finite()) => {
+                Err(format!("Invalid orthographic height: {}", height))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective {
+            fov: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// Projection parameters of the camera looking at the scene
+pub struct Camera {
+    pub projection: Projection,
+    pub near: f32,
+    pub far: f32,
+    /// Width divided by height of the viewport
+    pub aspect: f32,
+}
+
+impl Camera {
+    pub const DEFAULT_NEAR: f32 = 0.125;
+    pub const DEFAULT_FAR: f32 = 256.0;
+
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut camera = Self {
+            projection: Projection::default(),
+            near: Self::DEFAULT_NEAR,
+            far: Self::DEFAULT_FAR,
+            aspect: 1.0,
+        };
+        camera.resize(width, height);
+        camera
+    }
+
+    /// Sets the projection and depth range, keeping the previous ones when invalid
+    pub fn set_projection(&mut self, projection: Projection, near: f32, far: f32) -> Result<(), String> {
+        projection.validate(near, far)?;
+        self.projection = projection;
+        self.near = near;
+        self.far = far;
+        Ok(())
+    }
+
+    /// Updates the aspect ratio to match a viewport of the specified size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.aspect = width.max(1) as f32 / height.max(1) as f32;
+    }
+
+    /// Returns the view to clip space transform
+    pub fn proj(&self) -> Matrix4<f32> {
+        // A zero-sized canvas would make the projection degenerate
+        let aspect = if self.aspect > 0.0 && self.aspect.is_finite() {
+            self.aspect
+        } else {
+            1.0
+        };
+        match self.projection {
+            Projection::Perspective { fov } => {
+                Perspective3::new(aspect, fov, self.near, self.far).to_homogeneous()
+            }
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect;
+                Orthographic3::new(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.near,
+                    self.far,
+                )
+                .to_homogeneous()
+            }
+        }
+    }
+}
\ No newline at end of file
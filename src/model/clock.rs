@@ -0,0 +1,161 @@
+/// Source of time driving animations, in milliseconds from an arbitrary origin
+pub trait Clock {
+    fn now(&self) -> f64;
+
+    /// Called once at the start of every frame, before reading the time
+    fn tick(&mut self) {}
+}
+
+/// Wall clock time of the page
+pub struct RealTimeClock {
+    performance: web_sys::Performance,
+}
+
+impl RealTimeClock {
+    pub fn new(performance: web_sys::Performance) -> Self {
+        Self { performance }
+    }
+}
+
+impl Clock for RealTimeClock {
+    fn now(&self) -> f64 {
+        self.performance.now()
+    }
+}
+
+/// Time only moving when told to, by a fixed step every frame or explicitly,
+/// making frames reproducible whatever the actual frame rate
+pub struct ManualClock {
+    time: f64,
+    /// Milliseconds added every frame, `0.0` only moves by `advance` and `set`
+    pub step: f64,
+}
+
+impl ManualClock {
+    pub fn new(start: f64, step: f64) -> Self {
+        Self { time: start, step }
+    }
+
+    pub fn set(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    pub fn advance(&mut self, duration: f64) {
+        self.time += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.time
+    }
+
+    fn tick(&mut self) {
+        self.time += self.step;
+    }
+}
+
+/// Animation time derived from a clock, which can be paused, slowed down or sped up,
+/// and stepped one frame at a time while paused
+pub struct Timer {
+    clock: Box<dyn Clock>,
+    /// Clock time of the previous frame
+    last: f64,
+    /// Animation time in milliseconds
+    elapsed: f64,
+    /// Animation time of the last frame in milliseconds
+    delta: f64,
+    /// Clock time of the last frame in milliseconds, ignoring pause and scale
+    real_delta: f64,
+    paused: bool,
+    /// Frames to advance while paused
+    pending_steps: u32,
+
+    /// Animation speed relative to the clock, below `1.0` for slow motion
+    pub scale: f64,
+    /// Milliseconds of animation advanced by a single step
+    pub step: f64,
+    /// Longest frame in milliseconds, avoiding jumps after the page has been in background
+    pub max_delta: f64,
+}
+
+impl Timer {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        let last = clock.now();
+        Self {
+            clock,
+            last,
+            elapsed: 0.0,
+            delta: 0.0,
+            real_delta: 0.0,
+            paused: false,
+            pending_steps: 0,
+            scale: 1.0,
+            step: 1000.0 / 60.0,
+            max_delta: 100.0,
+        }
+    }
+
+    /// Replaces the clock, keeping the animation time where it is
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.last = clock.now();
+        self.clock = clock;
+    }
+
+    /// Moves to the next frame and returns the animation time elapsed since the previous one
+    pub fn tick(&mut self) -> f64 {
+        self.clock.tick();
+        let now = self.clock.now();
+        self.real_delta = (now - self.last).clamp(0.0, self.max_delta);
+        self.last = now;
+
+        self.delta = if !self.paused {
+            self.real_delta * self.scale.max(0.0)
+        } else if self.pending_steps > 0 {
+            self.pending_steps -= 1;
+            self.step
+        } else {
+            0.0
+        };
+        self.elapsed += self.delta;
+        self.delta
+    }
+
+    /// Animation time in milliseconds
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: f64) {
+        self.elapsed = elapsed;
+    }
+
+    /// Animation time of the last frame in milliseconds
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// Clock time of the last frame in milliseconds, still running while paused
+    pub fn real_delta(&self) -> f64 {
+        self.real_delta
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.pending_steps = 0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses and advances the animation by `step` on the next frame
+    pub fn step_frame(&mut self) {
+        self.paused = true;
+        self.pending_steps += 1;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,191 @@
+use nalgebra::Vector3;
+use web_sys::WebGlRenderingContext as GL;
+use web_sys::*;
+use crate::model::{stats, Image, Sampler};
+
+/// Texture made of six square faces, sampled with a direction
+pub struct Cubemap {
+    pub gl: GL,
+    pub handle: WebGlTexture,
+    /// Width and height of each face
+    pub size: u32,
+    /// Size of all the faces
+    bytes: u64,
+}
+
+impl Cubemap {
+    /// Face targets in upload order: +X, -X, +Y, -Y, +Z, -Z
+    pub const FACES: [u32; 6] = [
+        GL::TEXTURE_CUBE_MAP_POSITIVE_X,
+        GL::TEXTURE_CUBE_MAP_NEGATIVE_X,
+        GL::TEXTURE_CUBE_MAP_POSITIVE_Y,
+        GL::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+        GL::TEXTURE_CUBE_MAP_POSITIVE_Z,
+        GL::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+    ];
+
+    /// Returns a new cubemap from six square images of the same size, ordered as `FACES`
+    pub fn from_images(gl: GL, faces: &[Image]) -> Result<Self, String> {
+        if faces.len() != 6 {
+            return Err(format!("Expected 6 cubemap faces, got {}", faces.len()));
+        }
+        let size = faces[0].width;
+        if let Some(face) = faces
+            .iter()
+            .find(|face| face.width != size || face.height != size)
+        {
+            return Err(format!(
+                "Cubemap faces must be square and of the same size: {}x{} instead of {}x{}",
+                face.width, face.height, size, size
+            ));
+        }
+        if faces.iter().any(|face| face.format != faces[0].format) {
+            return Err("Cubemap faces must have the same pixel format".to_string());
+        }
+
+        let handle = gl.create_texture().ok_or("Failed to create cubemap")?;
+        let bytes = 6 * size as u64 * size as u64 * faces[0].format.channels() as u64;
+        let cubemap = Self {
+            gl,
+            handle,
+            size,
+            bytes,
+        };
+        // Freed when dropped, even if an upload fails
+        stats::allocate_texture(bytes);
+        cubemap.bind();
+
+        let gl = &cubemap.gl;
+        Sampler::new().apply(gl, GL::TEXTURE_CUBE_MAP);
+
+        gl.pixel_storei(GL::UNPACK_ALIGNMENT, 1);
+        for (target, face) in Self::FACES.iter().zip(faces) {
+            let format = face.format.to_gl();
+            gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                *target,
+                0,
+                format as i32,
+                size as i32,
+                size as i32,
+                0,
+                format,
+                GL::UNSIGNED_BYTE,
+                Some(&face.data),
+            )
+            .map_err(|_| "Failed to upload cubemap face")?;
+        }
+
+        Ok(cubemap)
+    }
+
+    /// Returns the largest face size supported by the device
+    pub fn max_size(gl: &GL) -> u32 {
+        gl.get_parameter(GL::MAX_CUBE_MAP_TEXTURE_SIZE)
+            .ok()
+            .and_then(|value| value.as_f64())
+            .unwrap_or(0.0) as u32
+    }
+
+    /// Checks a face size against the device limit, returning the bytes of an RGBA face
+    pub fn face_len(size: u32, max_size: u32) -> Result<usize, String> {
+        if size == 0 || size > max_size {
+            return Err(format!("Cubemap face size must be within 1 and {}, got {}", max_size, size));
+        }
+        (size as usize)
+            .checked_mul(size as usize)
+            .and_then(|texels| texels.checked_mul(4))
+            .ok_or_else(|| format!("Cubemap face size too large: {}", size))
+    }
+
+    /// Returns a new cubemap with faces of the specified size,
+    /// resampled from a panorama in equirectangular projection
+    pub fn from_equirectangular(gl: GL, panorama: &Image, size: u32) -> Result<Self, String> {
+        let face_len = Self::face_len(size, Self::max_size(&gl))?;
+        let panorama = &panorama.to_rgba();
+        let faces: Vec<Image> = (0..6)
+            .map(|face| {
+                let mut data = Vec::with_capacity(face_len);
+                for y in 0..size {
+                    for x in 0..size {
+                        let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                        let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                        let direction = Self::face_direction(face, s, t).normalize();
+                        data.extend_from_slice(&sample_equirectangular(panorama, &direction));
+                    }
+                }
+                Image::from_vec(data, size, size)
+            })
+            .collect();
+
+        Self::from_images(gl, &faces)
+    }
+
+    /// Returns the direction sampling the face at the coordinates in `[-1, 1]`,
+    /// `t` going down the rows of the face image
+    pub fn face_direction(face: usize, s: f32, t: f32) -> Vector3<f32> {
+        match face {
+            0 => Vector3::new(1.0, -t, -s),
+            1 => Vector3::new(-1.0, -t, s),
+            2 => Vector3::new(s, 1.0, t),
+            3 => Vector3::new(s, -1.0, -t),
+            4 => Vector3::new(s, -t, 1.0),
+            _ => Vector3::new(-s, -t, -1.0),
+        }
+    }
+
+    pub fn bind(&self) {
+        self.gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&self.handle));
+        stats::count_state_changes(1);
+    }
+
+    /// Binds the cubemap to the specified texture unit, leaving it as the active one
+    pub fn bind_to(&self, unit: u32) {
+        self.gl.active_texture(GL::TEXTURE0 + unit);
+        self.bind();
+    }
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        self.gl.delete_texture(Some(&self.handle));
+        stats::free_texture(self.bytes);
+    }
+}
+
+/// Bilinearly samples the panorama in the specified unit direction,
+/// the top row being straight up and the center looking towards -Z
+pub fn sample_equirectangular(panorama: &Image, direction: &Vector3<f32>) -> [u8; 4] {
+    use std::f32::consts::PI;
+
+    let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+    let x = u * panorama.width as f32 - 0.5;
+    let y = (v * panorama.height as f32 - 0.5).max(0.0);
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+
+    // Wrap around horizontally, clamp vertically
+    let width = panorama.width as i64;
+    let height = panorama.height as i64;
+    let texel = |x: i64, y: i64| {
+        let x = x.rem_euclid(width);
+        let y = y.clamp(0, height - 1);
+        let i = ((y * width + x) * 4) as usize;
+        &panorama.data[i..i + 4]
+    };
+
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let (a, b) = (texel(x0, y0), texel(x0 + 1, y0));
+    let (c, d) = (texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+
+    let mut color = [0u8; 4];
+    for i in 0..4 {
+        let top = a[i] as f32 * (1.0 - fx) + b[i] as f32 * fx;
+        let bottom = c[i] as f32 * (1.0 - fx) + d[i] as f32 * fx;
+        color[i] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    color
+}
\ No newline at end of file
@@ -1,27 +1,259 @@
+use nalgebra::{Isometry3, Vector3};
 use web_sys::{WebGlRenderingContext as GL, WebGlUniformLocation};
-use crate::model::{program, Vertex};
+use crate::model::{
+    gl_state, program, AlphaMode, Image, Light, LightKind, Material, ShadowMap, SphericalHarmonics,
+    Texture, Vertex,
+};
 
 pub struct DefaultPipeline {
     pub program: program::Program,
     pub transform_loc: Option<WebGlUniformLocation>,
     pub normal_transform_loc: Option<WebGlUniformLocation>,
+
+    /// Number of lights this shader variant has been compiled for
+    pub max_lights: usize,
+    light_count_loc: Option<WebGlUniformLocation>,
+    light_position_loc: Option<WebGlUniformLocation>,
+    light_direction_loc: Option<WebGlUniformLocation>,
+    light_color_loc: Option<WebGlUniformLocation>,
+    light_attenuation_loc: Option<WebGlUniformLocation>,
+    light_cone_loc: Option<WebGlUniformLocation>,
+    ambient_color_loc: Option<WebGlUniformLocation>,
+
+    base_color_loc: Option<WebGlUniformLocation>,
+    emissive_factor_loc: Option<WebGlUniformLocation>,
+    shininess_loc: Option<WebGlUniformLocation>,
+    normal_scale_loc: Option<WebGlUniformLocation>,
+    alpha_cutoff_loc: Option<WebGlUniformLocation>,
+    opaque_loc: Option<WebGlUniformLocation>,
+    metallic_loc: Option<WebGlUniformLocation>,
+    roughness_loc: Option<WebGlUniformLocation>,
+    occlusion_strength_loc: Option<WebGlUniformLocation>,
+
+    sh_coefficients_loc: Option<WebGlUniformLocation>,
+    view_to_world_loc: Option<WebGlUniformLocation>,
+
+    shadow_light_loc: Option<WebGlUniformLocation>,
+    shadow_matrix_loc: Option<WebGlUniformLocation>,
+    shadow_bias_loc: Option<WebGlUniformLocation>,
+    shadow_texel_loc: Option<WebGlUniformLocation>,
+
+    /// Bound to the texture slots a material leaves empty
+    white: Texture,
 }
 
 impl DefaultPipeline {
-    pub fn new(gl: &GL, vert_src: &str, frag_src: &str) -> Self {
-        let program = program::Program::new(gl.clone(), vert_src, frag_src);
+    pub const DEFAULT_MAX_LIGHTS: usize = 8;
+    /// Fragment uniform vectors taken by each light
+    pub const LIGHT_UNIFORM_VECTORS: usize = 5;
+    /// Fragment uniform vectors taken by everything but the lights,
+    /// counted for the PBR shader which has the most
+    pub const FIXED_UNIFORM_VECTORS: usize = 29;
+
+    /// Returns how many lights fit in the specified number of fragment uniform vectors
+    pub fn lights_fitting(uniform_vectors: usize) -> usize {
+        uniform_vectors.saturating_sub(Self::FIXED_UNIFORM_VECTORS) / Self::LIGHT_UNIFORM_VECTORS
+    }
+
+    /// Returns the most lights the fragment shaders can declare on this device
+    pub fn max_supported_lights(gl: &GL) -> usize {
+        let vectors = gl
+            .get_parameter(GL::MAX_FRAGMENT_UNIFORM_VECTORS)
+            .ok()
+            .and_then(|value| value.as_f64())
+            .unwrap_or(0.0);
+        Self::lights_fitting(vectors as usize)
+    }
+
+    pub fn new(gl: &GL, vert_src: &str, frag_src: &str, max_lights: usize) -> Self {
+        let max_lights = max_lights.max(1);
+        let mut defines = format!("#define MAX_LIGHTS {}\n", max_lights);
+        if gl.get_extension("OES_standard_derivatives").ok().flatten().is_some() {
+            defines.insert_str(0, "#extension GL_OES_standard_derivatives : enable\n");
+            defines.push_str("#define HAS_DERIVATIVES\n");
+        }
+        let frag_src = format!("{}{}", defines, frag_src);
+        let program = program::Program::new(gl.clone(), vert_src, &frag_src);
         program.bind();
 
+        // Each sampler reads from its own texture unit
+        let samplers = [
+            ("diffuse_sampler", Material::DIFFUSE_UNIT),
+            ("specular_sampler", Material::SPECULAR_UNIT),
+            ("normal_sampler", Material::NORMAL_UNIT),
+            ("emissive_sampler", Material::EMISSIVE_UNIT),
+            ("shadow_sampler", ShadowMap::UNIT),
+            ("metallic_roughness_sampler", Material::METALLIC_ROUGHNESS_UNIT),
+            ("occlusion_sampler", Material::OCCLUSION_UNIT),
+        ];
+        for (name, unit) in samplers.iter() {
+            gl.uniform1i(program.get_uniform_loc(name).as_ref(), *unit as i32);
+        }
+
         let transform_loc = program.get_uniform_loc("transform");
         let normal_transform_loc = program.get_uniform_loc("normal_transform");
 
         Self {
+            max_lights,
+            light_count_loc: program.get_uniform_loc("light_count"),
+            light_position_loc: program.get_uniform_loc("light_position"),
+            light_direction_loc: program.get_uniform_loc("light_direction"),
+            light_color_loc: program.get_uniform_loc("light_color"),
+            light_attenuation_loc: program.get_uniform_loc("light_attenuation"),
+            light_cone_loc: program.get_uniform_loc("light_cone"),
+            ambient_color_loc: program.get_uniform_loc("ambient_color"),
+            base_color_loc: program.get_uniform_loc("base_color"),
+            emissive_factor_loc: program.get_uniform_loc("emissive_factor"),
+            shininess_loc: program.get_uniform_loc("shininess"),
+            normal_scale_loc: program.get_uniform_loc("normal_scale"),
+            alpha_cutoff_loc: program.get_uniform_loc("alpha_cutoff"),
+            opaque_loc: program.get_uniform_loc("opaque"),
+            metallic_loc: program.get_uniform_loc("metallic"),
+            roughness_loc: program.get_uniform_loc("roughness"),
+            occlusion_strength_loc: program.get_uniform_loc("occlusion_strength"),
+            sh_coefficients_loc: program.get_uniform_loc("sh_coefficients"),
+            view_to_world_loc: program.get_uniform_loc("view_to_world"),
+            shadow_light_loc: program.get_uniform_loc("shadow_light"),
+            shadow_matrix_loc: program.get_uniform_loc("shadow_matrix"),
+            shadow_bias_loc: program.get_uniform_loc("shadow_bias"),
+            shadow_texel_loc: program.get_uniform_loc("shadow_texel"),
+            white: Texture::from_image(gl.clone(), &Image::new()),
             program,
             transform_loc,
             normal_transform_loc,
         }
     }
 
+    /// Uploads up to `max_lights` lights, converted to the view space
+    pub fn set_lights(&self, lights: &[Light], view: &Isometry3<f32>) {
+        let lights = &lights[..lights.len().min(self.max_lights)];
+
+        let mut positions = Vec::with_capacity(lights.len() * 4);
+        let mut directions = Vec::with_capacity(lights.len() * 3);
+        let mut colors = Vec::with_capacity(lights.len() * 3);
+        let mut attenuations = Vec::with_capacity(lights.len() * 3);
+        let mut cones = Vec::with_capacity(lights.len() * 2);
+
+        for light in lights {
+            positions.extend_from_slice(&light.view_position(view));
+            directions.extend_from_slice(light.view_direction(view).as_slice());
+            colors.extend_from_slice((light.color * light.intensity).as_slice());
+            attenuations.extend_from_slice(light.attenuation.as_slice());
+            match light.kind {
+                LightKind::Spot => {
+                    cones.extend_from_slice(&[light.inner_angle.cos(), light.outer_angle.cos()])
+                }
+                _ => cones.extend_from_slice(&[1.0, 1.0]),
+            }
+        }
+
+        let gl = &self.program.gl;
+        gl.uniform1i(self.light_count_loc.as_ref(), lights.len() as i32);
+        if lights.is_empty() {
+            return;
+        }
+        gl.uniform4fv_with_f32_array(self.light_position_loc.as_ref(), &positions);
+        gl.uniform3fv_with_f32_array(self.light_direction_loc.as_ref(), &directions);
+        gl.uniform3fv_with_f32_array(self.light_color_loc.as_ref(), &colors);
+        gl.uniform3fv_with_f32_array(self.light_attenuation_loc.as_ref(), &attenuations);
+        gl.uniform2fv_with_f32_array(self.light_cone_loc.as_ref(), &cones);
+    }
+
+    pub fn set_ambient(&self, color: &Vector3<f32>) {
+        self.program
+            .gl
+            .uniform3fv_with_f32_array(self.ambient_color_loc.as_ref(), color.as_slice());
+    }
+
+    /// Uploads the ambient environment of the physically based pipeline
+    pub fn set_environment(&self, sh: &SphericalHarmonics, view: &Isometry3<f32>) {
+        let gl = &self.program.gl;
+        gl.uniform3fv_with_f32_array(self.sh_coefficients_loc.as_ref(), &sh.to_vec());
+
+        // The environment is defined in world space
+        let view_to_world = view.rotation.inverse().to_rotation_matrix();
+        gl.uniform_matrix3fv_with_f32_array(
+            self.view_to_world_loc.as_ref(),
+            false,
+            view_to_world.matrix().as_slice(),
+        );
+    }
+
+    /// Binds the shadow map rendered by the last shadow pass, if it found a light
+    pub fn set_shadow(&self, shadow_map: &ShadowMap, view: &Isometry3<f32>) {
+        let gl = &self.program.gl;
+
+        let light = match shadow_map.light {
+            Some(light) => light,
+            None => {
+                gl.uniform1i(self.shadow_light_loc.as_ref(), -1);
+                self.white.bind_to(ShadowMap::UNIT);
+                gl.active_texture(GL::TEXTURE0);
+                return;
+            }
+        };
+
+        shadow_map.framebuffer.texture.bind_to(ShadowMap::UNIT);
+        gl.active_texture(GL::TEXTURE0);
+
+        // Fragment positions are in view space
+        let shadow_matrix = shadow_map.light_matrix * view.inverse().to_homogeneous();
+        gl.uniform1i(self.shadow_light_loc.as_ref(), light as i32);
+        gl.uniform_matrix4fv_with_f32_array(
+            self.shadow_matrix_loc.as_ref(),
+            false,
+            shadow_matrix.as_slice(),
+        );
+        gl.uniform1f(self.shadow_bias_loc.as_ref(), shadow_map.bias);
+        gl.uniform1f(
+            self.shadow_texel_loc.as_ref(),
+            1.0 / shadow_map.get_size() as f32,
+        );
+    }
+
+    /// Binds the material textures to their units and uploads its factors,
+    /// also setting the face culling and blending state it needs
+    pub fn set_material(&self, material: &Material) {
+        let gl = &self.program.gl;
+
+        let textures = [
+            (&material.diffuse_texture, Material::DIFFUSE_UNIT),
+            (&material.specular_texture, Material::SPECULAR_UNIT),
+            (&material.normal_texture, Material::NORMAL_UNIT),
+            (&material.emissive_texture, Material::EMISSIVE_UNIT),
+            (&material.metallic_roughness_texture, Material::METALLIC_ROUGHNESS_UNIT),
+            (&material.occlusion_texture, Material::OCCLUSION_UNIT),
+        ];
+        for (texture, unit) in textures.iter() {
+            texture.as_deref().unwrap_or(&self.white).bind_to(*unit);
+        }
+        gl.active_texture(GL::TEXTURE0);
+
+        gl.uniform4fv_with_f32_array(self.base_color_loc.as_ref(), &material.base_color);
+        gl.uniform3fv_with_f32_array(self.emissive_factor_loc.as_ref(), &material.emissive_factor);
+        gl.uniform1f(self.shininess_loc.as_ref(), material.shininess);
+        gl.uniform1f(self.metallic_loc.as_ref(), material.metallic);
+        gl.uniform1f(self.roughness_loc.as_ref(), material.roughness);
+        gl.uniform1f(self.occlusion_strength_loc.as_ref(), material.occlusion_strength);
+
+        let normal_scale = if material.normal_texture.is_some() { 1.0 } else { 0.0 };
+        gl.uniform1f(self.normal_scale_loc.as_ref(), normal_scale);
+
+        let (cutoff, opaque) = match material.alpha_mode {
+            AlphaMode::Opaque => (-1.0, 1.0),
+            AlphaMode::Mask { cutoff } => (cutoff, 1.0),
+            AlphaMode::Blend => (-1.0, 0.0),
+        };
+        gl.uniform1f(self.alpha_cutoff_loc.as_ref(), cutoff);
+        gl.uniform1f(self.opaque_loc.as_ref(), opaque);
+
+        gl_state::set(gl, GL::CULL_FACE, !material.double_sided);
+
+        // Blended surfaces do not hide what is drawn after them
+        gl_state::set(gl, GL::BLEND, material.is_blended());
+        gl_state::depth_mask(gl, !material.is_blended());
+    }
+
     pub fn bind_attribs(&self) {
         // Position
         let position_loc = self.program.get_attrib_loc("in_position");
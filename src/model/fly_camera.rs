@@ -0,0 +1,101 @@
+use nalgebra::{Isometry3, Point3, Vector2, Vector3};
+use crate::model::Keyboard;
+
+/// First-person camera moved with WASD keys and rotated by mouse look
+pub struct FlyCamera {
+    pub position: Point3<f32>,
+    /// Rotation around the up axis in radians, `0.0` looks towards -Z
+    pub yaw: f32,
+    /// Rotation above the horizon in radians
+    pub pitch: f32,
+
+    /// Units per second
+    pub speed: f32,
+    /// Speed multiplier while holding shift
+    pub boost: f32,
+    /// Radians per pixel of mouse motion
+    pub look_speed: f32,
+}
+
+impl FlyCamera {
+    pub fn new(position: Point3<f32>) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 2.0,
+            boost: 4.0,
+            look_speed: 0.003,
+        }
+    }
+
+    /// Places the camera at `eye` looking at `target`
+    pub fn look_at(&mut self, eye: &Point3<f32>, target: &Point3<f32>) {
+        let direction = target - eye;
+        let length = direction.norm().max(f32::EPSILON);
+        self.position = *eye;
+        self.yaw = (-direction.x).atan2(-direction.z);
+        self.pitch = (direction.y / length).asin();
+    }
+
+    /// Rotates the view by a mouse motion in pixels
+    pub fn look(&mut self, motion: Vector2<f32>) {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.yaw -= motion.x * self.look_speed;
+        self.pitch = (self.pitch - motion.y * self.look_speed).clamp(-limit, limit);
+    }
+
+    /// Moves according to the keys held down, `delta` being the frame time in seconds
+    pub fn update(&mut self, keyboard: &Keyboard, delta: f32) {
+        let mut direction = Vector3::zeros();
+        if keyboard.is_down("KeyW") {
+            direction += self.forward();
+        }
+        if keyboard.is_down("KeyS") {
+            direction -= self.forward();
+        }
+        if keyboard.is_down("KeyD") {
+            direction += self.right();
+        }
+        if keyboard.is_down("KeyA") {
+            direction -= self.right();
+        }
+        if keyboard.is_down("KeyE") || keyboard.is_down("Space") {
+            direction += Vector3::y();
+        }
+        if keyboard.is_down("KeyQ") || keyboard.is_down("KeyC") {
+            direction -= Vector3::y();
+        }
+
+        if let Some(direction) = direction.try_normalize(f32::EPSILON) {
+            let boost = if keyboard.is_down("ShiftLeft") || keyboard.is_down("ShiftRight") {
+                self.boost
+            } else {
+                1.0
+            };
+            self.position += direction * self.speed * boost * delta;
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        Vector3::new(
+            -self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.cos(), 0.0, -self.yaw.sin())
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.position + self.forward()
+    }
+
+    /// Returns the world to view transform
+    pub fn view(&self) -> Isometry3<f32> {
+        // OpenGL uses a right-handed coordinate system
+        Isometry3::look_at_rh(&self.position, &self.target(), &Vector3::y_axis())
+    }
+}
\ No newline at end of file
@@ -1,9 +1,260 @@
-use crate::model::Texture;
+use std::fmt;
+use wasm_bindgen::JsCast;
+use web_sys::WebGlRenderingContext as GL;
 use web_sys::*;
+use crate::model::{stats, Image, PixelFormat, Sampler, TexelType, Texture};
 
+/// Reasons for a framebuffer not being usable as a render target
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FramebufferError {
+    /// The context could not create one of the objects, usually because it is lost
+    CreationFailed,
+    IncompleteAttachment,
+    MissingAttachment,
+    IncompleteDimensions,
+    /// The combination of attachment formats is not supported by the implementation
+    Unsupported,
+    /// Any other status returned by `checkFramebufferStatus`
+    Unknown(u32),
+    /// Pixels could not be read back, as from a float framebuffer
+    ReadFailed,
+}
+
+impl FramebufferError {
+    fn from_status(status: u32) -> Self {
+        match status {
+            GL::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => FramebufferError::IncompleteAttachment,
+            GL::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => FramebufferError::MissingAttachment,
+            GL::FRAMEBUFFER_INCOMPLETE_DIMENSIONS => FramebufferError::IncompleteDimensions,
+            GL::FRAMEBUFFER_UNSUPPORTED => FramebufferError::Unsupported,
+            status => FramebufferError::Unknown(status),
+        }
+    }
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramebufferError::CreationFailed => write!(f, "Failed to create framebuffer objects"),
+            FramebufferError::IncompleteAttachment => {
+                write!(f, "Framebuffer incomplete: attachment is not complete")
+            }
+            FramebufferError::MissingAttachment => {
+                write!(f, "Framebuffer incomplete: no image is attached")
+            }
+            FramebufferError::IncompleteDimensions => {
+                write!(f, "Framebuffer incomplete: attachments have different sizes")
+            }
+            FramebufferError::Unsupported => {
+                write!(f, "Framebuffer unsupported: invalid combination of formats")
+            }
+            FramebufferError::Unknown(status) => {
+                write!(f, "Framebuffer incomplete: status {:#x}", status)
+            }
+            FramebufferError::ReadFailed => write!(f, "Failed to read framebuffer pixels"),
+        }
+    }
+}
+
+impl From<FramebufferError> for wasm_bindgen::JsValue {
+    fn from(error: FramebufferError) -> Self {
+        wasm_bindgen::JsValue::from_str(&error.to_string())
+    }
+}
+
+/// Offscreen render target with a color texture and a depth renderbuffer,
+/// all deleted when dropped
 pub struct Framebuffer {
-    pub frame: Option<WebGlFramebuffer>,
-    pub color: Option<WebGlRenderbuffer>,
-    pub depth: Option<WebGlRenderbuffer>,
-    pub texture: Option<Texture>,
+    gl: GL,
+    frame: WebGlFramebuffer,
+    depth: WebGlRenderbuffer,
+    pub texture: Texture,
+    width: u32,
+    height: u32,
+}
+
+impl Framebuffer {
+    pub fn new(gl: GL, width: u32, height: u32) -> Result<Self, FramebufferError> {
+        Self::with_texel_type(gl, width, height, TexelType::UnsignedByte)
+    }
+
+    /// Returns a framebuffer with a color texture of the specified texel type,
+    /// unsupported when the context can not render into it
+    pub fn with_texel_type(
+        gl: GL,
+        width: u32,
+        height: u32,
+        texel_type: TexelType,
+    ) -> Result<Self, FramebufferError> {
+        texel_type
+            .check_render_support(&gl)
+            .map_err(|_| FramebufferError::Unsupported)?;
+        let frame = gl
+            .create_framebuffer()
+            .ok_or(FramebufferError::CreationFailed)?;
+        let depth = gl
+            .create_renderbuffer()
+            .ok_or(FramebufferError::CreationFailed)?;
+        let texture = Texture::empty(gl.clone(), PixelFormat::Rgba, texel_type, 1, 1, Sampler::new())
+            .map_err(|_| FramebufferError::CreationFailed)?;
+
+        let mut framebuffer = Self {
+            gl,
+            frame,
+            depth,
+            texture,
+            width: 0,
+            height: 0,
+        };
+
+        framebuffer.resize(width, height)?;
+        Ok(framebuffer)
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Reallocates the attachments with the new size, discarding their content
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        let width = width.max(1);
+        let height = height.max(1);
+
+        let previous = bound_framebuffer(&self.gl);
+        self.gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&self.frame));
+
+        self.texture.bind();
+        self.texture.upload(None, width, height);
+        self.gl.bind_texture(GL::TEXTURE_2D, None);
+        self.gl.framebuffer_texture_2d(
+            GL::FRAMEBUFFER,
+            GL::COLOR_ATTACHMENT0,
+            GL::TEXTURE_2D,
+            Some(&self.texture.handle),
+            0,
+        );
+
+        self.gl.bind_renderbuffer(GL::RENDERBUFFER, Some(&self.depth));
+        self.gl.renderbuffer_storage(
+            GL::RENDERBUFFER,
+            GL::DEPTH_COMPONENT16,
+            width as i32,
+            height as i32,
+        );
+        self.gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+        self.gl.framebuffer_renderbuffer(
+            GL::FRAMEBUFFER,
+            GL::DEPTH_ATTACHMENT,
+            GL::RENDERBUFFER,
+            Some(&self.depth),
+        );
+
+        let status = self.gl.check_framebuffer_status(GL::FRAMEBUFFER);
+        self.gl.bind_framebuffer(GL::FRAMEBUFFER, previous.as_ref());
+
+        stats::free_texture(self.depth_bytes());
+        self.width = width;
+        self.height = height;
+        stats::allocate_texture(self.depth_bytes());
+
+        if status == GL::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(FramebufferError::from_status(status))
+        }
+    }
+
+    /// Binds this framebuffer as render target with a viewport covering it.
+    /// The previous target and viewport are restored when the returned binding is dropped
+    pub fn bind(&self) -> FramebufferBinding {
+        let previous = bound_framebuffer(&self.gl);
+        let viewport = self.gl.get_parameter(GL::VIEWPORT).ok();
+
+        self.gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&self.frame));
+        self.gl.viewport(0, 0, self.width as i32, self.height as i32);
+        stats::count_state_changes(1);
+
+        FramebufferBinding {
+            gl: self.gl.clone(),
+            previous,
+            viewport: viewport
+                .map(|viewport| js_sys::Int32Array::from(viewport).to_vec())
+                .filter(|viewport| viewport.len() == 4),
+        }
+    }
+
+    /// Returns the content of the color attachment, with the top row first
+    pub fn read_image(&self) -> Result<Image, FramebufferError> {
+        if self.texture.texel_type != TexelType::UnsignedByte {
+            return Err(FramebufferError::ReadFailed);
+        }
+        let _binding = self.bind();
+        read_pixels(&self.gl, self.width, self.height)
+    }
+
+    /// Size of the 16 bits depth renderbuffer
+    fn depth_bytes(&self) -> u64 {
+        self.width as u64 * self.height as u64 * 2
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.gl.delete_framebuffer(Some(&self.frame));
+        self.gl.delete_renderbuffer(Some(&self.depth));
+        stats::free_texture(self.depth_bytes());
+    }
+}
+
+/// Active render target binding returned by `Framebuffer::bind`
+pub struct FramebufferBinding {
+    gl: GL,
+    previous: Option<WebGlFramebuffer>,
+    viewport: Option<Vec<i32>>,
+}
+
+impl Drop for FramebufferBinding {
+    fn drop(&mut self) {
+        self.gl.bind_framebuffer(GL::FRAMEBUFFER, self.previous.as_ref());
+        stats::count_state_changes(1);
+        if let Some(viewport) = self.viewport.as_ref() {
+            self.gl
+                .viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+}
+
+/// Reads the RGBA pixels of the bound framebuffer from its bottom-left corner,
+/// flipping them as images start with the top row whereas framebuffers start with the bottom one
+pub fn read_pixels(gl: &GL, width: u32, height: u32) -> Result<Image, FramebufferError> {
+    let mut data = vec![0u8; (width * height * 4) as usize];
+    gl.pixel_storei(GL::PACK_ALIGNMENT, 1);
+    gl.read_pixels_with_opt_u8_array(
+        0,
+        0,
+        width as i32,
+        height as i32,
+        GL::RGBA,
+        GL::UNSIGNED_BYTE,
+        Some(&mut data),
+    )
+    .map_err(|_| FramebufferError::ReadFailed)?;
+    if gl.get_error() != GL::NO_ERROR {
+        return Err(FramebufferError::ReadFailed);
+    }
+
+    let mut image = Image::from_vec(data, width, height);
+    image.flip_vertical();
+    Ok(image)
+}
+
+/// Returns the framebuffer currently bound, `None` being the canvas
+fn bound_framebuffer(gl: &GL) -> Option<WebGlFramebuffer> {
+    gl.get_parameter(GL::FRAMEBUFFER_BINDING)
+        .ok()
+        .and_then(|frame| frame.dyn_into::<WebGlFramebuffer>().ok())
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+use nalgebra::{Matrix4, Vector3};
+use crate::model::Aabb;
+
+/// Plane in the form `normal · p + distance = 0` with the normal pointing inside
+#[derive(Clone, Copy, Debug)]
+struct Plane {
+    normal: Vector3<f32>,
+    distance: f32,
+}
+
+/// View frustum as six planes, used to skip geometry the camera can not see
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a `proj * view` matrix (Gribb-Hartmann)
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        let plane = |coeffs: nalgebra::Vector4<f32>| {
+            let normal = Vector3::new(coeffs.x, coeffs.y, coeffs.z);
+            let length = normal.norm();
+            Plane {
+                normal: normal / length,
+                distance: coeffs.w / length,
+            }
+        };
+
+        Self {
+            planes: [
+                plane(w + x), // Left
+                plane(w - x), // Right
+                plane(w + y), // Bottom
+                plane(w - y), // Top
+                plane(w + z), // Near
+                plane(w - z), // Far
+            ],
+        }
+    }
+
+    /// Returns whether the box is at least partially inside the frustum
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner of the box farthest along the plane normal
+            let positive = Vector3::new(
+                if plane.normal.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.normal.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.normal.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            plane.normal.dot(&positive) + plane.distance >= 0.0
+        })
+    }
+}
\ No newline at end of file
@@ -1,4 +1,5 @@
-use crate::model::Vertex;
+use nalgebra::Point3;
+use crate::model::{Aabb, Vertex};
 
 /// CPU-side primitive geometry
 pub struct Geometry<V> {
@@ -7,6 +8,15 @@ pub struct Geometry<V> {
 }
 
 impl Geometry<Vertex> {
+    /// Returns the bounding box of all the vertex positions
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(
+            self.vertices
+                .iter()
+                .map(|v| Point3::new(v.position[0], v.position[1], v.position[2])),
+        )
+    }
+
     pub fn triangle() -> Self {
         let vertices = vec![
             Vertex {
@@ -0,0 +1,37 @@
+//! Fixed-function state changes, each counted once in the render counters
+use web_sys::WebGlRenderingContext as GL;
+use crate::model::stats;
+
+pub fn enable(gl: &GL, cap: u32) {
+    gl.enable(cap);
+    stats::count_state_changes(1);
+}
+
+pub fn disable(gl: &GL, cap: u32) {
+    gl.disable(cap);
+    stats::count_state_changes(1);
+}
+
+/// Enables the capability when `on`, disables it otherwise
+pub fn set(gl: &GL, cap: u32, on: bool) {
+    if on {
+        enable(gl, cap);
+    } else {
+        disable(gl, cap);
+    }
+}
+
+pub fn depth_mask(gl: &GL, flag: bool) {
+    gl.depth_mask(flag);
+    stats::count_state_changes(1);
+}
+
+pub fn depth_func(gl: &GL, func: u32) {
+    gl.depth_func(func);
+    stats::count_state_changes(1);
+}
+
+pub fn blend_func(gl: &GL, src: u32, dst: u32) {
+    gl.blend_func(src, dst);
+    stats::count_state_changes(1);
+}
\ No newline at end of file
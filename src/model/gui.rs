@@ -1,10 +1,12 @@
 
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::{convert::From, ops::DerefMut};
 use web_sys::{WebGlUniformLocation, WebGlTexture};
 use web_sys::WebGlRenderingContext as GL;
-use nalgebra::{Isometry3, Point3, Translation3, UnitQuaternion, Vector2, Vector3, Matrix4};
-use crate::model::{Program, Primitive, Vertex, Texture, Geometry, Mouse, Image};
+use nalgebra::{Isometry3, Point3, Vector2, Vector3, Matrix4};
+use crate::model::{Program, Primitive, Vertex, Texture, Geometry, Mouse, Image, Sampler};
+use crate::model::{stats, Atlas, AtlasBuilder, Rect, UvRect};
 
 type UV = [f32; 2];
 
@@ -93,7 +95,7 @@ impl GuiPipeline {
     }
 
     fn bind_char_attribs(&self, c: char) {
-        let index = (c as u8 + 53 as u8) as i32;
+        let index = (c as u8 + 53) as i32;
 
         // Position
         // Number of bytes between each vertex element
@@ -177,14 +179,14 @@ pub struct Gui {
 
     /// Generic unit quad with default UVs
     quad: Primitive,
-    /// Generic window background
-    background: Primitive,
-    /// Generic window title bar
-    title_bar: Primitive,
-    /// Primitive for the shadow
-    shadow: Primitive,
 
+    /// Images of all the elements, the font and the icons
+    atlas_builder: AtlasBuilder,
+    atlas: Atlas,
+    /// Texture of the atlas image
     texture: Texture,
+    /// Quads sampling each image of the atlas, by name
+    sprites: HashMap<String, Primitive>,
 
     pub windows: Vec<Window>,
 
@@ -216,41 +218,53 @@ impl Gui {
     }
 
 
-    fn create_background(gl: GL) -> Primitive {
+    /// Returns a quad sampling an area of the atlas, with its top-left corner at the origin
+    fn create_sprite(gl: GL, uv: &UvRect) -> Primitive {
         let mut quad = Geometry::<Vertex>::quad();
 
-        quad.vertices[0].uv = [0.0, 1.0 / 4.0];
-        quad.vertices[1].uv = [1.0, 1.0 / 4.0];
-        quad.vertices[2].uv = [1.0, 2.0 / 4.0];
-        quad.vertices[3].uv = [0.0, 2.0 / 4.0];
+        for vertex in &mut quad.vertices {
+            vertex.uv = uv.map([vertex.position[0], vertex.position[1]]);
+        }
 
         Primitive::new(gl, &quad)
     }
 
-    fn create_title_bar(gl: GL) -> Primitive {
-        let mut quad = Geometry::<Vertex>::quad();
-
-        quad.vertices[0].uv = [0.0, 0.0 / 4.0];
-        quad.vertices[1].uv = [1.0, 0.0 / 4.0];
-        quad.vertices[2].uv = [1.0, 1.0 / 4.0];
-        quad.vertices[3].uv = [0.0, 1.0 / 4.0];
+    /// Returns the builder with the colors of the window elements and the font
+    /// Images of the atlas drawing the windows, which icons can not replace
+    pub const RESERVED_NAMES: [&'static str; 4] = ["title_bar", "background", "shadow", Font::NAME];
 
-        Primitive::new(gl, &quad)
+    fn create_atlas_builder() -> AtlasBuilder {
+        let mut builder = AtlasBuilder::new();
+        builder.add("title_bar", Image::from_raw(&[80, 80, 80, 255], 1, 1));
+        builder.add("background", Image::from_raw(&[50, 50, 50, 255], 1, 1));
+        builder.add("shadow", Image::from_raw(&[255, 255, 255, 255], 1, 1));
+        builder.add(Font::NAME, Font::load_image());
+        builder
     }
 
-    fn create_shadow(gl: GL) -> Primitive {
-        let mut quad = Geometry::<Vertex>::quad();
-
-        quad.vertices[0].uv = [0.0, 3.0 / 4.0];
-        quad.vertices[1].uv = [1.0, 3.0 / 4.0];
-        quad.vertices[2].uv = [1.0, 4.0 / 4.0];
-        quad.vertices[3].uv = [0.0, 4.0 / 4.0];
+    /// Returns one quad for each image of the atlas, single pixel ones sampling their center
+    fn create_sprites(gl: &GL, atlas: &Atlas) -> HashMap<String, Primitive> {
+        atlas
+            .names()
+            .map(|name| {
+                let rect = atlas.get_rect(name).unwrap();
+                let uv = if rect.width == 1 && rect.height == 1 {
+                    atlas.get_center_uv(name)
+                } else {
+                    atlas.get_uv(name)
+                };
+                (name.clone(), Gui::create_sprite(gl.clone(), &uv.unwrap()))
+            })
+            .collect()
+    }
 
-        Primitive::new(gl, &quad)
+    fn create_proj(width: u32, height: u32) -> Matrix4<f32> {
+        nalgebra::Orthographic3::new(0.0, width as f32, height as f32, 0.0, 0.125, 101.0)
+            .to_homogeneous()
     }
 
     pub fn new(gl: &GL, width: u32, height: u32) -> Self {
-        let pipeline = GuiPipeline::new(&gl);
+        let pipeline = GuiPipeline::new(gl);
 
         let view = Isometry3::look_at_rh(
             &Point3::new(0.0, 0.0, 100.5),
@@ -259,25 +273,18 @@ impl Gui {
         )
         .to_homogeneous();
 
-        let proj =
-            nalgebra::Orthographic3::new(0.0, width as f32, height as f32, 0.0, 0.125, 101.0)
-                .to_homogeneous();
+        let proj = Gui::create_proj(width, height);
 
         let quad = Gui::create_quad(gl.clone());
-        let background = Gui::create_background(gl.clone());
-        let title_bar = Gui::create_title_bar(gl.clone());
-        let shadow = Gui::create_shadow(gl.clone());
-
-        let pixels = &[
-            80, 80, 80, 255, // Title color
-            50, 50, 50, 255, // Body color
-            50, 50, 50, 255, // Red color
-            255, 255, 255, 255, // Shadow color
-        ];
-        let image = Image::from_raw(pixels, 1, 4);
-        let texture = Texture::from_image(gl.clone(), &image);
 
-        let font = Font::new(gl.clone());
+        let atlas_builder = Gui::create_atlas_builder();
+        let atlas = atlas_builder
+            .build()
+            .expect("Failed to build GUI atlas");
+        let texture = Texture::from_image_with_sampler(gl.clone(), &atlas.image, Sampler::nearest());
+        let sprites = Gui::create_sprites(gl, &atlas);
+
+        let font = Font::new(gl.clone(), &atlas);
 
         let margin = 3;
         let title_height = font.tile_height + margin * 2;
@@ -289,10 +296,10 @@ impl Gui {
             view,
             proj,
             quad,
-            background,
-            title_bar,
-            shadow,
+            atlas_builder,
+            atlas,
             texture,
+            sprites,
             windows: vec![],
             windows_order: vec![],
             font,
@@ -302,11 +309,52 @@ impl Gui {
         }
     }
 
+    /// Updates the projection to match the new canvas size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.proj = Gui::create_proj(width, height);
+    }
+
+    /// Adds an image to the atlas, which windows can show in their title bar by name.
+    /// An icon with the name of an existing one replaces it, except for `RESERVED_NAMES`
+    pub fn add_icon(&mut self, name: &str, image: Image) -> Result<(), String> {
+        if Gui::RESERVED_NAMES.contains(&name) {
+            return Err(format!("Icon name reserved by the GUI: {}", name));
+        }
+
+        let mut builder = std::mem::take(&mut self.atlas_builder);
+        builder.add(name, image);
+        let atlas = builder.build();
+        self.atlas_builder = builder;
+        let atlas = atlas?;
+
+        let gl = &self.pipeline.program.gl;
+        self.texture = Texture::from_image_with_sampler(gl.clone(), &atlas.image, Sampler::nearest());
+        self.sprites = Gui::create_sprites(gl, &atlas);
+        self.font = Font::new(gl.clone(), &atlas);
+        self.atlas = atlas;
+        Ok(())
+    }
+
+    /// Returns whether the atlas contains an icon with the specified name
+    pub fn has_icon(&self, name: &str) -> bool {
+        self.atlas.get_rect(name).is_some()
+    }
+
     pub fn add_window(&mut self, window: Window) {
         self.windows_order.push(self.windows.len());
         self.windows.push(window);
     }
 
+    /// Removes all the windows
+    pub fn clear_windows(&mut self) {
+        self.windows.clear();
+        self.windows_order.clear();
+        self.focus = None;
+        self.dragging = false;
+    }
+
     // Returns whether input has been handled or not
     pub fn handle_mouse(&mut self, mouse: &Mouse) -> bool {
         let mut handled = false;
@@ -363,11 +411,31 @@ impl Gui {
             let window = &mut self.windows[*self.windows_order.last().unwrap()];
             window.pos.x += mouse.drag.x;
             window.pos.y -= mouse.drag.y;
+            handled = true;
         }
 
         handled
     }
 
+    /// Returns the size of the area below the title bar where the window content is drawn
+    pub fn get_content_size(&self, window: &Window) -> Vector2<u32> {
+        // Windows smaller than their margins and title bar have no content
+        Vector2::new(
+            window.width.saturating_sub(Window::MARGIN * 2),
+            window
+                .height
+                .saturating_sub(Window::MARGIN * 2 + self.title_height),
+        )
+    }
+
+    /// Returns whether the mouse is hovering any window
+    pub fn contains(&self, mouse: &Mouse) -> bool {
+        let mouse_y = self.height as i32 - mouse.pos.y;
+        self.windows
+            .iter()
+            .any(|window| window.contains(mouse.pos.x, mouse_y))
+    }
+
     pub fn draw(&self) {
         self.pipeline.program.gl.clear(GL::DEPTH_BUFFER_BIT);
         self.pipeline.program.bind();
@@ -417,7 +485,7 @@ impl Gui {
                 z + 0.2,
             ));
         self.pipeline.set_transform(&transform);
-        self.pipeline.draw(&self.title_bar);
+        self.pipeline.draw(&self.sprites["title_bar"]);
 
         self.pipeline.set_color(&[1.0, 1.0, 1.0, 1.0]);
 
@@ -434,7 +502,7 @@ impl Gui {
                 z + 0.1,
             ));
         self.pipeline.set_transform(&transform);
-        self.pipeline.draw(&self.background);
+        self.pipeline.draw(&self.sprites["background"]);
 
         if is_focused {
             self.pipeline.set_color(&[0.8, 0.3, 0.0, 0.4]);
@@ -455,12 +523,26 @@ impl Gui {
                 z,
             ));
         self.pipeline.set_transform(&transform);
-        self.pipeline.draw(&self.shadow);
+        self.pipeline.draw(&self.sprites["shadow"]);
 
-        // Text
-        self.font.texture.bind();
         self.pipeline.set_color(&[1.0, 1.0, 1.0, 1.0]);
 
+        // Icon at the right end of the title bar
+        if let Some(icon) = window.icon.as_ref().and_then(|icon| self.sprites.get(icon)) {
+            let size = self.font.tile_height as f32;
+            let transform = Matrix4::identity()
+                .append_nonuniform_scaling(&Vector3::new(size, size, 0.0))
+                .append_translation(&Vector3::new(
+                    window.pos.x as f32 + window.width as f32 - size - 3.0,
+                    window.pos.y as f32 + 3.0,
+                    z + 0.3,
+                ));
+            self.pipeline.set_transform(&transform);
+            self.pipeline.draw(icon);
+        }
+
+        // Text
+
         // Draw window title name
         for (i, c) in window.name.chars().enumerate() {
             let transform = Matrix4::identity()
@@ -537,6 +619,8 @@ impl Gui {
         // Draw image
         if let Some(image) = window.image.as_ref() {
             self.draw_image(window, z, image);
+            // Restore the atlas for the next window
+            self.texture.bind();
         }
     }
 
@@ -545,14 +629,16 @@ impl Gui {
 
         //self.pipeline.program.gl.active_texture(GL::TEXTURE0);
         self.pipeline.program.gl.bind_texture(GL::TEXTURE_2D, Some(&image.texture));
+        stats::count_state_changes(1);
 
         self.pipeline.set_color(&[1.0, 1.0, 1.0, 1.0]);
         // @todo Consider refactoring either window margin or title height
         // Image
+        let content_size = self.get_content_size(window);
         let transform = Matrix4::identity()
             .append_nonuniform_scaling(&Vector3::new(
-                (window.width - Window::MARGIN * 2) as f32,
-                (window.height - Window::MARGIN * 2 - self.title_height) as f32,
+                content_size.x as f32,
+                content_size.y as f32,
                 0.0,
             ))
             .append_translation(&Vector3::new(
@@ -577,6 +663,12 @@ impl Text {
     }
 }
 
+impl Default for Text {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<&str> for Text {
     fn from(value: &str) -> Self {
         Self {
@@ -599,7 +691,7 @@ impl DerefMut for Text {
     }
 }
 
-trait Element {
+pub trait Element {
     /// Draw a GUI element within a window
     /// @param gui Object responsible to actually draw the element
     fn draw(&self, gui: &Gui, window: &Window, z: f32);
@@ -634,6 +726,8 @@ pub struct Window {
     // @todo Figure out how to use Option<dyn Element> here
     pub text: Option<Text>,
     pub image: Option<GuiImage>,
+    /// Name of an icon of the GUI atlas shown in the title bar
+    pub icon: Option<String>,
 }
 
 impl Window {
@@ -649,6 +743,7 @@ impl Window {
             name: String::from("Test window"),
             text: None,
             image: None,
+            icon: None,
         }
     }
 
@@ -666,6 +761,14 @@ impl Window {
         x_in_title && y_in_title
     }
 
+    pub fn get_pos(&self) -> Vector2<i32> {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: Vector2<i32>) {
+        self.pos = pos;
+    }
+
     pub fn get_width(&self) -> u32 {
         self.width
     }
@@ -716,7 +819,6 @@ impl Geometry<FontVertex> {
 }
 
 struct Font {
-    texture: Texture,
     tile_width: u32,
     tile_height: u32,
     /// This primitive is using a special vertex buffer with 4 vertices
@@ -725,60 +827,70 @@ struct Font {
 }
 
 impl Font {
+    /// Name of the font image in the GUI atlas
+    const NAME: &'static str = "font";
+
+    fn load_image() -> Image {
+        let data = include_bytes!("../../res/font/spd.png");
+        Image::from_png(data).expect("Failed to decode font image")
+    }
+
+    /// Returns the UVs of each glyph of the font image, found at the specified area of the atlas
     fn create_uvs(
-        image_width: u32,
-        image_height: u32,
+        atlas_width: u32,
+        atlas_height: u32,
+        rect: &Rect,
         tile_width: u32,
         tile_height: u32,
     ) -> Vec<UV> {
-        let row_count = image_height / tile_height;
-        let column_count = image_width / tile_width;
+        let row_count = rect.height / tile_height;
+        let column_count = rect.width / tile_width;
+        let uv = |x: u32, y: u32| {
+            [
+                (rect.x + x) as f32 / atlas_width as f32,
+                (rect.y + y) as f32 / atlas_height as f32,
+            ]
+        };
 
         let expected_column_count = 32;
         assert!(column_count >= expected_column_count);
 
-        let mut uvs: Vec<UV> = vec![];
-        uvs.reserve((row_count * expected_column_count * 4) as usize);
+        let mut uvs: Vec<UV> = Vec::with_capacity((row_count * expected_column_count * 4) as usize);
 
         for i in 0..row_count {
             for j in 0..expected_column_count {
                 // 4 UVs
 
                 // Bottom-left
-                uvs.push([
-                    (j * tile_width) as f32 / image_width as f32,
-                    (i * tile_height + tile_height) as f32 / image_height as f32,
-                ]);
+                uvs.push(uv(j * tile_width, i * tile_height + tile_height));
                 // Bottom-right
-                uvs.push([
-                    (j * tile_width + tile_width) as f32 / image_width as f32,
-                    (i * tile_height + tile_height) as f32 / image_height as f32,
-                ]);
+                uvs.push(uv(j * tile_width + tile_width, i * tile_height + tile_height));
                 // Top-right
-                uvs.push([
-                    (j * tile_width + tile_width) as f32 / image_width as f32,
-                    (i * tile_height) as f32 / image_height as f32,
-                ]);
+                uvs.push(uv(j * tile_width + tile_width, i * tile_height));
                 // Top-left
-                uvs.push([
-                    (j * tile_width) as f32 / image_width as f32,
-                    (i * tile_height) as f32 / image_height as f32,
-                ]);
+                uvs.push(uv(j * tile_width, i * tile_height));
             }
         }
 
         uvs
     }
 
-    pub fn new(gl: GL) -> Self {
-        let data = include_bytes!("../../res/font/spd.png");
-        let image = Image::from_png(data);
-
-        let texture = Texture::from_image(gl.clone(), &image);
+    /// Returns the font drawing glyphs from its image in the atlas,
+    /// whose texture is sampled with nearest filtering as glyphs are drawn at their pixel size
+    pub fn new(gl: GL, atlas: &Atlas) -> Self {
+        let rect = atlas
+            .get_rect(Font::NAME)
+            .expect("GUI atlas without font");
 
         let tile_width = 8;
         let tile_height = 13;
-        let uvs = Font::create_uvs(image.width, image.height, tile_width, tile_height);
+        let uvs = Font::create_uvs(
+            atlas.image.width,
+            atlas.image.height,
+            &rect,
+            tile_width,
+            tile_height,
+        );
         let uvs_size = uvs.len() * std::mem::size_of::<UV>();
 
         // Make a quad with vertices with no UVs
@@ -786,8 +898,7 @@ impl Font {
         let vertices_size = quad.vertices.len() * std::mem::size_of::<FontVertex>();
 
         // Make a vertex buffer with 4 (position,color) at the beginning, and then all the various UVs
-        let mut vertex_buffer = Vec::<u8>::new();
-        vertex_buffer.resize(vertices_size + uvs_size, 0);
+        let mut vertex_buffer = vec![0u8; vertices_size + uvs_size];
 
         // Split it
         let (vb_vertices, vb_uvs) = vertex_buffer.split_at_mut(vertices_size);
@@ -805,7 +916,6 @@ impl Font {
         let primitive = Primitive::from_raw(gl.clone(), &vertex_buffer, &quad.indices);
 
         Self {
-            texture,
             tile_width,
             tile_height,
             primitive,
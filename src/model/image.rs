@@ -1,7 +1,105 @@
+use std::fmt;
+use web_sys::WebGlRenderingContext as GL;
+use crate::model::image_format::{self, ImageFormat};
+
+/// Reasons an image can not be decoded
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageError {
+    /// The data is corrupted or not in the expected format
+    InvalidData(String),
+    /// The image is valid but uses a feature the decoder does not handle
+    Unsupported(String),
+    /// The encoder failed to write the image
+    EncodingFailed(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::InvalidData(reason) => write!(f, "Invalid image data: {}", reason),
+            ImageError::Unsupported(feature) => write!(f, "Unsupported image: {}", feature),
+            ImageError::EncodingFailed(reason) => write!(f, "Failed to encode image: {}", reason),
+        }
+    }
+}
+
+impl From<png::DecodingError> for ImageError {
+    fn from(error: png::DecodingError) -> Self {
+        ImageError::InvalidData(error.to_string())
+    }
+}
+
+impl From<png::EncodingError> for ImageError {
+    fn from(error: png::EncodingError) -> Self {
+        ImageError::EncodingFailed(error.to_string())
+    }
+}
+
+impl From<ImageError> for wasm_bindgen::JsValue {
+    fn from(error: ImageError) -> Self {
+        wasm_bindgen::JsValue::from_str(&error.to_string())
+    }
+}
+
+/// Channels of each pixel, one byte per channel in an `Image`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelFormat {
+    Rgba,
+    Rgb,
+    /// Single gray channel, sampled as `(l, l, l, 1)`
+    Luminance,
+    /// Gray and alpha channels, sampled as `(l, l, l, a)`
+    LuminanceAlpha,
+    /// Single alpha channel, sampled as `(0, 0, 0, a)`
+    Alpha,
+}
+
+impl PixelFormat {
+    pub fn channels(self) -> u32 {
+        match self {
+            PixelFormat::Rgba => 4,
+            PixelFormat::Rgb => 3,
+            PixelFormat::LuminanceAlpha => 2,
+            PixelFormat::Luminance | PixelFormat::Alpha => 1,
+        }
+    }
+
+    /// Number of channels holding a color rather than an opacity
+    pub fn color_channels(self) -> usize {
+        match self {
+            PixelFormat::Rgba | PixelFormat::Rgb => 3,
+            PixelFormat::Luminance | PixelFormat::LuminanceAlpha => 1,
+            PixelFormat::Alpha => 0,
+        }
+    }
+
+    /// Index of the alpha channel within a pixel, if any
+    pub fn alpha_channel(self) -> Option<usize> {
+        match self {
+            PixelFormat::Rgba => Some(3),
+            PixelFormat::LuminanceAlpha => Some(1),
+            PixelFormat::Alpha => Some(0),
+            PixelFormat::Rgb | PixelFormat::Luminance => None,
+        }
+    }
+
+    pub fn to_gl(self) -> u32 {
+        match self {
+            PixelFormat::Rgba => GL::RGBA,
+            PixelFormat::Rgb => GL::RGB,
+            PixelFormat::Luminance => GL::LUMINANCE,
+            PixelFormat::LuminanceAlpha => GL::LUMINANCE_ALPHA,
+            PixelFormat::Alpha => GL::ALPHA,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Image {
     pub data: Vec<u8>,
     pub width: u32,
     pub height: u32,
+    pub format: PixelFormat,
 }
 
 impl Image {
@@ -11,6 +109,7 @@ impl Image {
             data: vec![255, 255, 255, 255],
             width: 1,
             height: 1,
+            format: PixelFormat::Rgba,
         }
     }
 
@@ -21,24 +120,435 @@ impl Image {
 
     /// Creates an image from raw data as vector
     pub fn from_vec(data: Vec<u8>, width: u32, height: u32) -> Self {
-        let channels = 4; // RGBA
-        assert!(data.len() as u32 == width * height * channels);
+        Self::from_vec_with_format(data, width, height, PixelFormat::Rgba)
+    }
+
+    /// Creates an image from raw data with the specified channels
+    pub fn from_vec_with_format(data: Vec<u8>, width: u32, height: u32, format: PixelFormat) -> Self {
+        assert!(data.len() as u32 == width * height * format.channels());
         Self {
             data,
             width,
             height,
+            format,
+        }
+    }
+
+    /// Returns a copy of this image expanded to RGBA, as sampled by a texture
+    pub fn to_rgba(&self) -> Image {
+        let data = match self.format {
+            PixelFormat::Rgba => self.data.clone(),
+            PixelFormat::Rgb => self
+                .data
+                .chunks(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            PixelFormat::Luminance => self.data.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+            PixelFormat::LuminanceAlpha => self
+                .data
+                .chunks(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            PixelFormat::Alpha => self.data.iter().flat_map(|&a| [0, 0, 0, a]).collect(),
+        };
+        Image::from_vec(data, self.width, self.height)
+    }
+
+    /// Decodes an image of any supported format, detected from its first bytes, into RGBA
+    pub fn decode(data: &[u8]) -> Result<Self, ImageError> {
+        match ImageFormat::detect(data) {
+            Some(ImageFormat::Png) => Self::from_png(data),
+            Some(ImageFormat::Jpeg) => Self::from_jpeg(data),
+            Some(ImageFormat::Bmp) => Self::from_bmp(data),
+            Some(ImageFormat::Tga) => Self::from_tga(data),
+            Some(ImageFormat::Pnm) => Self::from_pnm(data),
+            None => Err(ImageError::Unsupported("unknown image format".to_string())),
         }
     }
 
-    /// Creates an image from png data
-    pub fn from_png(png_data: &[u8]) -> Self {
-        let decoder = png::Decoder::new(png_data);
-        let (info, mut reader) = decoder.read_info().expect("Failed reading png info");
-        let mut data: Vec<u8> = vec![0; info.buffer_size()];
-        reader
-            .next_frame(data.as_mut_slice())
-            .expect("Failed to read png frame");
+    /// Decodes a baseline or progressive JPEG into RGBA
+    pub fn from_jpeg(data: &[u8]) -> Result<Self, ImageError> {
+        image_format::decode_jpeg(data)
+    }
+
+    /// Decodes a BMP of any bit count, with bit fields or run length encoding, into RGBA
+    pub fn from_bmp(data: &[u8]) -> Result<Self, ImageError> {
+        image_format::decode_bmp(data)
+    }
+
+    /// Decodes a color mapped, true color or grayscale TGA into RGBA
+    pub fn from_tga(data: &[u8]) -> Result<Self, ImageError> {
+        image_format::decode_tga(data)
+    }
+
+    /// Decodes a binary or ASCII PPM or PGM into RGBA
+    pub fn from_pnm(data: &[u8]) -> Result<Self, ImageError> {
+        image_format::decode_pnm(data)
+    }
+
+    /// Encodes the image as an 8 bits PNG, keeping its channels except for alpha only
+    /// images, which PNG can not represent and become black with transparency
+    pub fn to_png(&self) -> Result<Vec<u8>, ImageError> {
+        let (color_type, data) = match self.format {
+            PixelFormat::Rgba => (png::ColorType::RGBA, &self.data),
+            PixelFormat::Rgb => (png::ColorType::RGB, &self.data),
+            PixelFormat::Luminance => (png::ColorType::Grayscale, &self.data),
+            PixelFormat::LuminanceAlpha => (png::ColorType::GrayscaleAlpha, &self.data),
+            PixelFormat::Alpha => return self.to_rgba().to_png(),
+        };
+
+        let mut png_data = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, self.width, self.height);
+            encoder.set_color(color_type);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(data)?;
+        }
+        Ok(png_data)
+    }
+
+    /// Decodes a PNG of any color type, bit depth and interlacing into RGBA with 8 bits per channel.
+    /// Colors are converted to sRGB when the image specifies another gamma
+    pub fn from_png(png_data: &[u8]) -> Result<Self, ImageError> {
+        let mut decoder = png::Decoder::new(png_data);
+        // Palettes, transparency chunks and low bit depths become 8 bit channels
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let (info, mut reader) = decoder.read_info()?;
+
+        let (color_type, bit_depth) = reader.output_color_type();
+        if bit_depth != png::BitDepth::Eight {
+            return Err(ImageError::Unsupported(format!("{:?} bit PNG output", bit_depth)));
+        }
+        let format = match color_type {
+            png::ColorType::RGBA => PixelFormat::Rgba,
+            png::ColorType::RGB => PixelFormat::Rgb,
+            png::ColorType::Grayscale => PixelFormat::Luminance,
+            png::ColorType::GrayscaleAlpha => PixelFormat::LuminanceAlpha,
+            png::ColorType::Indexed => {
+                return Err(ImageError::Unsupported("indexed PNG output".to_string()))
+            }
+        };
+
+        let mut data: Vec<u8> = vec![0; reader.output_buffer_size()];
+        reader.next_frame(data.as_mut_slice())?;
+
+        if let Some(gamma) = png_gamma(png_data) {
+            correct_gamma(&mut data, format, gamma);
+        }
+
+        let image = Image::from_vec_with_format(data, info.width, info.height, format);
+        Ok(image.to_rgba())
+    }
+}
+
+/// Returns the gamma PNG samples are encoded with, when it is not the sRGB one.
+/// An sRGB chunk takes precedence over a gAMA chunk, as the specification requires
+fn png_gamma(png_data: &[u8]) -> Option<f32> {
+    // Chunks are a big endian length, a type, the data and a CRC, after an 8 bytes signature
+    let mut gamma = None;
+    let mut offset = 8;
+    while offset + 8 <= png_data.len() {
+        let mut length = [0; 4];
+        length.copy_from_slice(&png_data[offset..offset + 4]);
+        let length = u32::from_be_bytes(length) as usize;
+        let chunk_type = &png_data[offset + 4..offset + 8];
+        let chunk_data = png_data.get(offset + 8..offset + 8 + length)?;
+
+        match chunk_type {
+            b"sRGB" => return None,
+            b"gAMA" if length == 4 => {
+                let mut value = [0; 4];
+                value.copy_from_slice(chunk_data);
+                let value = u32::from_be_bytes(value);
+                if value > 0 {
+                    gamma = Some(value as f32 / 100_000.0);
+                }
+            }
+            // Color chunks come before the image data
+            b"IDAT" => break,
+            _ => (),
+        }
+
+        offset += 12 + length;
+    }
+
+    // Close enough to the sRGB curve not to be worth resampling
+    gamma.filter(|gamma| (gamma * 2.2 - 1.0).abs() > 0.01)
+}
+
+/// Converts color channels encoded with the specified gamma to sRGB, leaving alpha linear
+fn correct_gamma(data: &mut [u8], format: PixelFormat, gamma: f32) {
+    let exponent = 1.0 / (gamma * 2.2);
+    let table = transfer_table(|c| c.powf(exponent));
+    map_color_channels(data, format, &table);
+}
+
+/// Replaces the color channels of every pixel through a lookup table, leaving alpha linear
+fn map_color_channels(data: &mut [u8], format: PixelFormat, table: &[u8; 256]) {
+    let color_channels = format.color_channels();
+    for pixel in data.chunks_mut(format.channels() as usize) {
+        for channel in &mut pixel[..color_channels] {
+            *channel = table[*channel as usize];
+        }
+    }
+}
+
+/// Returns a lookup table applying a transfer function to values in `[0, 1]`
+fn transfer_table(transfer: impl Fn(f32) -> f32) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        *value = (transfer(i as f32 / 255.0) * 255.0).round() as u8;
+    }
+    table
+}
+
+/// Returns for each destination texel the source texels it covers with their weights,
+/// which sum to one, so that every source texel contributes to the result
+fn box_weights(src_len: u32, dst_len: u32) -> Vec<Vec<(usize, f32)>> {
+    let scale = src_len as f32 / dst_len as f32;
+    (0..dst_len)
+        .map(|i| {
+            let start = i as f32 * scale;
+            let end = (i + 1) as f32 * scale;
+            let first = start.floor() as u32;
+            let last = (end.ceil() as u32).min(src_len);
+            (first..last)
+                .map(|s| {
+                    let coverage = end.min(s as f32 + 1.0) - start.max(s as f32);
+                    (s as usize, coverage / (end - start))
+                })
+                .filter(|&(_, weight)| weight > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns for each destination texel the two nearest source texels and the weight of the second,
+/// matching texel centers and clamping to the edges
+fn bilinear_weights(src_len: u32, dst_len: u32) -> Vec<(usize, usize, f32)> {
+    let scale = src_len as f32 / dst_len as f32;
+    (0..dst_len)
+        .map(|i| {
+            let position = ((i as f32 + 0.5) * scale - 0.5).clamp(0.0, (src_len - 1) as f32);
+            let first = position.floor() as usize;
+            let second = (first + 1).min(src_len as usize - 1);
+            (first, second, position - first as f32)
+        })
+        .collect()
+}
+
+/// Processing operations, which all keep the pixel format
+impl Image {
+    /// Returns whether the image has no pixels, being zero wide or tall
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    fn channels(&self) -> usize {
+        self.format.channels() as usize
+    }
+
+    /// Swaps the top and bottom rows, converting between the top-left origin of images
+    /// and the bottom-left one of framebuffers
+    pub fn flip_vertical(&mut self) {
+        let row_len = self.width as usize * self.channels();
+        let height = self.height as usize;
+        for y in 0..height / 2 {
+            let (top, bottom) = self.data.split_at_mut((height - 1 - y) * row_len);
+            top[y * row_len..(y + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+        }
+    }
+
+    /// Swaps the left and right columns
+    pub fn flip_horizontal(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+        let channels = self.channels();
+        for row in self.data.chunks_mut(self.width as usize * channels) {
+            let width = row.len() / channels;
+            for x in 0..width / 2 {
+                for c in 0..channels {
+                    row.swap(x * channels + c, (width - 1 - x) * channels + c);
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of an area of the image, or `None` if it is not within the image
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Option<Image> {
+        if x.checked_add(width)? > self.width || y.checked_add(height)? > self.height {
+            return None;
+        }
+        let channels = self.channels();
+        let mut data = Vec::with_capacity(width as usize * height as usize * channels);
+        for row in y..y + height {
+            let start = (row as usize * self.width as usize + x as usize) * channels;
+            data.extend_from_slice(&self.data[start..start + width as usize * channels]);
+        }
+        Some(Image::from_vec_with_format(data, width, height, self.format))
+    }
+
+    /// Returns the image resampled to the specified size with bilinear filtering,
+    /// which is smooth when enlarging but skips texels when shrinking more than twice
+    pub fn resize_bilinear(&self, width: u32, height: u32) -> Image {
+        // Nothing to sample from
+        if self.is_empty() {
+            return self.clone();
+        }
+        let (width, height) = (width.max(1), height.max(1));
+        let channels = self.channels();
+        let columns = bilinear_weights(self.width, width);
+        let rows = bilinear_weights(self.height, height);
+        let src_width = self.width as usize;
+
+        let mut data = Vec::with_capacity(width as usize * height as usize * channels);
+        for &(y0, y1, fy) in &rows {
+            for &(x0, x1, fx) in &columns {
+                for c in 0..channels {
+                    let texel = |x: usize, y: usize| self.data[(y * src_width + x) * channels + c] as f32;
+                    let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+                    let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+                    data.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+                }
+            }
+        }
+        Image::from_vec_with_format(data, width, height, self.format)
+    }
+
+    /// Returns the image resampled to the specified size averaging all the texels each
+    /// destination texel covers, which keeps every detail's contribution when shrinking
+    pub fn resize_box(&self, width: u32, height: u32) -> Image {
+        if self.is_empty() {
+            return self.clone();
+        }
+        let (width, height) = (width.max(1), height.max(1));
+        let channels = self.channels();
+        let columns = box_weights(self.width, width);
+        let rows = box_weights(self.height, height);
+
+        // Horizontal pass, then vertical pass
+        let mut horizontal = vec![0.0f32; width as usize * self.height as usize * channels];
+        for (y, row) in self.data.chunks(self.width as usize * channels).enumerate() {
+            for (x, weights) in columns.iter().enumerate() {
+                for c in 0..channels {
+                    horizontal[(y * width as usize + x) * channels + c] = weights
+                        .iter()
+                        .map(|&(s, weight)| row[s * channels + c] as f32 * weight)
+                        .sum();
+                }
+            }
+        }
+
+        let row_len = width as usize * channels;
+        let mut data = Vec::with_capacity(row_len * height as usize);
+        for weights in &rows {
+            for i in 0..row_len {
+                let value: f32 = weights
+                    .iter()
+                    .map(|&(s, weight)| horizontal[s * row_len + i] * weight)
+                    .sum();
+                data.push(value.round().clamp(0.0, 255.0) as u8);
+            }
+        }
+        Image::from_vec_with_format(data, width, height, self.format)
+    }
+
+    /// Returns the image enlarged to the next power of two size, which WebGL 1 requires
+    /// for mipmaps and repeating, or a copy if it already has such a size
+    pub fn to_power_of_two(&self) -> Image {
+        if self.is_empty() {
+            return self.clone();
+        }
+        let width = self.width.next_power_of_two();
+        let height = self.height.next_power_of_two();
+        if width == self.width && height == self.height {
+            self.clone()
+        } else {
+            self.resize_bilinear(width, height)
+        }
+    }
+
+    /// Returns the mipmap levels below this image, each half the size of the previous one
+    /// down to a single texel, box filtered from the previous level
+    pub fn mipmaps(&self) -> Vec<Image> {
+        let mut levels: Vec<Image> = vec![];
+        if self.is_empty() {
+            return levels;
+        }
+        let (mut width, mut height) = (self.width, self.height);
+        while width > 1 || height > 1 {
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            let previous = levels.last().unwrap_or(self);
+            let level = previous.resize_box(width, height);
+            levels.push(level);
+        }
+        levels
+    }
+
+    /// Multiplies the color channels by alpha, so that filtering and blending with
+    /// `ONE, ONE_MINUS_SRC_ALPHA` do not bleed the color of transparent texels
+    pub fn premultiply_alpha(&mut self) {
+        let alpha = match self.format.alpha_channel() {
+            Some(alpha) => alpha,
+            None => return,
+        };
+        let (channels, color_channels) = (self.channels(), self.format.color_channels());
+        for pixel in self.data.chunks_mut(channels) {
+            let a = pixel[alpha] as u32;
+            for channel in &mut pixel[..color_channels] {
+                *channel = ((*channel as u32 * a + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Divides the color channels by alpha, reverting `premultiply_alpha` up to rounding.
+    /// Colors of fully transparent texels are lost and become black
+    pub fn unpremultiply_alpha(&mut self) {
+        let alpha = match self.format.alpha_channel() {
+            Some(alpha) => alpha,
+            None => return,
+        };
+        let (channels, color_channels) = (self.channels(), self.format.color_channels());
+        for pixel in self.data.chunks_mut(channels) {
+            let a = pixel[alpha] as u32;
+            for channel in &mut pixel[..color_channels] {
+                *channel = (*channel as u32 * 255 + a / 2)
+                    .checked_div(a)
+                    .map_or(0, |value| value.min(255) as u8);
+            }
+        }
+    }
+
+    /// Converts the color channels from the sRGB curve to linear values, leaving alpha as is
+    pub fn srgb_to_linear(&mut self) {
+        let table = transfer_table(|c| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        map_color_channels(&mut self.data, self.format, &table);
+    }
+
+    /// Converts the color channels from linear values to the sRGB curve, leaving alpha as is
+    pub fn linear_to_srgb(&mut self) {
+        let table = transfer_table(|c| {
+            if c <= 0.003_130_8 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        });
+        map_color_channels(&mut self.data, self.format, &table);
+    }
+}
 
-        Image::from_vec(data, info.width, info.height)
+impl Default for Image {
+    fn default() -> Self {
+        Self::new()
     }
 }
\ No newline at end of file
@@ -0,0 +1,607 @@
+use crate::model::{Image, ImageError, PixelFormat};
+
+/// Encodings `Image::decode` understands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// Baseline and progressive JPEG
+    Jpeg,
+    Bmp,
+    Tga,
+    /// Netpbm PPM and PGM, in binary or ASCII
+    Pnm,
+}
+
+impl ImageFormat {
+    /// Guesses the format from the first bytes of the data.
+    /// TGA has no signature, so it is only recognized by a plausible header
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']) {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else if data.len() > 2
+            && data[0] == b'P'
+            && matches!(data[1], b'2' | b'3' | b'5' | b'6')
+            && data[2].is_ascii_whitespace()
+        {
+            Some(ImageFormat::Pnm)
+        } else if TgaHeader::parse(data).is_ok() {
+            Some(ImageFormat::Tga)
+        } else {
+            None
+        }
+    }
+}
+
+fn invalid<T>(reason: &str) -> Result<T, ImageError> {
+    Err(ImageError::InvalidData(reason.to_string()))
+}
+
+fn unsupported<T>(feature: String) -> Result<T, ImageError> {
+    Err(ImageError::Unsupported(feature))
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> Result<u16, ImageError> {
+    match data.get(offset..offset + 2) {
+        Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+        None => invalid("unexpected end of data"),
+    }
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32, ImageError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => invalid("unexpected end of data"),
+    }
+}
+
+/// Largest number of pixels decoders accept, more than textures of most devices can hold
+const MAX_PIXELS: usize = 1 << 26;
+
+/// Returns the number of bytes of an RGBA image, failing for sizes a small header could
+/// declare to exhaust memory
+fn rgba_size(width: u32, height: u32) -> Result<usize, ImageError> {
+    match (width as usize).checked_mul(height as usize) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(pixels * 4),
+        _ => unsupported(format!("image of {}x{} pixels", width, height)),
+    }
+}
+
+/// Decodes a JPEG into RGBA, converting grayscale and CMYK images
+pub(crate) fn decode_jpeg(data: &[u8]) -> Result<Image, ImageError> {
+    let jpeg_error = |error| match error {
+        jpeg_decoder::Error::Unsupported(feature) => {
+            ImageError::Unsupported(format!("JPEG {:?}", feature))
+        }
+        error => ImageError::InvalidData(error.to_string()),
+    };
+
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    // Check the size from the header before allocating the pixels
+    decoder.read_info().map_err(jpeg_error)?;
+    let info = match decoder.info() {
+        Some(info) => info,
+        None => return invalid("missing JPEG frame"),
+    };
+    let (width, height) = (info.width as u32, info.height as u32);
+    rgba_size(width, height)?;
+
+    let pixels = decoder.decode().map_err(jpeg_error)?;
+
+    let image = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            Image::from_vec_with_format(pixels, width, height, PixelFormat::Luminance)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            Image::from_vec_with_format(pixels, width, height, PixelFormat::Rgb)
+        }
+        jpeg_decoder::PixelFormat::CMYK32 => {
+            let rgb = pixels
+                .chunks(4)
+                .flat_map(|cmyk| {
+                    let white = 255 - cmyk[3] as u32;
+                    let channel = |ink: u8| ((255 - ink as u32) * white / 255) as u8;
+                    [channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2])]
+                })
+                .collect();
+            Image::from_vec_with_format(rgb, width, height, PixelFormat::Rgb)
+        }
+        jpeg_decoder::PixelFormat::L16 => return unsupported("16 bit JPEG".to_string()),
+    };
+    Ok(image.to_rgba())
+}
+
+/// How the bits of a BMP pixel map to channels
+struct BitMasks {
+    masks: [u32; 4],
+}
+
+impl BitMasks {
+    /// Extracts a channel scaled to 8 bits, or `default` for channels without a mask
+    fn channel(&self, pixel: u32, channel: usize, default: u8) -> u8 {
+        let mask = self.masks[channel];
+        if mask == 0 {
+            return default;
+        }
+        let shift = mask.trailing_zeros();
+        let max = mask >> shift;
+        let value = (pixel & mask) >> shift;
+        ((value as u64 * 255 + max as u64 / 2) / max as u64) as u8
+    }
+
+    fn rgba(&self, pixel: u32) -> [u8; 4] {
+        [
+            self.channel(pixel, 0, 0),
+            self.channel(pixel, 1, 0),
+            self.channel(pixel, 2, 0),
+            self.channel(pixel, 3, 255),
+        ]
+    }
+}
+
+/// Decodes an uncompressed, bit field or run length encoded BMP into RGBA
+pub(crate) fn decode_bmp(data: &[u8]) -> Result<Image, ImageError> {
+    const RGB: u32 = 0;
+    const RLE8: u32 = 1;
+    const RLE4: u32 = 2;
+    const BITFIELDS: u32 = 3;
+    const ALPHA_BITFIELDS: u32 = 6;
+
+    if !data.starts_with(b"BM") {
+        return invalid("missing BMP signature");
+    }
+    let pixels_offset = read_u32_le(data, 10)? as usize;
+    let header_size = read_u32_le(data, 14)? as usize;
+
+    // Core headers of OS/2 have 16 bits sizes and 3 bytes palette entries
+    let (width, height, bit_count, compression, palette_entry_size) = if header_size == 12 {
+        let width = read_u16_le(data, 18)? as i32;
+        let height = read_u16_le(data, 20)? as i16 as i32;
+        (width, height, read_u16_le(data, 24)?, RGB, 3)
+    } else if header_size >= 40 {
+        let width = read_u32_le(data, 18)? as i32;
+        let height = read_u32_le(data, 22)? as i32;
+        (width, height, read_u16_le(data, 28)?, read_u32_le(data, 30)?, 4)
+    } else {
+        return unsupported(format!("BMP header of {} bytes", header_size));
+    };
+    if width <= 0 || height == 0 {
+        return invalid("empty BMP");
+    }
+    let top_down = height < 0;
+    let width = width as u32;
+    let height = height.unsigned_abs();
+    let mut rgba = vec![0u8; rgba_size(width, height)?];
+
+    // Bit fields follow a 40 bytes header, or are part of the larger ones
+    let masks = match compression {
+        BITFIELDS | ALPHA_BITFIELDS => {
+            let count = if compression == ALPHA_BITFIELDS || header_size >= 56 { 4 } else { 3 };
+            let mut masks = [0u32; 4];
+            for (i, mask) in masks.iter_mut().enumerate().take(count) {
+                *mask = read_u32_le(data, 14 + 40 + i * 4)?;
+            }
+            BitMasks { masks }
+        }
+        _ => match bit_count {
+            16 => BitMasks {
+                masks: [0x7C00, 0x03E0, 0x001F, 0],
+            },
+            // The fourth byte is usually left at zero rather than meaning transparent
+            _ => BitMasks {
+                masks: [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0],
+            },
+        },
+    };
+
+    let palette: Vec<[u8; 4]> = if bit_count <= 8 {
+        let palette_offset = 14 + header_size;
+        let declared = if header_size >= 40 { read_u32_le(data, 46)? as usize } else { 0 };
+        let count = if declared == 0 || declared > 1 << bit_count { 1 << bit_count } else { declared };
+        (0..count)
+            .map(|i| {
+                let offset = palette_offset + i * palette_entry_size;
+                match data.get(offset..offset + 3) {
+                    Some(bgr) => Ok([bgr[2], bgr[1], bgr[0], 255]),
+                    None => invalid("truncated BMP palette"),
+                }
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        vec![]
+    };
+    let color = |index: usize| palette.get(index).copied().map_or_else(|| invalid("BMP palette index out of range"), Ok);
+
+    // Rows are stored bottom-up unless the height is negative
+    let row_index = |y: u32| if top_down { y } else { height - 1 - y };
+    let pixels = match data.get(pixels_offset..) {
+        Some(pixels) => pixels,
+        None => return invalid("BMP pixel data out of bounds"),
+    };
+
+    match (compression, bit_count) {
+        (RGB, 1) | (RGB, 2) | (RGB, 4) | (RGB, 8) | (RGB, 16) | (RGB, 24) | (RGB, 32)
+        | (BITFIELDS, 16) | (BITFIELDS, 32) | (ALPHA_BITFIELDS, 16) | (ALPHA_BITFIELDS, 32) => {
+            let bits = bit_count as usize;
+            let row_size = (bits * width as usize).div_ceil(32) * 4;
+            if pixels.len() < row_size * height as usize {
+                return invalid("truncated BMP pixel data");
+            }
+            for y in 0..height {
+                let row = &pixels[y as usize * row_size..(y as usize + 1) * row_size];
+                let out_row = row_index(y) as usize * width as usize * 4;
+                for x in 0..width as usize {
+                    let pixel = match bits {
+                        1 | 2 | 4 => {
+                            let bit = x * bits;
+                            let byte = row[bit / 8];
+                            let index = (byte >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+                            color(index as usize)?
+                        }
+                        8 => color(row[x] as usize)?,
+                        16 => masks.rgba(u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32),
+                        24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                        _ => {
+                            let bytes = &row[x * 4..x * 4 + 4];
+                            masks.rgba(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                        }
+                    };
+                    rgba[out_row + x * 4..out_row + x * 4 + 4].copy_from_slice(&pixel);
+                }
+            }
+        }
+        (RLE8, 8) | (RLE4, 4) => {
+            // Pixels skipped by deltas or ends of line are left transparent
+            let mut put = |x: u32, y: u32, index: u8| -> Result<(), ImageError> {
+                if x < width && y < height {
+                    let offset = (row_index(y) as usize * width as usize + x as usize) * 4;
+                    rgba[offset..offset + 4].copy_from_slice(&color(index as usize)?);
+                }
+                Ok(())
+            };
+            let nibbles = |byte: u8, i: u32| if i & 1 == 0 { byte >> 4 } else { byte & 0x0F };
+            let is_rle4 = compression == RLE4;
+
+            let (mut x, mut y, mut i) = (0u32, 0u32, 0usize);
+            loop {
+                let (count, value) = match pixels.get(i..i + 2) {
+                    Some(pair) => (pair[0], pair[1]),
+                    None => return invalid("truncated BMP run length data"),
+                };
+                i += 2;
+                match (count, value) {
+                    (0, 0) => {
+                        x = 0;
+                        y += 1;
+                    }
+                    (0, 1) => break,
+                    (0, 2) => {
+                        let delta = match pixels.get(i..i + 2) {
+                            Some(delta) => delta,
+                            None => return invalid("truncated BMP delta"),
+                        };
+                        x += delta[0] as u32;
+                        y += delta[1] as u32;
+                        i += 2;
+                    }
+                    (0, literal) => {
+                        let literal = literal as u32;
+                        let len = if is_rle4 { literal.div_ceil(2) } else { literal } as usize;
+                        let bytes = match pixels.get(i..i + len) {
+                            Some(bytes) => bytes,
+                            None => return invalid("truncated BMP literal run"),
+                        };
+                        for j in 0..literal {
+                            let index = if is_rle4 { nibbles(bytes[j as usize / 2], j) } else { bytes[j as usize] };
+                            put(x, y, index)?;
+                            x += 1;
+                        }
+                        // Literal runs are padded to 16 bits
+                        i += len + len % 2;
+                    }
+                    (count, value) => {
+                        for j in 0..count as u32 {
+                            put(x, y, if is_rle4 { nibbles(value, j) } else { value })?;
+                            x += 1;
+                        }
+                    }
+                }
+                if y >= height {
+                    break;
+                }
+            }
+        }
+        _ => {
+            return unsupported(format!(
+                "BMP of {} bits with compression {}",
+                bit_count, compression
+            ))
+        }
+    }
+
+    Ok(Image::from_vec(rgba, width, height))
+}
+
+/// Fields of the 18 bytes header starting a TGA file
+struct TgaHeader {
+    id_length: usize,
+    has_color_map: bool,
+    image_type: u8,
+    color_map_first: usize,
+    color_map_length: usize,
+    color_map_depth: u8,
+    width: u32,
+    height: u32,
+    pixel_depth: u8,
+    descriptor: u8,
+}
+
+impl TgaHeader {
+    fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        if data.len() < 18 {
+            return invalid("truncated TGA header");
+        }
+        let header = Self {
+            id_length: data[0] as usize,
+            has_color_map: data[1] == 1,
+            image_type: data[2],
+            color_map_first: read_u16_le(data, 3)? as usize,
+            color_map_length: read_u16_le(data, 5)? as usize,
+            color_map_depth: data[7],
+            width: read_u16_le(data, 12)? as u32,
+            height: read_u16_le(data, 14)? as u32,
+            pixel_depth: data[16],
+            descriptor: data[17],
+        };
+
+        let depths: &[u8] = match header.image_type & !8 {
+            1 => &[8],
+            2 => &[15, 16, 24, 32],
+            3 => &[8],
+            _ => return invalid("unknown TGA image type"),
+        };
+        let color_map_valid = if header.image_type & !8 == 1 {
+            header.has_color_map && matches!(header.color_map_depth, 15 | 16 | 24 | 32)
+        } else {
+            data[1] <= 1
+        };
+        if !depths.contains(&header.pixel_depth)
+            || !color_map_valid
+            || header.width == 0
+            || header.height == 0
+            || header.descriptor & 0xC0 != 0
+        {
+            return invalid("inconsistent TGA header");
+        }
+        Ok(header)
+    }
+
+    fn color_map_size(&self) -> usize {
+        if self.has_color_map {
+            self.color_map_length * (self.color_map_depth as usize).div_ceil(8)
+        } else {
+            0
+        }
+    }
+}
+
+/// Reads a little endian true color value of the specified depth as RGBA
+fn tga_color(bytes: &[u8], depth: u8, alpha_bits: u8) -> [u8; 4] {
+    match depth {
+        15 | 16 => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let channel = |shift: u16| {
+                let value = (value >> shift) & 0x1F;
+                ((value << 3) | (value >> 2)) as u8
+            };
+            let alpha = if depth == 16 && alpha_bits > 0 && value & 0x8000 == 0 { 0 } else { 255 };
+            [channel(10), channel(5), channel(0), alpha]
+        }
+        24 => [bytes[2], bytes[1], bytes[0], 255],
+        _ => [bytes[2], bytes[1], bytes[0], bytes[3]],
+    }
+}
+
+/// Decodes a color mapped, true color or grayscale TGA, raw or run length encoded, into RGBA
+pub(crate) fn decode_tga(data: &[u8]) -> Result<Image, ImageError> {
+    let header = TgaHeader::parse(data)?;
+    let alpha_bits = header.descriptor & 0x0F;
+    let color_map_type = header.image_type & !8 == 1;
+    let grayscale = header.image_type & !8 == 3;
+    let rle = header.image_type & 8 != 0;
+
+    let color_map_offset = 18 + header.id_length;
+    let pixels_offset = color_map_offset + header.color_map_size();
+    let color_map: Vec<[u8; 4]> = if color_map_type {
+        let entry_size = (header.color_map_depth as usize).div_ceil(8);
+        match data.get(color_map_offset..pixels_offset) {
+            Some(entries) => entries
+                .chunks(entry_size)
+                .map(|entry| tga_color(entry, header.color_map_depth, alpha_bits))
+                .collect(),
+            None => return invalid("truncated TGA color map"),
+        }
+    } else {
+        vec![]
+    };
+
+    let pixel_size = (header.pixel_depth as usize).div_ceil(8);
+    let read_pixel = |bytes: &[u8]| -> Result<[u8; 4], ImageError> {
+        if color_map_type {
+            let index = bytes[0] as usize;
+            match index
+                .checked_sub(header.color_map_first)
+                .and_then(|index| color_map.get(index))
+            {
+                Some(color) => Ok(*color),
+                None => invalid("TGA color map index out of range"),
+            }
+        } else if grayscale {
+            Ok([bytes[0], bytes[0], bytes[0], 255])
+        } else {
+            Ok(tga_color(bytes, header.pixel_depth, alpha_bits))
+        }
+    };
+
+    let mut rgba = vec![0u8; rgba_size(header.width, header.height)?];
+    let pixel_count = header.width as usize * header.height as usize;
+    let mut pixels = match data.get(pixels_offset..) {
+        Some(pixels) => pixels,
+        None => return invalid("truncated TGA pixel data"),
+    };
+    // Decoded pixels in file order
+    let mut decoded: Vec<[u8; 4]> = Vec::with_capacity(pixel_count.min(pixels.len()));
+    while decoded.len() < pixel_count {
+        if rle {
+            let packet = match pixels.first() {
+                Some(packet) => *packet,
+                None => return invalid("truncated TGA run length data"),
+            };
+            let count = (packet & 0x7F) as usize + 1;
+            let run = if packet & 0x80 != 0 { pixel_size } else { pixel_size * count };
+            let bytes = match pixels.get(1..1 + run) {
+                Some(bytes) => bytes,
+                None => return invalid("truncated TGA run length data"),
+            };
+            if packet & 0x80 != 0 {
+                let pixel = read_pixel(bytes)?;
+                decoded.extend(std::iter::repeat_n(pixel, count));
+            } else {
+                for bytes in bytes.chunks(pixel_size) {
+                    decoded.push(read_pixel(bytes)?);
+                }
+            }
+            pixels = &pixels[1 + run..];
+        } else {
+            let bytes = match pixels.get(..pixel_size) {
+                Some(bytes) => bytes,
+                None => return invalid("truncated TGA pixel data"),
+            };
+            decoded.push(read_pixel(bytes)?);
+            pixels = &pixels[pixel_size..];
+        }
+    }
+    // Runs may cross the end of the image
+    decoded.truncate(pixel_count);
+
+    // Rows start at the bottom-left corner unless the descriptor says otherwise
+    let right_to_left = header.descriptor & 0x10 != 0;
+    let top_to_bottom = header.descriptor & 0x20 != 0;
+    let (width, height) = (header.width as usize, header.height as usize);
+    for (i, pixel) in decoded.iter().enumerate() {
+        let (mut x, mut y) = (i % width, i / width);
+        if right_to_left {
+            x = width - 1 - x;
+        }
+        if !top_to_bottom {
+            y = height - 1 - y;
+        }
+        let offset = (y * width + x) * 4;
+        rgba[offset..offset + 4].copy_from_slice(pixel);
+    }
+
+    Ok(Image::from_vec(rgba, header.width, header.height))
+}
+
+/// Splits the ASCII part of a Netpbm file into tokens, skipping comments
+struct PnmTokens<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PnmTokens<'a> {
+    fn next_token(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.offset)? {
+                b'#' => {
+                    while self.data.get(self.offset).is_some_and(|&c| c != b'\n') {
+                        self.offset += 1;
+                    }
+                }
+                c if c.is_ascii_whitespace() => self.offset += 1,
+                _ => break,
+            }
+        }
+        let start = self.offset;
+        while self
+            .data
+            .get(self.offset)
+            .is_some_and(|c| !c.is_ascii_whitespace() && *c != b'#')
+        {
+            self.offset += 1;
+        }
+        Some(&self.data[start..self.offset])
+    }
+
+    fn next_number(&mut self) -> Result<u32, ImageError> {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None => return invalid("unexpected end of PNM data"),
+        };
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .map_or_else(|| invalid("invalid PNM number"), Ok)
+    }
+}
+
+/// Decodes a PGM or PPM, in binary or ASCII, into RGBA
+pub(crate) fn decode_pnm(data: &[u8]) -> Result<Image, ImageError> {
+    let (channels, binary) = match data.get(..2) {
+        Some(b"P2") => (1, false),
+        Some(b"P3") => (3, false),
+        Some(b"P5") => (1, true),
+        Some(b"P6") => (3, true),
+        _ => return invalid("missing PGM or PPM signature"),
+    };
+
+    let mut tokens = PnmTokens { data, offset: 2 };
+    let width = tokens.next_number()?;
+    let height = tokens.next_number()?;
+    let max = tokens.next_number()?;
+    if width == 0 || height == 0 {
+        return invalid("empty PNM");
+    }
+    if max == 0 || max > 65535 {
+        return invalid("PNM maximum value out of range");
+    }
+
+    let sample_count = rgba_size(width, height)? / 4 * channels;
+    let scale = |value: u32| -> Result<u8, ImageError> {
+        if value > max {
+            return invalid("PNM sample above the maximum value");
+        }
+        Ok(((value * 255 + max / 2) / max) as u8)
+    };
+
+    let samples: Vec<u8> = if binary {
+        // A single whitespace separates the header from the samples
+        let start = tokens.offset + 1;
+        let sample_size = if max < 256 { 1 } else { 2 };
+        let bytes = match data.get(start..start + sample_count * sample_size) {
+            Some(bytes) => bytes,
+            None => return invalid("truncated PNM samples"),
+        };
+        bytes
+            .chunks(sample_size)
+            .map(|sample| match sample {
+                [value] => scale(*value as u32),
+                _ => scale(u16::from_be_bytes([sample[0], sample[1]]) as u32),
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        // Checked before allocating, each sample taking at least two characters
+        if sample_count > data.len() {
+            return invalid("truncated PNM samples");
+        }
+        (0..sample_count)
+            .map(|_| tokens.next_number().and_then(scale))
+            .collect::<Result<_, _>>()?
+    };
+
+    let format = if channels == 1 { PixelFormat::Luminance } else { PixelFormat::Rgb };
+    Ok(Image::from_vec_with_format(samples, width, height, format).to_rgba())
+}
\ No newline at end of file
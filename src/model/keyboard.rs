@@ -0,0 +1,37 @@
+use std::collections::HashSet;
+
+/// Keys currently held down, identified by their physical `KeyboardEvent.code`
+pub struct Keyboard {
+    down: HashSet<String>,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self {
+            down: HashSet::new(),
+        }
+    }
+
+    pub fn press(&mut self, code: String) {
+        self.down.insert(code);
+    }
+
+    pub fn release(&mut self, code: &str) {
+        self.down.remove(code);
+    }
+
+    /// Releases all keys, for instance when the page loses focus
+    pub fn release_all(&mut self) {
+        self.down.clear();
+    }
+
+    pub fn is_down(&self, code: &str) -> bool {
+        self.down.contains(code)
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,94 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far light with parallel rays, like the sun
+    Directional,
+    /// Light emitting in all directions from a position
+    Point,
+    /// Point light restricted to a cone around its direction
+    Spot,
+}
+
+/// Light source illuminating the scene
+#[derive(Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// World position, ignored by directional lights
+    pub position: Point3<f32>,
+    /// World direction the light is travelling to, ignored by point lights
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Constant, linear and quadratic distance attenuation factors
+    pub attenuation: Vector3<f32>,
+    /// Spot angle in radians from the axis where the light starts fading
+    pub inner_angle: f32,
+    /// Spot angle in radians from the axis where the light is completely faded
+    pub outer_angle: f32,
+}
+
+impl Light {
+    pub fn new(kind: LightKind) -> Self {
+        Self {
+            kind,
+            position: Point3::origin(),
+            direction: -Vector3::y(),
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Vector3::new(1.0, 0.0, 0.0),
+            inner_angle: std::f32::consts::PI / 8.0,
+            outer_angle: std::f32::consts::PI / 6.0,
+        }
+    }
+
+    pub fn point(position: Point3<f32>, color: Vector3<f32>) -> Self {
+        Self {
+            position,
+            color,
+            ..Self::new(LightKind::Point)
+        }
+    }
+
+    pub fn directional(direction: Vector3<f32>, color: Vector3<f32>) -> Self {
+        Self {
+            direction: direction.normalize(),
+            color,
+            ..Self::new(LightKind::Directional)
+        }
+    }
+
+    pub fn spot(position: Point3<f32>, direction: Vector3<f32>, color: Vector3<f32>) -> Self {
+        Self {
+            position,
+            direction: direction.normalize(),
+            color,
+            ..Self::new(LightKind::Spot)
+        }
+    }
+
+    /// Position as expected by the shader, `w` being `0.0` for directional lights
+    pub fn view_position(&self, view: &Isometry3<f32>) -> [f32; 4] {
+        match self.kind {
+            LightKind::Directional => [0.0, 0.0, 0.0, 0.0],
+            _ => {
+                let position = view * self.position;
+                [position.x, position.y, position.z, 1.0]
+            }
+        }
+    }
+
+    pub fn view_direction(&self, view: &Isometry3<f32>) -> Vector3<f32> {
+        view * self.direction.normalize()
+    }
+}
+
+impl Default for Light {
+    /// White point light on the right of the origin
+    fn default() -> Self {
+        Self::point(Point3::new(4.0, 1.0, 1.0), Vector3::new(1.0, 1.0, 1.0))
+    }
+}
\ No newline at end of file
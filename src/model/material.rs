@@ -0,0 +1,118 @@
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::model::Texture;
+
+/// How the alpha channel of a material is interpreted
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored and the surface is fully opaque
+    #[default]
+    Opaque,
+    /// Fragments with alpha below the cutoff are discarded, the rest is opaque
+    Mask { cutoff: f32 },
+    /// Surface is blended with what is behind it
+    Blend,
+}
+
+/// Texture map of a material
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureSlot {
+    /// Multiplied with the base color
+    Diffuse,
+    Specular,
+    Normal,
+    Emissive,
+    MetallicRoughness,
+    Occlusion,
+}
+
+/// Surface appearance of the primitives drawn by a node
+#[derive(Clone)]
+pub struct Material {
+    /// Multiplied with the vertex color and the diffuse texture
+    pub base_color: [f32; 4],
+    pub diffuse_texture: Option<Rc<Texture>>,
+    /// Scales the specular highlights per texel, using the red channel
+    pub specular_texture: Option<Rc<Texture>>,
+    /// Tangent space normals
+    pub normal_texture: Option<Rc<Texture>>,
+    pub emissive_texture: Option<Rc<Texture>>,
+    /// Multiplied with the emissive texture
+    pub emissive_factor: [f32; 3],
+    /// Blinn-Phong specular exponent
+    pub shininess: f32,
+    /// Metalness factor of the physically based pipeline
+    pub metallic: f32,
+    /// Roughness factor of the physically based pipeline
+    pub roughness: f32,
+    /// Roughness in the green channel and metalness in the blue one, as in glTF
+    pub metallic_roughness_texture: Option<Rc<Texture>>,
+    /// Ambient occlusion in the red channel
+    pub occlusion_texture: Option<Rc<Texture>>,
+    pub occlusion_strength: f32,
+    pub alpha_mode: AlphaMode,
+    /// Whether back faces are drawn too
+    pub double_sided: bool,
+}
+
+impl Material {
+    /// Texture unit of each material texture slot
+    pub const DIFFUSE_UNIT: u32 = 0;
+    pub const SPECULAR_UNIT: u32 = 1;
+    pub const NORMAL_UNIT: u32 = 2;
+    pub const EMISSIVE_UNIT: u32 = 3;
+    pub const METALLIC_ROUGHNESS_UNIT: u32 = 5;
+    pub const OCCLUSION_UNIT: u32 = 6;
+
+    pub fn new() -> Self {
+        Self {
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+            emissive_texture: None,
+            emissive_factor: [0.0, 0.0, 0.0],
+            shininess: 32.0,
+            metallic: 0.0,
+            roughness: 0.5,
+            metallic_roughness_texture: None,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+
+    /// Returns a material using the specified texture as diffuse
+    pub fn from_texture(texture: Rc<Texture>) -> Self {
+        Self {
+            diffuse_texture: Some(texture),
+            ..Self::new()
+        }
+    }
+
+    /// Sets or clears the texture of a slot
+    pub fn set_texture(&mut self, slot: TextureSlot, texture: Option<Rc<Texture>>) {
+        let target = match slot {
+            TextureSlot::Diffuse => &mut self.diffuse_texture,
+            TextureSlot::Specular => &mut self.specular_texture,
+            TextureSlot::Normal => &mut self.normal_texture,
+            TextureSlot::Emissive => &mut self.emissive_texture,
+            TextureSlot::MetallicRoughness => &mut self.metallic_roughness_texture,
+            TextureSlot::Occlusion => &mut self.occlusion_texture,
+        };
+        *target = texture;
+    }
+
+    pub fn is_blended(&self) -> bool {
+        self.alpha_mode == AlphaMode::Blend
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
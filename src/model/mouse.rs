@@ -4,9 +4,20 @@ pub struct Mouse {
     pub pos: Vector2<i32>,
     pub prev: Vector2<i32>,
     pub drag: Vector2<i32>,
+    /// Relative motion since the last reset, also reported while the pointer is locked
+    pub movement: Vector2<i32>,
 
     pub left_click: bool,
     pub left_down: bool,
+    pub right_down: bool,
+    pub middle_down: bool,
+
+    /// Whether any button has been pressed since the last reset
+    pub pressed: bool,
+    /// Whether the buttons currently down were pressed over the GUI
+    pub captured: bool,
+    /// Wheel scroll accumulated since the last reset
+    pub wheel: f32,
 
     pub selected_node: Option<u32>,
 }
@@ -21,15 +32,61 @@ impl Mouse {
             pos: Vector2::new(0, 0),
             prev: Vector2::new(0, 0),
             drag: Vector2::new(0, 0),
+            movement: Vector2::new(0, 0),
             left_click: false,
             left_down: false,
+            right_down: false,
+            middle_down: false,
+            pressed: false,
+            captured: false,
+            wheel: 0.0,
             selected_node: None,
         }
     }
 
+    /// Updates the state from the `buttons` bitmask of a mouse event
+    pub fn set_buttons(&mut self, buttons: u16) {
+        let left_down = buttons & Self::LEFT != 0;
+        let right_down = buttons & Self::RIGHT != 0;
+        let middle_down = buttons & Self::MIDDLE != 0;
+
+        self.left_click |= left_down && !self.left_down;
+        self.pressed |= (left_down && !self.left_down)
+            || (right_down && !self.right_down)
+            || (middle_down && !self.middle_down);
+
+        self.left_down = left_down;
+        self.right_down = right_down;
+        self.middle_down = middle_down;
+    }
+
+    /// Moves the cursor to the specified position, accumulating the drag
+    pub fn move_to(&mut self, x: i32, y: i32) {
+        self.prev = self.pos;
+        self.pos = Vector2::new(x, y);
+
+        if self.is_down() {
+            self.drag += self.pos - self.prev;
+        }
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.left_down || self.right_down || self.middle_down
+    }
+
     pub fn reset(&mut self) {
         self.left_click = false;
+        self.pressed = false;
+        self.wheel = 0.0;
         self.drag.x = 0;
         self.drag.y = 0;
+        self.movement.x = 0;
+        self.movement.y = 0;
+    }
+}
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self::new()
     }
 }
\ No newline at end of file
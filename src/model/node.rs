@@ -1,20 +1,98 @@
+use std::rc::Rc;
 use nalgebra::Isometry3;
-use crate::model::Primitive;
+use crate::model::{Aabb, Material, Primitive};
 
 pub struct Node {
     pub id: u32,
     pub transform: Isometry3<f32>,
-    pub primitive: Primitive,
+    /// Mesh drawn by this node, shared between all nodes referencing the same GPU buffers
+    pub primitive: Option<Rc<Primitive>>,
+    /// Appearance of the primitive, the context default material being used when `None`
+    pub material: Option<Rc<Material>>,
     pub children: Vec<Node>,
+    /// Bounds of this node and all its descendants in the node local space,
+    /// refreshed by `update_bounds`. `None` means the node is never culled
+    pub bounds: Option<Aabb>,
 }
 
 impl Node {
-    pub fn new(primitive: Primitive) -> Self {
+    /// Returns a new node drawing the specified primitive
+    pub fn new(primitive: Rc<Primitive>) -> Self {
+        Self {
+            primitive: Some(primitive),
+            ..Self::empty()
+        }
+    }
+
+    /// Returns a new node without geometry, useful as a group or pivot for its children
+    pub fn empty() -> Self {
         Self {
             id: 0,
             transform: Isometry3::identity(),
-            primitive,
+            primitive: None,
+            material: None,
             children: vec![],
+            bounds: None,
         }
     }
+
+    /// Recomputes the bounds of this subtree from the primitives local bounds
+    /// and the children transforms. Needs to be called after changing the hierarchy
+    pub fn update_bounds(&mut self) -> Option<Aabb> {
+        let own = self.primitive.as_ref().map(|primitive| primitive.aabb);
+        let children: Vec<ChildBounds> = self
+            .children
+            .iter_mut()
+            .map(|child| ChildBounds {
+                bounds: child.update_bounds(),
+                transform: child.transform,
+                has_geometry: child.primitive_count() > 0,
+            })
+            .collect();
+
+        self.bounds = merge_bounds(own, &children);
+        self.bounds
+    }
+
+    /// Returns the number of nodes with geometry in this subtree
+    pub fn primitive_count(&self) -> u32 {
+        let own = self.primitive.is_some() as u32;
+        own + self.children.iter().map(Node::primitive_count).sum::<u32>()
+    }
+}
+
+/// Bounds of a child subtree in its own space, as used by `merge_bounds`
+#[derive(Clone, Copy, Debug)]
+pub struct ChildBounds {
+    pub bounds: Option<Aabb>,
+    /// Child to parent transform
+    pub transform: Isometry3<f32>,
+    pub has_geometry: bool,
+}
+
+/// Returns the bounds of a subtree in the node space from the bounds of the node geometry,
+/// `Some(None)` when unknown, and of its children.
+/// Geometry with unknown bounds makes the whole subtree unbounded
+pub fn merge_bounds(own: Option<Option<Aabb>>, children: &[ChildBounds]) -> Option<Aabb> {
+    let mut unbounded = own == Some(None);
+    let mut bounds = own.flatten();
+
+    for child in children {
+        match child.bounds {
+            Some(child_bounds) => {
+                let child_bounds = child_bounds.transform(&child.transform);
+                bounds = Some(match bounds {
+                    Some(bounds) => bounds.merge(&child_bounds),
+                    None => child_bounds,
+                });
+            }
+            None => unbounded |= child.has_geometry,
+        }
+    }
+
+    if unbounded {
+        None
+    } else {
+        bounds
+    }
 }
\ No newline at end of file
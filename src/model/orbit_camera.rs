@@ -0,0 +1,144 @@
+use nalgebra::{Isometry3, Point3, Vector2, Vector3};
+
+/// Camera rotating around a target point, driven by mouse drag and wheel
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// Rotation around the up axis in radians
+    pub yaw: f32,
+    /// Elevation above the target plane in radians
+    pub pitch: f32,
+
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+
+    /// Radians per pixel of drag
+    pub rotate_speed: f32,
+    /// Fraction of the distance moved per pixel of drag
+    pub pan_speed: f32,
+    /// Fraction of the distance moved per wheel unit
+    pub zoom_speed: f32,
+    /// Fraction of the motion kept after a sixtieth of a second, `0.0` stops immediately.
+    /// Clamped below `MAX_DAMPING` so the motion always dies out
+    pub damping: f32,
+
+    rotate_velocity: Vector2<f32>,
+    pan_velocity: Vector2<f32>,
+    zoom_velocity: f32,
+}
+
+impl OrbitCamera {
+    pub const MAX_DAMPING: f32 = 0.99;
+
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+
+        Self {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.25,
+            max_distance: 128.0,
+            min_pitch: -limit,
+            max_pitch: limit,
+            rotate_speed: 0.01,
+            pan_speed: 0.002,
+            zoom_speed: 0.001,
+            damping: 0.8,
+            rotate_velocity: Vector2::zeros(),
+            pan_velocity: Vector2::zeros(),
+            zoom_velocity: 0.0,
+        }
+    }
+
+    /// Places the camera at `eye` looking at `target`
+    pub fn look_at(&mut self, eye: &Point3<f32>, target: &Point3<f32>) {
+        let offset = eye - target;
+        self.target = *target;
+        self.distance = offset.norm().clamp(self.min_distance, self.max_distance);
+        self.yaw = offset.x.atan2(offset.z);
+        self.pitch = (offset.y / offset.norm().max(f32::EPSILON))
+            .asin()
+            .clamp(self.min_pitch, self.max_pitch);
+        self.stop();
+    }
+
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = Self::clamp_damping(damping);
+    }
+
+    fn clamp_damping(damping: f32) -> f32 {
+        if damping.is_nan() {
+            0.0
+        } else {
+            damping.clamp(0.0, Self::MAX_DAMPING)
+        }
+    }
+
+    /// Rotates around the target by a mouse drag in pixels
+    pub fn rotate(&mut self, drag: Vector2<f32>) {
+        self.rotate_velocity += drag * self.rotate_speed;
+    }
+
+    /// Moves the target on the view plane by a mouse drag in pixels
+    pub fn pan(&mut self, drag: Vector2<f32>) {
+        self.pan_velocity += drag * self.pan_speed;
+    }
+
+    /// Moves closer to the target for negative values, farther for positive ones
+    pub fn zoom(&mut self, delta: f32) {
+        self.zoom_velocity += delta * self.zoom_speed;
+    }
+
+    /// Cancels any motion left by damping
+    pub fn stop(&mut self) {
+        self.rotate_velocity = Vector2::zeros();
+        self.pan_velocity = Vector2::zeros();
+        self.zoom_velocity = 0.0;
+    }
+
+    /// Applies the accumulated motion for `delta` seconds, slowing it down according to damping.
+    /// The motion covers the same distance whatever the frame rate
+    pub fn update(&mut self, delta: f32) {
+        let damping = Self::clamp_damping(self.damping);
+        let frames = delta.max(0.0) * 60.0;
+        let decay = damping.powf(frames);
+        // Sum of the motion over the frames, one step per frame at 60 Hz
+        let travel = (1.0 - decay) / (1.0 - damping);
+
+        self.yaw -= self.rotate_velocity.x * travel;
+        self.pitch = (self.pitch + self.rotate_velocity.y * travel)
+            .clamp(self.min_pitch, self.max_pitch);
+
+        let view = self.view();
+        let right = view.inverse_transform_vector(&Vector3::x());
+        let up = view.inverse_transform_vector(&Vector3::y());
+        let pan = self.pan_velocity * self.distance * travel;
+        self.target += up * pan.y - right * pan.x;
+
+        self.distance = (self.distance * (self.zoom_velocity * travel).exp())
+            .clamp(self.min_distance, self.max_distance);
+
+        self.rotate_velocity *= decay;
+        self.pan_velocity *= decay;
+        self.zoom_velocity *= decay;
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let offset = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + offset * self.distance
+    }
+
+    /// Returns the world to view transform
+    pub fn view(&self) -> Isometry3<f32> {
+        // OpenGL uses a right-handed coordinate system
+        Isometry3::look_at_rh(&self.eye(), &self.target, &Vector3::y_axis())
+    }
+}
\ No newline at end of file
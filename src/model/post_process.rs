@@ -0,0 +1,221 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGlRenderingContext as GL, WebGlUniformLocation};
+use crate::model::{
+    gl_state, Framebuffer, FramebufferBinding, FramebufferError, Primitive, Program, TexelType, Texture,
+};
+
+/// Full-screen effect applied to the rendered scene
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Maps the scene into the displayable range with the Reinhard operator.
+    /// Skipped without half-float render targets, as the scene is then already clamped
+    ToneMapping,
+    Gamma,
+    Fxaa,
+    Blur,
+    Grayscale,
+    Vignette,
+}
+
+impl PostEffect {
+    /// All the effects in the order they are applied
+    pub const ALL: [PostEffect; 6] = [
+        PostEffect::ToneMapping,
+        PostEffect::Gamma,
+        PostEffect::Fxaa,
+        PostEffect::Blur,
+        PostEffect::Grayscale,
+        PostEffect::Vignette,
+    ];
+
+    fn frag_src(&self) -> &'static str {
+        match self {
+            PostEffect::ToneMapping => include_str!("../../res/shader/post_tone_mapping.frag.glsl"),
+            PostEffect::Gamma => include_str!("../../res/shader/post_gamma.frag.glsl"),
+            PostEffect::Fxaa => include_str!("../../res/shader/post_fxaa.frag.glsl"),
+            PostEffect::Blur => include_str!("../../res/shader/post_blur.frag.glsl"),
+            PostEffect::Grayscale => include_str!("../../res/shader/post_grayscale.frag.glsl"),
+            PostEffect::Vignette => include_str!("../../res/shader/post_vignette.frag.glsl"),
+        }
+    }
+
+    /// Name and default value of the uniform tweaking the effect, if any
+    fn param(&self) -> Option<(&'static str, f32)> {
+        match self {
+            PostEffect::ToneMapping => Some(("exposure", 1.0)),
+            PostEffect::Gamma => Some(("gamma", 2.2)),
+            PostEffect::Vignette => Some(("strength", 1.0)),
+            _ => None,
+        }
+    }
+}
+
+struct PostPass {
+    effect: PostEffect,
+    enabled: bool,
+    program: Program,
+    sampler_loc: Option<WebGlUniformLocation>,
+    resolution_loc: Option<WebGlUniformLocation>,
+    param_loc: Option<WebGlUniformLocation>,
+    param: f32,
+}
+
+impl PostPass {
+    fn new(gl: &GL, effect: PostEffect) -> Self {
+        let vert_src = include_str!("../../res/shader/post.vert.glsl");
+        let program = Program::new(gl.clone(), vert_src, effect.frag_src());
+        program.bind();
+
+        let sampler_loc = program.get_uniform_loc("tex_sampler");
+        let resolution_loc = program.get_uniform_loc("resolution");
+        let (param_loc, param) = match effect.param() {
+            Some((name, value)) => (program.get_uniform_loc(name), value),
+            None => (None, 0.0),
+        };
+
+        Self {
+            effect,
+            enabled: false,
+            program,
+            sampler_loc,
+            resolution_loc,
+            param_loc,
+            param,
+        }
+    }
+
+    fn draw(&self, triangle: &Primitive, input: &Texture) {
+        let gl = &self.program.gl;
+        self.program.bind();
+
+        gl.active_texture(GL::TEXTURE0);
+        input.bind();
+        gl.uniform1i(self.sampler_loc.as_ref(), 0);
+        gl.uniform2f(
+            self.resolution_loc.as_ref(),
+            input.width as f32,
+            input.height as f32,
+        );
+        gl.uniform1f(self.param_loc.as_ref(), self.param);
+
+        triangle.bind();
+        let position_loc = self.program.get_attrib_loc("in_position") as u32;
+        gl.vertex_attrib_pointer_with_i32(position_loc, 2, GL::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(position_loc);
+
+        triangle.draw();
+    }
+}
+
+/// Chain of full-screen passes applied to the scene rendered offscreen
+pub struct PostProcess {
+    passes: Vec<PostPass>,
+    /// Single triangle covering the whole screen
+    triangle: Primitive,
+    /// Receives the scene before any effect, in half floats when supported
+    /// so colors brighter than one are kept for tone mapping
+    scene: Framebuffer,
+    /// Intermediate results, alternately read and written by consecutive passes
+    ping_pong: [Framebuffer; 2],
+}
+
+impl PostProcess {
+    pub fn new(gl: &GL, width: u32, height: u32) -> Result<Self, FramebufferError> {
+        let passes = PostEffect::ALL
+            .iter()
+            .map(|effect| PostPass::new(gl, *effect))
+            .collect();
+
+        let vertices: [[f32; 2]; 3] = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]];
+        let triangle = Primitive::from_raw(gl.clone(), &vertices, &[0, 1, 2]);
+
+        let scene = Framebuffer::with_texel_type(gl.clone(), width, height, TexelType::HalfFloat)
+            .or_else(|_| Framebuffer::new(gl.clone(), width, height))?;
+
+        Ok(Self {
+            passes,
+            triangle,
+            scene,
+            ping_pong: [
+                Framebuffer::new(gl.clone(), width, height)?,
+                Framebuffer::new(gl.clone(), width, height)?,
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        self.scene.resize(width, height)?;
+        for framebuffer in &mut self.ping_pong {
+            framebuffer.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    fn get_pass(&mut self, effect: PostEffect) -> &mut PostPass {
+        self.passes
+            .iter_mut()
+            .find(|pass| pass.effect == effect)
+            .expect("Missing post-processing pass")
+    }
+
+    pub fn set_enabled(&mut self, effect: PostEffect, enabled: bool) {
+        self.get_pass(effect).enabled = enabled;
+    }
+
+    pub fn is_enabled(&self, effect: PostEffect) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.effect == effect && pass.enabled)
+    }
+
+    /// Sets the value tweaking the effect: exposure for tone mapping,
+    /// gamma for gamma correction and strength for vignette
+    pub fn set_param(&mut self, effect: PostEffect, value: f32) {
+        self.get_pass(effect).param = value;
+    }
+
+    /// Returns whether the scene is rendered with a range above one
+    pub fn is_hdr(&self) -> bool {
+        self.scene.texture.texel_type != TexelType::UnsignedByte
+    }
+
+    /// Returns whether the pass runs, tone mapping needing an HDR scene to do anything
+    fn runs(&self, pass: &PostPass) -> bool {
+        pass.enabled && (pass.effect != PostEffect::ToneMapping || self.is_hdr())
+    }
+
+    /// Returns whether any effect runs
+    pub fn is_active(&self) -> bool {
+        self.passes.iter().any(|pass| self.runs(pass))
+    }
+
+    /// Binds the framebuffer the scene should be drawn to before calling `apply`
+    pub fn begin(&self) -> FramebufferBinding {
+        self.scene.bind()
+    }
+
+    /// Runs all enabled passes, the last one drawing into the currently bound target
+    pub fn apply(&self) {
+        let gl = &self.triangle.gl;
+        gl_state::disable(gl, GL::DEPTH_TEST);
+        gl_state::disable(gl, GL::BLEND);
+
+        let passes: Vec<&PostPass> = self.passes.iter().filter(|pass| self.runs(pass)).collect();
+        let mut input = &self.scene.texture;
+
+        for (i, pass) in passes.iter().enumerate() {
+            if i + 1 == passes.len() {
+                pass.draw(&self.triangle, input);
+            } else {
+                let output = &self.ping_pong[i % 2];
+                let _binding = output.bind();
+                pass.draw(&self.triangle, input);
+                input = &output.texture;
+            }
+        }
+
+        gl_state::enable(gl, GL::DEPTH_TEST);
+        gl_state::enable(gl, GL::BLEND);
+    }
+}
\ No newline at end of file
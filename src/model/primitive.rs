@@ -1,5 +1,5 @@
 use web_sys::WebGlBuffer;
-use crate::model::Geometry;
+use crate::model::{stats, Aabb, Geometry, Vertex};
 use web_sys::WebGlRenderingContext as GL;
 
 /// GPU-side primitive geometry
@@ -8,35 +8,45 @@ pub struct Primitive {
     pub vertex_buffer: Option<WebGlBuffer>,
     pub index_buffer: Option<WebGlBuffer>,
     pub index_count: i32,
+    /// Bounds of the vertices in local space, `None` when unknown
+    pub aabb: Option<Aabb>,
+    /// Size of both buffers
+    bytes: u64,
 }
 
 impl Primitive {
-    pub fn from_raw<T>(gl: GL, vertices: &Vec<T>, indices: &Vec<u8>) -> Self {
+    pub fn from_raw<T>(gl: GL, vertices: &[T], indices: &[u8]) -> Self {
         let vertex_buffer = gl.create_buffer();
         gl.bind_buffer(GL::ARRAY_BUFFER, vertex_buffer.as_ref());
         let u8_slice = unsafe {
             std::slice::from_raw_parts(
                 vertices.as_ptr() as *const u8,
-                vertices.len() * std::mem::size_of::<T>(),
+                std::mem::size_of_val(vertices),
             )
         };
         gl.buffer_data_with_u8_array(GL::ARRAY_BUFFER, u8_slice, GL::STATIC_DRAW);
 
         let index_buffer = gl.create_buffer();
         gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, index_buffer.as_ref());
-        gl.buffer_data_with_u8_array(GL::ELEMENT_ARRAY_BUFFER, &indices, GL::STATIC_DRAW);
+        gl.buffer_data_with_u8_array(GL::ELEMENT_ARRAY_BUFFER, indices, GL::STATIC_DRAW);
 
         let index_count = indices.len() as i32;
+        let bytes = (u8_slice.len() + indices.len()) as u64;
+        stats::allocate_buffer(bytes);
         Self {
             gl,
             vertex_buffer,
             index_buffer,
             index_count,
+            aabb: None,
+            bytes,
         }
     }
 
-    pub fn new<V>(gl: GL, geometry: &Geometry<V>) -> Self {
-        Self::from_raw(gl, &geometry.vertices, &geometry.indices)
+    pub fn new(gl: GL, geometry: &Geometry<Vertex>) -> Self {
+        let mut primitive = Self::from_raw(gl, &geometry.vertices, &geometry.indices);
+        primitive.aabb = geometry.aabb();
+        primitive
     }
 
     pub fn bind(&self) {
@@ -49,6 +59,7 @@ impl Primitive {
     pub fn draw(&self) {
         self.gl
             .draw_elements_with_i32(GL::TRIANGLES, self.index_count, GL::UNSIGNED_BYTE, 0);
+        stats::count_draw(self.index_count as u32 / 3);
     }
 }
 
@@ -56,5 +67,6 @@ impl Drop for Primitive {
     fn drop(&mut self) {
         self.gl.delete_buffer(self.vertex_buffer.as_ref());
         self.gl.delete_buffer(self.index_buffer.as_ref());
+        stats::free_buffer(self.bytes);
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use web_sys::{WebGlRenderingContext as GL, WebGlProgram, WebGlUniformLocation, WebGlShader};
+use crate::model::stats;
 
 pub struct Program {
     pub gl: GL,
@@ -17,6 +18,7 @@ impl Program {
 
     pub fn bind(&self) {
         self.gl.use_program(Some(&self.program));
+        stats::count_state_changes(1);
     }
 
     pub fn get_attrib_loc(&self, name: &str) -> i32 {
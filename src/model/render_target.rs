@@ -0,0 +1,60 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+use web_sys::WebGlRenderingContext as GL;
+use crate::model::{Camera, Framebuffer, FramebufferError, Texture};
+
+/// Offscreen pass rendering the scene from its own point of view into a texture,
+/// which can then be shown anywhere, for instance in a GUI window
+pub struct RenderTarget {
+    pub framebuffer: Framebuffer,
+    pub camera: Camera,
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+}
+
+impl RenderTarget {
+    /// Returns a new render target looking at the origin from above
+    pub fn new(gl: GL, width: u32, height: u32) -> Result<Self, FramebufferError> {
+        Ok(Self {
+            framebuffer: Framebuffer::new(gl, width, height)?,
+            camera: Camera::new(width, height),
+            eye: Point3::new(0.0, 8.0, 0.0),
+            target: Point3::origin(),
+        })
+    }
+
+    /// Points the view from `eye` towards `target`, which must be different points
+    pub fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>) -> Result<(), String> {
+        if (target - eye).try_normalize(f32::EPSILON).is_none() {
+            return Err(String::from("Render target eye and target must be different points"));
+        }
+        self.eye = eye;
+        self.target = target;
+        Ok(())
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        self.camera.resize(width, height);
+        self.framebuffer.resize(width, height)
+    }
+
+    /// Texture receiving the rendered image
+    pub fn texture(&self) -> &Texture {
+        &self.framebuffer.texture
+    }
+
+    /// Returns the world to view transform
+    pub fn view(&self) -> Isometry3<f32> {
+        // Looking straight up or down needs another up direction
+        let direction = (self.target - self.eye)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| -Vector3::z());
+        let up = if direction.cross(&Vector3::y()).norm() < 1e-4 {
+            -Vector3::z()
+        } else {
+            Vector3::y()
+        };
+
+        // OpenGL uses a right-handed coordinate system
+        Isometry3::look_at_rh(&self.eye, &self.target, &up)
+    }
+}
\ No newline at end of file
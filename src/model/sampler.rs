@@ -0,0 +1,161 @@
+use web_sys::WebGlRenderingContext as GL;
+
+/// `EXT_texture_filter_anisotropic` constants, not exposed by web-sys
+const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+/// How texels are combined when a texture is minified or magnified
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+    /// Nearest texel of the nearest mipmap level
+    NearestMipmapNearest,
+    /// Linear filtering within the nearest mipmap level
+    LinearMipmapNearest,
+    /// Nearest texel, blended between the two nearest mipmap levels
+    NearestMipmapLinear,
+    /// Linear filtering blended between the two nearest mipmap levels (trilinear)
+    LinearMipmapLinear,
+}
+
+impl Filter {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            Filter::Nearest => GL::NEAREST,
+            Filter::Linear => GL::LINEAR,
+            Filter::NearestMipmapNearest => GL::NEAREST_MIPMAP_NEAREST,
+            Filter::LinearMipmapNearest => GL::LINEAR_MIPMAP_NEAREST,
+            Filter::NearestMipmapLinear => GL::NEAREST_MIPMAP_LINEAR,
+            Filter::LinearMipmapLinear => GL::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    pub fn uses_mipmaps(self) -> bool {
+        !matches!(self, Filter::Nearest | Filter::Linear)
+    }
+
+    /// Returns the closest filter reading only the base level
+    pub fn without_mipmaps(self) -> Self {
+        match self {
+            Filter::Nearest | Filter::NearestMipmapNearest | Filter::NearestMipmapLinear => {
+                Filter::Nearest
+            }
+            _ => Filter::Linear,
+        }
+    }
+}
+
+/// What texture coordinates outside `[0, 1]` read
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wrap {
+    ClampToEdge,
+    Repeat,
+    MirroredRepeat,
+}
+
+impl Wrap {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            Wrap::ClampToEdge => GL::CLAMP_TO_EDGE,
+            Wrap::Repeat => GL::REPEAT,
+            Wrap::MirroredRepeat => GL::MIRRORED_REPEAT,
+        }
+    }
+}
+
+/// Describes how a texture is sampled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampler {
+    pub min_filter: Filter,
+    /// Only `Nearest` and `Linear` apply to magnification, mipmapped modes use their base filter
+    pub mag_filter: Filter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    /// Maximum anisotropy, `1.0` disabling anisotropic filtering
+    pub anisotropy: f32,
+}
+
+impl Sampler {
+    /// Linear filtering clamped to the edges, without mipmaps
+    pub fn new() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            anisotropy: 1.0,
+        }
+    }
+
+    /// Sharp texels, as for pixel art and bitmap fonts
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            ..Self::new()
+        }
+    }
+
+    /// Trilinear filtering with repeating coordinates, as for surface textures
+    pub fn mipmapped() -> Self {
+        Self {
+            min_filter: Filter::LinearMipmapLinear,
+            mag_filter: Filter::Linear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            anisotropy: 1.0,
+        }
+    }
+
+    pub fn with_anisotropy(self, anisotropy: f32) -> Self {
+        Self { anisotropy, ..self }
+    }
+
+    /// Returns the sampler WebGL 1 can use with a texture of the specified size,
+    /// as non power of two textures support neither mipmaps nor repeating
+    pub fn supported_for(&self, width: u32, height: u32) -> Self {
+        if width.is_power_of_two() && height.is_power_of_two() {
+            return *self;
+        }
+        Self {
+            min_filter: self.min_filter.without_mipmaps(),
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            ..*self
+        }
+    }
+
+    /// Sets the parameters of the texture bound to the specified target
+    pub fn apply(&self, gl: &GL, target: u32) {
+        gl.tex_parameteri(target, GL::TEXTURE_MIN_FILTER, self.min_filter.to_gl() as i32);
+        gl.tex_parameteri(
+            target,
+            GL::TEXTURE_MAG_FILTER,
+            self.mag_filter.without_mipmaps().to_gl() as i32,
+        );
+        gl.tex_parameteri(target, GL::TEXTURE_WRAP_S, self.wrap_s.to_gl() as i32);
+        gl.tex_parameteri(target, GL::TEXTURE_WRAP_T, self.wrap_t.to_gl() as i32);
+
+        if self.anisotropy > 1.0 {
+            let extension = gl
+                .get_extension("EXT_texture_filter_anisotropic")
+                .ok()
+                .flatten();
+            if extension.is_some() {
+                let max = gl
+                    .get_parameter(MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+                    .ok()
+                    .and_then(|max| max.as_f64())
+                    .unwrap_or(1.0) as f32;
+                gl.tex_parameterf(target, TEXTURE_MAX_ANISOTROPY_EXT, self.anisotropy.min(max));
+            }
+        }
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
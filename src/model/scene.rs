@@ -0,0 +1,349 @@
+use nalgebra::{Isometry3, Point3, Quaternion, Translation3, UnitQuaternion, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+use crate::model::{AlphaMode, Camera, Geometry, Light, LightKind, Material, Projection, Text, Vertex, Window};
+
+/// Serializable description of a whole scene, used to save and reproduce it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Scene {
+    /// Format version, bumped on incompatible changes
+    pub version: u32,
+    pub meshes: Vec<SceneMesh>,
+    #[serde(default)]
+    pub materials: Vec<SceneMaterial>,
+    pub nodes: Vec<SceneNode>,
+    pub lights: Vec<SceneLight>,
+    #[serde(default = "Scene::default_ambient")]
+    pub ambient: [f32; 3],
+    pub camera: SceneCamera,
+    #[serde(default)]
+    pub windows: Vec<SceneWindow>,
+}
+
+impl Scene {
+    pub const VERSION: u32 = 1;
+
+    fn default_ambient() -> [f32; 3] {
+        [0.1, 0.1, 0.1]
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Parses a scene from either JSON or RON, detecting the format by the first character
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let scene: Scene = if src.trim_start().starts_with('{') {
+            serde_json::from_str(src).map_err(|e| e.to_string())?
+        } else {
+            ron::from_str(src).map_err(|e| e.to_string())?
+        };
+
+        if scene.version > Self::VERSION {
+            return Err(format!("Unsupported scene version: {}", scene.version));
+        }
+
+        let camera = &scene.camera;
+        camera.projection.validate(camera.near, camera.far)?;
+
+        for node in &scene.nodes {
+            node.validate(scene.meshes.len(), scene.materials.len())?;
+        }
+
+        for light in &scene.lights {
+            light.validate()?;
+        }
+
+        Ok(scene)
+    }
+}
+
+/// Procedural geometry a node can reference by index
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SceneMesh {
+    Triangle,
+    Quad,
+    Cube,
+}
+
+impl SceneMesh {
+    pub fn geometry(&self) -> Geometry<Vertex> {
+        match self {
+            SceneMesh::Triangle => Geometry::triangle(),
+            SceneMesh::Quad => Geometry::quad(),
+            SceneMesh::Cube => Geometry::cube(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SceneTransform {
+    pub translation: [f32; 3],
+    /// Rotation quaternion as `[i, j, k, w]`
+    pub rotation: [f32; 4],
+}
+
+impl From<&Isometry3<f32>> for SceneTransform {
+    fn from(isometry: &Isometry3<f32>) -> Self {
+        let t = &isometry.translation.vector;
+        let r = isometry.rotation.quaternion().coords;
+        Self {
+            translation: [t.x, t.y, t.z],
+            rotation: [r.x, r.y, r.z, r.w],
+        }
+    }
+}
+
+impl From<&SceneTransform> for Isometry3<f32> {
+    fn from(transform: &SceneTransform) -> Self {
+        let [x, y, z] = transform.translation;
+        let [i, j, k, w] = transform.rotation;
+        Isometry3::from_parts(
+            Translation3::new(x, y, z),
+            UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k)),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SceneNode {
+    pub transform: SceneTransform,
+    /// Index into the scene meshes, `None` for group nodes
+    #[serde(default)]
+    pub mesh: Option<usize>,
+    /// Index into the scene materials, `None` for the default material
+    #[serde(default)]
+    pub material: Option<usize>,
+    #[serde(default)]
+    pub children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    fn validate(&self, mesh_count: usize, material_count: usize) -> Result<(), String> {
+        if let Some(mesh) = self.mesh {
+            if mesh >= mesh_count {
+                return Err(format!("Invalid mesh index: {}", mesh));
+            }
+        }
+        if let Some(material) = self.material {
+            if material >= material_count {
+                return Err(format!("Invalid material index: {}", material));
+            }
+        }
+        self.children
+            .iter()
+            .try_for_each(|child| child.validate(mesh_count, material_count))
+    }
+}
+
+/// Material factors, textures are not part of the scene description
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SceneMaterial {
+    #[serde(default = "SceneMaterial::default_base_color")]
+    pub base_color: [f32; 4],
+    #[serde(default)]
+    pub emissive_factor: [f32; 3],
+    #[serde(default = "SceneMaterial::default_shininess")]
+    pub shininess: f32,
+    #[serde(default)]
+    pub metallic: f32,
+    #[serde(default = "SceneMaterial::default_roughness")]
+    pub roughness: f32,
+    #[serde(default)]
+    pub alpha_mode: AlphaMode,
+    #[serde(default)]
+    pub double_sided: bool,
+}
+
+impl SceneMaterial {
+    fn default_base_color() -> [f32; 4] {
+        Material::new().base_color
+    }
+
+    fn default_shininess() -> f32 {
+        Material::new().shininess
+    }
+
+    fn default_roughness() -> f32 {
+        Material::new().roughness
+    }
+}
+
+impl From<&Material> for SceneMaterial {
+    fn from(material: &Material) -> Self {
+        Self {
+            base_color: material.base_color,
+            emissive_factor: material.emissive_factor,
+            shininess: material.shininess,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            alpha_mode: material.alpha_mode,
+            double_sided: material.double_sided,
+        }
+    }
+}
+
+impl From<&SceneMaterial> for Material {
+    fn from(material: &SceneMaterial) -> Self {
+        Material {
+            base_color: material.base_color,
+            emissive_factor: material.emissive_factor,
+            shininess: material.shininess,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            alpha_mode: material.alpha_mode,
+            double_sided: material.double_sided,
+            ..Material::new()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SceneLight {
+    #[serde(default = "SceneLight::default_kind")]
+    pub kind: LightKind,
+    #[serde(default)]
+    pub position: [f32; 3],
+    #[serde(default = "SceneLight::default_direction")]
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    #[serde(default = "SceneLight::default_intensity")]
+    pub intensity: f32,
+    #[serde(default = "SceneLight::default_attenuation")]
+    pub attenuation: [f32; 3],
+    /// Spot cone angles in radians, inner then outer
+    #[serde(default = "SceneLight::default_cone")]
+    pub cone: [f32; 2],
+}
+
+impl SceneLight {
+    fn default_kind() -> LightKind {
+        LightKind::Point
+    }
+
+    fn default_direction() -> [f32; 3] {
+        Light::new(LightKind::Point).direction.into()
+    }
+
+    fn default_intensity() -> f32 {
+        1.0
+    }
+
+    fn default_attenuation() -> [f32; 3] {
+        [1.0, 0.0, 0.0]
+    }
+
+    fn default_cone() -> [f32; 2] {
+        let light = Light::new(LightKind::Spot);
+        [light.inner_angle, light.outer_angle]
+    }
+
+    /// Checks the direction can be normalized, unless the light ignores it
+    fn validate(&self) -> Result<(), String> {
+        let length = Vector3::from(self.direction).norm();
+        if self.kind != LightKind::Point && !(length > 0.0 && length.is_finite()) {
+            return Err(format!("Invalid light direction: {:?}", self.direction));
+        }
+        Ok(())
+    }
+}
+
+impl From<&Light> for SceneLight {
+    fn from(light: &Light) -> Self {
+        Self {
+            kind: light.kind,
+            position: light.position.coords.into(),
+            direction: light.direction.into(),
+            color: light.color.into(),
+            intensity: light.intensity,
+            attenuation: light.attenuation.into(),
+            cone: [light.inner_angle, light.outer_angle],
+        }
+    }
+}
+
+impl From<&SceneLight> for Light {
+    fn from(light: &SceneLight) -> Self {
+        Light {
+            kind: light.kind,
+            position: Point3::from(light.position),
+            direction: Vector3::from(light.direction)
+                .try_normalize(0.0)
+                .unwrap_or_else(|| Light::new(light.kind).direction),
+            color: Vector3::from(light.color),
+            intensity: light.intensity,
+            attenuation: Vector3::from(light.attenuation),
+            inner_angle: light.cone[0],
+            outer_angle: light.cone[1],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SceneCamera {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    #[serde(default)]
+    pub projection: Projection,
+    #[serde(default = "SceneCamera::default_near")]
+    pub near: f32,
+    #[serde(default = "SceneCamera::default_far")]
+    pub far: f32,
+}
+
+impl SceneCamera {
+    pub fn new(camera: &Camera, eye: &Point3<f32>, target: &Point3<f32>) -> Self {
+        Self {
+            eye: eye.coords.into(),
+            target: target.coords.into(),
+            projection: camera.projection,
+            near: camera.near,
+            far: camera.far,
+        }
+    }
+
+    fn default_near() -> f32 {
+        Camera::DEFAULT_NEAR
+    }
+
+    fn default_far() -> f32 {
+        Camera::DEFAULT_FAR
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SceneWindow {
+    pub name: String,
+    pub pos: [i32; 2],
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl From<&Window> for SceneWindow {
+    fn from(window: &Window) -> Self {
+        let pos = window.get_pos();
+        Self {
+            name: window.name.clone(),
+            pos: [pos.x, pos.y],
+            width: window.get_width(),
+            height: window.get_height(),
+            text: window.text.as_ref().map(|text| text.value.clone()),
+        }
+    }
+}
+
+impl From<&SceneWindow> for Window {
+    fn from(desc: &SceneWindow) -> Self {
+        let mut window = Window::new(desc.width, desc.height);
+        window.name = desc.name.clone();
+        window.set_pos(Vector2::new(desc.pos[0], desc.pos[1]));
+        window.text = desc.text.as_deref().map(Text::from);
+        window
+    }
+}
\ No newline at end of file
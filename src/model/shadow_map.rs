@@ -0,0 +1,162 @@
+use nalgebra::{Isometry3, Matrix4, Orthographic3, Perspective3, Point3, Vector3};
+use web_sys::{WebGlRenderingContext as GL, WebGlUniformLocation};
+use crate::model::{
+    Aabb, Framebuffer, FramebufferBinding, FramebufferError, Light, LightKind, Primitive, Program,
+    gl_state, Sampler, Vertex,
+};
+
+/// Depth of the scene rendered from the main light, packed into a color texture
+/// and sampled by the default pipeline to find out which fragments are in shadow
+pub struct ShadowMap {
+    pub framebuffer: Framebuffer,
+    program: Program,
+    transform_loc: Option<WebGlUniformLocation>,
+    light_matrix_loc: Option<WebGlUniformLocation>,
+
+    pub enabled: bool,
+    /// Depth offset avoiding surfaces shadowing themselves (shadow acne)
+    pub bias: f32,
+    /// Index of the light casting shadows during the last pass, if any
+    pub light: Option<usize>,
+    /// World to light clip space transform used during the last pass
+    pub light_matrix: Matrix4<f32>,
+}
+
+impl ShadowMap {
+    /// Texture unit the default pipeline samples the shadow map from
+    pub const UNIT: u32 = 4;
+    pub const DEFAULT_SIZE: u32 = 1024;
+    pub const DEFAULT_BIAS: f32 = 0.005;
+
+    pub fn new(gl: &GL, size: u32) -> Result<Self, FramebufferError> {
+        let mut framebuffer = Framebuffer::new(gl.clone(), size, size)?;
+
+        // Interpolating packed depth values would give meaningless results
+        framebuffer.texture.set_sampler(Sampler::nearest());
+
+        let vert_src = include_str!("../../res/shader/shadow.vert.glsl");
+        let frag_src = include_str!("../../res/shader/shadow.frag.glsl");
+        let program = Program::new(gl.clone(), vert_src, frag_src);
+
+        Ok(Self {
+            framebuffer,
+            transform_loc: program.get_uniform_loc("transform"),
+            light_matrix_loc: program.get_uniform_loc("light_matrix"),
+            program,
+            enabled: true,
+            bias: Self::DEFAULT_BIAS,
+            light: None,
+            light_matrix: Matrix4::identity(),
+        })
+    }
+
+    /// Width and height of the shadow map in texels
+    pub fn get_size(&self) -> u32 {
+        self.framebuffer.get_width()
+    }
+
+    pub fn resize(&mut self, size: u32) -> Result<(), FramebufferError> {
+        self.framebuffer.resize(size, size)
+    }
+
+    /// Returns the index of the first directional or spot light among the first `max` ones
+    pub fn find_light(lights: &[Light], max: usize) -> Option<usize> {
+        lights
+            .iter()
+            .take(max)
+            .position(|light| light.kind != LightKind::Point)
+    }
+
+    /// Returns the world to light clip space transform covering the specified bounds
+    pub fn light_matrix(light: &Light, bounds: &Aabb) -> Matrix4<f32> {
+        let center = bounds.center();
+        let radius = bounds.extents().norm().max(1.0);
+        let direction = light.direction.normalize();
+
+        match light.kind {
+            LightKind::Spot => {
+                let view = look_at(&light.position, &(light.position + direction));
+                let far = (center - light.position).norm() + radius;
+                let fov = (light.outer_angle * 2.0).min(std::f32::consts::PI * 0.95);
+                let proj = Perspective3::new(1.0, fov, 0.125, far.max(1.0));
+                proj.to_homogeneous() * view.to_homogeneous()
+            }
+            _ => {
+                // Orthographic box around the bounds, seen from outside along the light rays
+                let eye = center - direction * radius * 2.0;
+                let view = look_at(&eye, &center);
+                let proj =
+                    Orthographic3::new(-radius, radius, -radius, radius, radius, radius * 3.0);
+                proj.to_homogeneous() * view.to_homogeneous()
+            }
+        }
+    }
+
+    /// Prepares the shadow pass for the main light, covering the specified scene bounds.
+    /// Returns `None` when shadows are disabled or no light can cast them,
+    /// otherwise primitives should be drawn while the returned binding is alive
+    pub fn begin(
+        &mut self,
+        lights: &[Light],
+        max_lights: usize,
+        bounds: &Aabb,
+    ) -> Option<FramebufferBinding> {
+        self.light = if self.enabled {
+            Self::find_light(lights, max_lights)
+        } else {
+            None
+        };
+        let light = &lights[self.light?];
+        self.light_matrix = Self::light_matrix(light, bounds);
+
+        let gl = &self.program.gl;
+        let binding = self.framebuffer.bind();
+
+        gl_state::enable(gl, GL::DEPTH_TEST);
+        gl_state::disable(gl, GL::BLEND);
+        gl_state::disable(gl, GL::CULL_FACE);
+        gl_state::depth_mask(gl, true);
+
+        // Nothing drawn means farthest depth
+        gl.clear_color(1.0, 1.0, 1.0, 1.0);
+        gl.clear(GL::COLOR_BUFFER_BIT | GL::DEPTH_BUFFER_BIT);
+
+        self.program.bind();
+        gl.uniform_matrix4fv_with_f32_array(
+            self.light_matrix_loc.as_ref(),
+            false,
+            self.light_matrix.as_slice(),
+        );
+
+        Some(binding)
+    }
+
+    /// Draws the depth of a primitive with the specified node to world transform
+    pub fn draw(&self, primitive: &Primitive, transform: &Isometry3<f32>) {
+        let gl = &self.program.gl;
+        gl.uniform_matrix4fv_with_f32_array(
+            self.transform_loc.as_ref(),
+            false,
+            transform.to_homogeneous().as_slice(),
+        );
+
+        primitive.bind();
+        let position_loc = self.program.get_attrib_loc("in_position") as u32;
+        let stride = std::mem::size_of::<Vertex>() as i32;
+        gl.vertex_attrib_pointer_with_i32(position_loc, 3, GL::FLOAT, false, stride, 0);
+        gl.enable_vertex_attrib_array(position_loc);
+
+        primitive.draw();
+    }
+}
+
+/// Returns the view transform from eye to target, even when looking straight up or down
+fn look_at(eye: &Point3<f32>, target: &Point3<f32>) -> Isometry3<f32> {
+    let direction = (target - eye).normalize();
+    let up = if direction.cross(&Vector3::y()).norm() < 1e-4 {
+        -Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    Isometry3::look_at_rh(eye, target, &up)
+}
\ No newline at end of file
@@ -0,0 +1,74 @@
+use nalgebra::{Isometry3, Matrix4};
+use web_sys::{WebGlRenderingContext as GL, WebGlUniformLocation};
+use crate::model::{gl_state, Cubemap, Primitive, Program};
+
+/// Environment cubemap drawn around the camera, behind all the geometry
+pub struct Skybox {
+    pub cubemap: Cubemap,
+    program: Program,
+    view_proj_loc: Option<WebGlUniformLocation>,
+    cube: Primitive,
+}
+
+impl Skybox {
+    pub fn new(gl: &GL, cubemap: Cubemap) -> Self {
+        let vert_src = include_str!("../../res/shader/skybox.vert.glsl");
+        let frag_src = include_str!("../../res/shader/skybox.frag.glsl");
+        let program = Program::new(gl.clone(), vert_src, frag_src);
+        program.bind();
+        gl.uniform1i(program.get_uniform_loc("cubemap").as_ref(), 0);
+
+        let vertices: [[f32; 3]; 8] = [
+            [-1.0, -1.0, -1.0],
+            [1.0, -1.0, -1.0],
+            [1.0, 1.0, -1.0],
+            [-1.0, 1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+            [1.0, -1.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [-1.0, 1.0, 1.0],
+        ];
+        let indices = [
+            0, 1, 2, 0, 2, 3, // back
+            4, 6, 5, 4, 7, 6, // front
+            0, 3, 7, 0, 7, 4, // left
+            1, 5, 6, 1, 6, 2, // right
+            3, 2, 6, 3, 6, 7, // top
+            0, 4, 5, 0, 5, 1, // bottom
+        ];
+        let cube = Primitive::from_raw(gl.clone(), &vertices, &indices);
+
+        Self {
+            cubemap,
+            view_proj_loc: program.get_uniform_loc("view_proj"),
+            program,
+            cube,
+        }
+    }
+
+    /// Draws the skybox where nothing has been drawn yet, so it should come after opaque geometry
+    pub fn draw(&self, view: &Isometry3<f32>, proj: &Matrix4<f32>) {
+        let gl = &self.program.gl;
+        self.program.bind();
+
+        let view_proj = proj * view.rotation.to_homogeneous();
+        gl.uniform_matrix4fv_with_f32_array(self.view_proj_loc.as_ref(), false, view_proj.as_slice());
+
+        self.cubemap.bind_to(0);
+
+        // Depth is exactly 1.0, which passes only where the buffer has been cleared
+        gl_state::depth_func(gl, GL::LEQUAL);
+        gl_state::depth_mask(gl, false);
+        gl_state::disable(gl, GL::CULL_FACE);
+        gl_state::disable(gl, GL::BLEND);
+
+        self.cube.bind();
+        let position_loc = self.program.get_attrib_loc("in_position") as u32;
+        gl.vertex_attrib_pointer_with_i32(position_loc, 3, GL::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(position_loc);
+        self.cube.draw();
+
+        gl_state::depth_func(gl, GL::LESS);
+        gl_state::depth_mask(gl, true);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,74 @@
+use nalgebra::Vector3;
+
+/// Low frequency lighting environment as the L2 spherical harmonics projection of its radiance.
+/// The RGB coefficients are ordered L00, L1-1, L10, L11, L2-2, L2-1, L20, L21, L22
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SphericalHarmonics {
+    pub coefficients: [Vector3<f32>; 9],
+}
+
+impl SphericalHarmonics {
+    pub fn new() -> Self {
+        Self {
+            coefficients: [Vector3::zeros(); 9],
+        }
+    }
+
+    /// Returns an environment with the same radiance in every direction
+    pub fn from_ambient(color: &Vector3<f32>) -> Self {
+        let mut sh = Self::new();
+        // Integral of the constant basis function over the sphere
+        sh.coefficients[0] = color * (4.0 * std::f32::consts::PI).sqrt();
+        sh
+    }
+
+    /// Returns an environment from 27 values, the RGB triplets of each coefficient
+    pub fn from_slice(values: &[f32]) -> Option<Self> {
+        if values.len() != 27 {
+            return None;
+        }
+        let mut sh = Self::new();
+        for (coefficient, rgb) in sh.coefficients.iter_mut().zip(values.chunks(3)) {
+            *coefficient = Vector3::new(rgb[0], rgb[1], rgb[2]);
+        }
+        Some(sh)
+    }
+
+    /// Values of the basis functions for the specified unit direction
+    pub fn basis(direction: &Vector3<f32>) -> [f32; 9] {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        [
+            0.282095,
+            0.488603 * y,
+            0.488603 * z,
+            0.488603 * x,
+            1.092548 * x * y,
+            1.092548 * y * z,
+            0.315392 * (3.0 * z * z - 1.0),
+            1.092548 * x * z,
+            0.546274 * (x * x - y * y),
+        ]
+    }
+
+    /// Accumulates the radiance coming from a direction, weighted by the solid angle it covers
+    pub fn add_sample(&mut self, direction: &Vector3<f32>, radiance: &Vector3<f32>, weight: f32) {
+        let basis = Self::basis(&direction.normalize());
+        for (coefficient, value) in self.coefficients.iter_mut().zip(basis.iter()) {
+            *coefficient += radiance * (value * weight);
+        }
+    }
+
+    /// Coefficients as a flat array, as expected by the shader
+    pub fn to_vec(&self) -> Vec<f32> {
+        self.coefficients
+            .iter()
+            .flat_map(|coefficient| coefficient.iter().copied())
+            .collect()
+    }
+}
+
+impl Default for SphericalHarmonics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,266 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use serde::Serialize;
+
+/// Work submitted to WebGL since the counters were last taken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct RenderCounters {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    /// Bindings of programs, textures and framebuffers, and changes of blending, depth and culling
+    pub state_changes: u32,
+}
+
+/// GPU memory currently allocated by the renderer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct GpuMemory {
+    /// Vertex and index buffers
+    pub buffer_bytes: u64,
+    /// Base level of textures, cubemaps and depth renderbuffers
+    pub texture_bytes: u64,
+}
+
+thread_local! {
+    // WebGL objects belong to the thread of their context, which tests keep separate
+    static COUNTERS: Cell<RenderCounters> = Cell::new(RenderCounters::default());
+    static MEMORY: Cell<GpuMemory> = Cell::new(GpuMemory::default());
+}
+
+fn update_counters(update: impl FnOnce(&mut RenderCounters)) {
+    COUNTERS.with(|counters| {
+        let mut value = counters.get();
+        update(&mut value);
+        counters.set(value);
+    });
+}
+
+fn update_memory(update: impl FnOnce(&mut GpuMemory)) {
+    MEMORY.with(|memory| {
+        let mut value = memory.get();
+        update(&mut value);
+        memory.set(value);
+    });
+}
+
+pub fn count_draw(triangles: u32) {
+    update_counters(|counters| {
+        counters.draw_calls += 1;
+        counters.triangles += triangles;
+    });
+}
+
+pub fn count_state_changes(count: u32) {
+    update_counters(|counters| counters.state_changes += count);
+}
+
+/// Returns the counters, starting them again from zero
+pub fn take_render_counters() -> RenderCounters {
+    COUNTERS.with(|counters| counters.replace(RenderCounters::default()))
+}
+
+pub fn allocate_buffer(bytes: u64) {
+    update_memory(|memory| memory.buffer_bytes += bytes);
+}
+
+pub fn free_buffer(bytes: u64) {
+    update_memory(|memory| memory.buffer_bytes = memory.buffer_bytes.saturating_sub(bytes));
+}
+
+pub fn allocate_texture(bytes: u64) {
+    update_memory(|memory| memory.texture_bytes += bytes);
+}
+
+pub fn free_texture(bytes: u64) {
+    update_memory(|memory| memory.texture_bytes = memory.texture_bytes.saturating_sub(bytes));
+}
+
+pub fn gpu_memory() -> GpuMemory {
+    MEMORY.with(Cell::get)
+}
+
+/// Latest durations in milliseconds, dropping the oldest ones beyond the capacity
+pub struct TimeWindow {
+    samples: VecDeque<f64>,
+    capacity: usize,
+}
+
+/// Distribution of the durations of a window, all zero when it is empty
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct TimeSummary {
+    pub samples: usize,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl TimeWindow {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, duration: f64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Returns the duration below or equal to which `percent` of the samples are, by nearest rank
+    pub fn percentile(&self, percent: f64) -> Option<f64> {
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        percentile(&sorted, percent)
+    }
+
+    pub fn summary(&self) -> TimeSummary {
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+            return TimeSummary::default();
+        };
+        let at = |percent| percentile(&sorted, percent).unwrap_or(0.0);
+        TimeSummary {
+            samples: sorted.len(),
+            min,
+            avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            max,
+            p50: at(50.0),
+            p90: at(90.0),
+            p99: at(99.0),
+        }
+    }
+}
+
+fn percentile(sorted: &[f64], percent: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied()
+}
+
+/// Calls made from JavaScript into the context
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CallCounts {
+    /// Calls between the start of the last frame and the start of the one before
+    pub last_frame: u32,
+    pub total: u64,
+    pub by_method: BTreeMap<&'static str, u64>,
+}
+
+/// Snapshot of the statistics, as exported to JavaScript
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub frames: u64,
+    /// Interval between the starts of consecutive frames in milliseconds
+    pub frame_time: TimeSummary,
+    /// Time spent drawing each frame in milliseconds
+    pub cpu_time: TimeSummary,
+    /// Work of the last frame, including uploads made since the previous one
+    pub last_frame: RenderCounters,
+    pub memory: GpuMemory,
+    pub calls: CallCounts,
+}
+
+/// Collects frame timings, rendering work and calls over the latest frames
+pub struct Stats {
+    frame_times: TimeWindow,
+    cpu_times: TimeWindow,
+    last_frame_start: Option<f64>,
+    frames: u64,
+    last_frame: RenderCounters,
+    calls: CallCounts,
+    calls_since_frame: u32,
+}
+
+impl Stats {
+    /// Number of frames timed by default, two seconds at 60 Hz
+    pub const DEFAULT_WINDOW: usize = 120;
+
+    pub fn new(window: usize) -> Self {
+        Self {
+            frame_times: TimeWindow::new(window),
+            cpu_times: TimeWindow::new(window),
+            last_frame_start: None,
+            frames: 0,
+            last_frame: RenderCounters::default(),
+            calls: CallCounts::default(),
+            calls_since_frame: 0,
+        }
+    }
+
+    pub fn count_call(&mut self, method: &'static str) {
+        self.calls_since_frame += 1;
+        self.calls.total += 1;
+        *self.calls.by_method.entry(method).or_insert(0) += 1;
+    }
+
+    /// Records a frame drawn between the specified times in milliseconds,
+    /// taking the render counters accumulated since the previous frame
+    pub fn end_frame(&mut self, start: f64, end: f64) {
+        if let Some(last_start) = self.last_frame_start {
+            self.frame_times.push(start - last_start);
+        }
+        self.cpu_times.push(end - start);
+        self.last_frame_start = Some(start);
+        self.frames += 1;
+
+        self.last_frame = take_render_counters();
+        self.calls.last_frame = self.calls_since_frame;
+        self.calls_since_frame = 0;
+    }
+
+    /// Forgets everything recorded, except the memory which is still allocated
+    pub fn reset(&mut self) {
+        let window = self.frame_times.capacity;
+        *self = Self::new(window);
+        take_render_counters();
+    }
+
+    /// Changes the number of frames timed, forgetting the previous timings
+    pub fn set_window(&mut self, window: usize) {
+        self.frame_times = TimeWindow::new(window);
+        self.cpu_times = TimeWindow::new(window);
+    }
+
+    pub fn report(&self) -> StatsReport {
+        StatsReport {
+            frames: self.frames,
+            frame_time: self.frame_times.summary(),
+            cpu_time: self.cpu_times.summary(),
+            last_frame: self.last_frame,
+            memory: gpu_memory(),
+            calls: self.calls.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(&self.report()).map_err(|e| e.to_string())
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_WINDOW)
+    }
+}
\ No newline at end of file
@@ -1,46 +1,243 @@
+use std::fmt;
 use web_sys::WebGlRenderingContext as GL;
 use web_sys::*;
-use crate::model::Image;
+use crate::model::{f32_to_f16, stats, Filter, Image, PixelFormat, Sampler};
+
+/// `OES_texture_half_float` constant, not exposed by web-sys
+const HALF_FLOAT_OES: u32 = 0x8D61;
+
+/// Reasons for a texture not being created or uploaded
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextureError {
+    /// The context could not create the texture, usually because it is lost
+    CreationFailed,
+    /// The texel type needs an extension the context does not support
+    Unsupported(&'static str),
+    /// The data does not match the size and format of the texture
+    InvalidData { expected: usize, actual: usize },
+    /// The texel type does not match the type of the data
+    InvalidType(TexelType),
+    /// The updated region does not fit in the texture
+    OutOfBounds,
+    /// `texImage2D` threw an exception
+    UploadFailed,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::CreationFailed => write!(f, "Failed to create texture"),
+            TextureError::Unsupported(extension) => {
+                write!(f, "Texture format unsupported: missing {}", extension)
+            }
+            TextureError::InvalidData { expected, actual } => write!(
+                f,
+                "Invalid texture data: expected {} values, got {}",
+                expected, actual
+            ),
+            TextureError::InvalidType(texel_type) => {
+                write!(f, "Invalid texture data for {:?} texels", texel_type)
+            }
+            TextureError::OutOfBounds => write!(f, "Texture region out of bounds"),
+            TextureError::UploadFailed => write!(f, "Failed to upload texture data"),
+        }
+    }
+}
+
+impl From<TextureError> for wasm_bindgen::JsValue {
+    fn from(error: TextureError) -> Self {
+        wasm_bindgen::JsValue::from_str(&error.to_string())
+    }
+}
+
+/// Storage of each channel of a texel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TexelType {
+    UnsignedByte,
+    /// 32-bit float, needs `OES_texture_float`
+    Float,
+    /// 16-bit float, needs `OES_texture_half_float`
+    HalfFloat,
+}
+
+impl TexelType {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            TexelType::UnsignedByte => GL::UNSIGNED_BYTE,
+            TexelType::Float => GL::FLOAT,
+            TexelType::HalfFloat => HALF_FLOAT_OES,
+        }
+    }
+
+    /// Bytes of each channel
+    pub fn size(self) -> u32 {
+        match self {
+            TexelType::UnsignedByte => 1,
+            TexelType::Float => 4,
+            TexelType::HalfFloat => 2,
+        }
+    }
+
+    /// Extension needed to create textures of this type
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            TexelType::UnsignedByte => None,
+            TexelType::Float => Some("OES_texture_float"),
+            TexelType::HalfFloat => Some("OES_texture_half_float"),
+        }
+    }
+
+    /// Extension needed to filter textures of this type linearly
+    pub fn linear_extension(self) -> Option<&'static str> {
+        match self {
+            TexelType::UnsignedByte => None,
+            TexelType::Float => Some("OES_texture_float_linear"),
+            TexelType::HalfFloat => Some("OES_texture_half_float_linear"),
+        }
+    }
+
+    /// Extension needed to render into textures of this type
+    pub fn render_extension(self) -> Option<&'static str> {
+        match self {
+            TexelType::UnsignedByte => None,
+            TexelType::Float => Some("WEBGL_color_buffer_float"),
+            TexelType::HalfFloat => Some("EXT_color_buffer_half_float"),
+        }
+    }
+
+    /// Returns an error when the context can not create textures of this type,
+    /// enabling the needed extension otherwise
+    pub fn check_support(self, gl: &GL) -> Result<(), TextureError> {
+        match self.extension() {
+            Some(extension) if !has_extension(gl, extension) => {
+                Err(TextureError::Unsupported(extension))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error when the context can not render into textures of this type,
+    /// enabling the needed extensions otherwise
+    pub fn check_render_support(self, gl: &GL) -> Result<(), TextureError> {
+        self.check_support(gl)?;
+        match self.render_extension() {
+            Some(extension) if !has_extension(gl, extension) => {
+                Err(TextureError::Unsupported(extension))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Element or pixels provided by the page, as a canvas drawing or a video frame
+pub enum TextureSource<'a> {
+    Canvas(&'a HtmlCanvasElement),
+    Image(&'a HtmlImageElement),
+    ImageData(&'a ImageData),
+    Video(&'a HtmlVideoElement),
+}
+
+impl TextureSource<'_> {
+    pub fn width(&self) -> u32 {
+        match self {
+            TextureSource::Canvas(canvas) => canvas.width(),
+            TextureSource::Image(image) => image.natural_width(),
+            TextureSource::ImageData(data) => data.width(),
+            TextureSource::Video(video) => video.video_width(),
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            TextureSource::Canvas(canvas) => canvas.height(),
+            TextureSource::Image(image) => image.natural_height(),
+            TextureSource::ImageData(data) => data.height(),
+            TextureSource::Video(video) => video.video_height(),
+        }
+    }
+}
 
 pub struct Texture {
     pub gl: GL,
     pub handle: WebGlTexture,
     pub width: u32,
     pub height: u32,
+    pub format: PixelFormat,
+    pub texel_type: TexelType,
+    /// Requested sampling, adjusted to what the texture size and type support when applied
+    sampler: Sampler,
 }
 
 impl Texture {
-    /// Returns a new texture uploading data from the specified image
+    /// Returns a new texture uploading data from the specified image,
+    /// with linear filtering clamped to the edges
     pub fn from_image(gl: GL, image: &Image) -> Self {
-        let handle = gl.create_texture().expect("Failed to create texture");
+        Self::from_image_with_sampler(gl, image, Sampler::new())
+    }
 
-        let mut texture = Self {
-            gl,
-            handle,
-            width: 0,
-            height: 0,
-        };
+    /// Returns a new texture uploading data from the specified image,
+    /// generating mipmaps when the sampler uses them
+    pub fn from_image_with_sampler(gl: GL, image: &Image, sampler: Sampler) -> Self {
+        let mut texture = Self::create(gl, image.format, TexelType::UnsignedByte, sampler)
+            .expect("Failed to create texture");
 
         texture.bind();
+        texture.upload(Some(&image.data), image.width, image.height);
 
         texture
-            .gl
-            .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
-        texture
-            .gl
-            .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+    }
 
-        //    gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MIN_FILTER, gl.LINEAR);
-        texture
-            .gl
-            .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
-        texture
-            .gl
-            .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+    /// Returns a new texture of the specified format and size with undefined content,
+    /// or an error when the texel type is not supported
+    pub fn empty(
+        gl: GL,
+        format: PixelFormat,
+        texel_type: TexelType,
+        width: u32,
+        height: u32,
+        sampler: Sampler,
+    ) -> Result<Self, TextureError> {
+        let mut texture = Self::create(gl, format, texel_type, sampler)?;
+        texture.bind();
+        texture.upload_bytes(None, width, height)?;
+        Ok(texture)
+    }
 
-        texture.upload(Some(&image.data), image.width, image.height);
+    /// Returns a new float texture with the specified values, one per channel of the format
+    /// for each texel, stored as 32 or 16-bit floats depending on the texel type
+    pub fn from_floats(
+        gl: GL,
+        format: PixelFormat,
+        texel_type: TexelType,
+        data: &[f32],
+        width: u32,
+        height: u32,
+        sampler: Sampler,
+    ) -> Result<Self, TextureError> {
+        let mut texture = Self::create(gl, format, texel_type, sampler)?;
+        texture.bind();
+        texture.upload_floats(data, width, height)?;
+        Ok(texture)
+    }
 
-        texture
+    fn create(
+        gl: GL,
+        format: PixelFormat,
+        texel_type: TexelType,
+        sampler: Sampler,
+    ) -> Result<Self, TextureError> {
+        texel_type.check_support(&gl)?;
+        let handle = gl.create_texture().ok_or(TextureError::CreationFailed)?;
+
+        Ok(Self {
+            gl,
+            handle,
+            width: 0,
+            height: 0,
+            format,
+            texel_type,
+            sampler,
+        })
     }
 
     /// Returns a new default texture with a default image (2x2 red, blue, green, white)
@@ -55,31 +252,281 @@ impl Texture {
     pub fn bind(&self) {
         //self.gl.active_texture(GL::TEXTURE0);
         self.gl.bind_texture(GL::TEXTURE_2D, Some(&self.handle));
+        stats::count_state_changes(1);
     }
 
-    /// Uploads pixels data to the texture memory in the GPU
+    /// Binds the texture to the specified texture unit, leaving it as the active one
+    pub fn bind_to(&self, unit: u32) {
+        self.gl.active_texture(GL::TEXTURE0 + unit);
+        self.bind();
+    }
+
+    pub fn get_sampler(&self) -> Sampler {
+        self.sampler
+    }
+
+    /// Changes how the texture is sampled, generating mipmaps if needed
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = sampler;
+        self.bind();
+        self.apply_sampler();
+    }
+
+    /// Returns the sampler falling back to what WebGL 1 supports
+    /// for non power of two sizes and float texels
+    fn supported_sampler(&self) -> Sampler {
+        let mut sampler = self.sampler.supported_for(self.width, self.height);
+
+        if let Some(linear_extension) = self.texel_type.linear_extension() {
+            sampler.min_filter = sampler.min_filter.without_mipmaps();
+            if !has_extension(&self.gl, linear_extension) {
+                sampler.min_filter = Filter::Nearest;
+                sampler.mag_filter = Filter::Nearest;
+            }
+        }
+
+        sampler
+    }
+
+    /// Sets the parameters of the bound texture from the sampler
+    fn apply_sampler(&self) {
+        let sampler = self.supported_sampler();
+        sampler.apply(&self.gl, GL::TEXTURE_2D);
+        if sampler.min_filter.uses_mipmaps() {
+            self.gl.generate_mipmap(GL::TEXTURE_2D);
+        }
+    }
+
+    /// Regenerates the mipmaps of the bound texture after its content changed, if it has any
+    fn update_mipmaps(&self) {
+        if self.supported_sampler().min_filter.uses_mipmaps() {
+            self.gl.generate_mipmap(GL::TEXTURE_2D);
+        }
+    }
+
+    fn check_region(&self, x: u32, y: u32, width: u32, height: u32) -> Result<(), TextureError> {
+        // Sizes come from JavaScript and may overflow
+        let fits = |start: u32, len: u32, size: u32| {
+            start.checked_add(len).is_some_and(|end| end <= size)
+        };
+        if fits(x, width, self.width) && fits(y, height, self.height) {
+            Ok(())
+        } else {
+            Err(TextureError::OutOfBounds)
+        }
+    }
+
+    /// Replaces the pixels of a region with one byte per channel, keeping the rest
+    pub fn update_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        if self.texel_type != TexelType::UnsignedByte {
+            return Err(TextureError::InvalidType(self.texel_type));
+        }
+        self.check_region(x, y, width, height)?;
+        self.check_len(pixels.len(), width, height)?;
+
+        self.bind();
+        self.gl.pixel_storei(GL::UNPACK_ALIGNMENT, 1);
+        self.gl
+            .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+                GL::TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                self.format.to_gl(),
+                self.texel_type.to_gl(),
+                Some(pixels),
+            )
+            .map_err(|_| TextureError::UploadFailed)?;
+
+        self.update_mipmaps();
+        Ok(())
+    }
+
+    /// Replaces a region with the content of the source, its top-left corner at `x`, `y`.
+    /// Meant for streaming, as the texture keeps its size and is not reallocated
+    pub fn update_from_source(
+        &self,
+        x: u32,
+        y: u32,
+        source: &TextureSource,
+    ) -> Result<(), TextureError> {
+        self.check_region(x, y, source.width(), source.height())?;
+
+        self.bind();
+        let gl = &self.gl;
+        let (x, y) = (x as i32, y as i32);
+        let format = self.format.to_gl();
+        let kind = self.texel_type.to_gl();
+        let target = GL::TEXTURE_2D;
+        match source {
+            TextureSource::Canvas(canvas) => {
+                gl.tex_sub_image_2d_with_u32_and_u32_and_canvas(target, 0, x, y, format, kind, canvas)
+            }
+            TextureSource::Image(image) => {
+                gl.tex_sub_image_2d_with_u32_and_u32_and_image(target, 0, x, y, format, kind, image)
+            }
+            TextureSource::ImageData(data) => {
+                gl.tex_sub_image_2d_with_u32_and_u32_and_image_data(target, 0, x, y, format, kind, data)
+            }
+            TextureSource::Video(video) => {
+                gl.tex_sub_image_2d_with_u32_and_u32_and_video(target, 0, x, y, format, kind, video)
+            }
+        }
+        .map_err(|_| TextureError::UploadFailed)?;
+
+        self.update_mipmaps();
+        Ok(())
+    }
+
+    /// Reallocates the texture with the size and content of the source
+    pub fn upload_source(&mut self, source: &TextureSource) -> Result<(), TextureError> {
+        self.bind();
+        let gl = &self.gl;
+        let format = self.format.to_gl();
+        let internal = format as i32;
+        let kind = self.texel_type.to_gl();
+        let target = GL::TEXTURE_2D;
+        match source {
+            TextureSource::Canvas(canvas) => {
+                gl.tex_image_2d_with_u32_and_u32_and_canvas(target, 0, internal, format, kind, canvas)
+            }
+            TextureSource::Image(image) => {
+                gl.tex_image_2d_with_u32_and_u32_and_image(target, 0, internal, format, kind, image)
+            }
+            TextureSource::ImageData(data) => {
+                gl.tex_image_2d_with_u32_and_u32_and_image_data(target, 0, internal, format, kind, data)
+            }
+            TextureSource::Video(video) => {
+                gl.tex_image_2d_with_u32_and_u32_and_video(target, 0, internal, format, kind, video)
+            }
+        }
+        .map_err(|_| TextureError::UploadFailed)?;
+
+        self.set_size(source.width(), source.height());
+        self.apply_sampler();
+        Ok(())
+    }
+
+    /// Uploads pixels data to the texture memory in the GPU, the texture being bound
     pub fn upload(&mut self, pixels: Option<&[u8]>, width: u32, height: u32) {
+        self.upload_bytes(pixels, width, height)
+            .expect("Failed to upload texture data");
+    }
+
+    /// Uploads one byte per channel to the bound texture, `None` only allocating it.
+    /// Fails when the data does not match the size, or the texture is not made of bytes
+    pub fn upload_bytes(
+        &mut self,
+        pixels: Option<&[u8]>,
+        width: u32,
+        height: u32,
+    ) -> Result<(), TextureError> {
+        if let Some(pixels) = pixels {
+            if self.texel_type != TexelType::UnsignedByte {
+                return Err(TextureError::InvalidType(self.texel_type));
+            }
+            self.check_len(pixels.len(), width, height)?;
+        }
+
+        self.gl.pixel_storei(GL::UNPACK_ALIGNMENT, 1);
         self.gl
             .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
                 GL::TEXTURE_2D,
                 0,
-                GL::RGBA as i32,
+                self.format.to_gl() as i32,
                 width as i32,
                 height as i32,
                 0,
-                GL::RGBA,
-                GL::UNSIGNED_BYTE,
+                self.format.to_gl(),
+                self.texel_type.to_gl(),
                 pixels,
             )
-            .expect("Failed to upload texture data");
+            .map_err(|_| TextureError::UploadFailed)?;
+
+        self.set_size(width, height);
+        self.apply_sampler();
+        Ok(())
+    }
+
+    /// Uploads float channels to the bound texture, converted to half floats if needed
+    pub fn upload_floats(&mut self, data: &[f32], width: u32, height: u32) -> Result<(), TextureError> {
+        self.check_len(data.len(), width, height)?;
+
+        let array: js_sys::Object = match self.texel_type {
+            TexelType::Float => js_sys::Float32Array::from(data).into(),
+            TexelType::HalfFloat => {
+                let halves: Vec<u16> = data.iter().map(|&value| f32_to_f16(value)).collect();
+                js_sys::Uint16Array::from(halves.as_slice()).into()
+            }
+            TexelType::UnsignedByte => return Err(TextureError::InvalidType(self.texel_type)),
+        };
+
+        self.gl.pixel_storei(GL::UNPACK_ALIGNMENT, 1);
+        self.gl
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+                GL::TEXTURE_2D,
+                0,
+                self.format.to_gl() as i32,
+                width as i32,
+                height as i32,
+                0,
+                self.format.to_gl(),
+                self.texel_type.to_gl(),
+                Some(&array),
+            )
+            .map_err(|_| TextureError::UploadFailed)?;
+
+        self.set_size(width, height);
+        self.apply_sampler();
+        Ok(())
+    }
+
+    /// Bytes of the base level
+    fn bytes(&self) -> u64 {
+        self.width as u64 * self.height as u64 * (self.format.channels() * self.texel_type.size()) as u64
+    }
 
+    /// Records the size of the storage reallocated by an upload
+    fn set_size(&mut self, width: u32, height: u32) {
+        stats::free_texture(self.bytes());
         self.width = width;
         self.height = height;
+        stats::allocate_texture(self.bytes());
+    }
+
+    fn check_len(&self, len: usize, width: u32, height: u32) -> Result<(), TextureError> {
+        // Saturating, so sizes too large for memory never match
+        let expected = (width as usize)
+            .saturating_mul(height as usize)
+            .saturating_mul(self.format.channels() as usize);
+        if len == expected {
+            Ok(())
+        } else {
+            Err(TextureError::InvalidData {
+                expected,
+                actual: len,
+            })
+        }
     }
 }
 
 impl Drop for Texture {
     fn drop(&mut self) {
-        self.gl.delete_texture(Some(&self.handle))
+        self.gl.delete_texture(Some(&self.handle));
+        stats::free_texture(self.bytes());
     }
+}
+
+/// Returns whether the extension is supported, enabling it
+fn has_extension(gl: &GL, name: &str) -> bool {
+    gl.get_extension(name).ok().flatten().is_some()
 }
\ No newline at end of file
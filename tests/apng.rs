@@ -0,0 +1,48 @@
+//! Encodes animations and decodes them back frame by frame with the `png` decoder.
+
+use md::model::{encode_apng, Image, ImageError};
+
+fn frame(width: u32, height: u32, seed: u8) -> Image {
+    let data = (0..width * height * 4)
+        .map(|i| (i as u8).wrapping_mul(7).wrapping_add(seed))
+        .collect();
+    Image::from_vec(data, width, height)
+}
+
+#[test]
+fn frames_decode_in_order() {
+    let frames: Vec<Image> = (0..4).map(|i| frame(5, 3, i * 40)).collect();
+    let apng = encode_apng(&frames, 40.0).unwrap();
+
+    let decoder = png::Decoder::new(apng.as_slice());
+    let (output, mut reader) = decoder.read_info().unwrap();
+    let control = *reader.info().animation_control().unwrap();
+    assert_eq!(control.num_frames, 4);
+    assert_eq!(control.num_plays, 0);
+
+    let mut buffer = vec![0; output.buffer_size()];
+    for expected in &frames {
+        reader.next_frame(&mut buffer).unwrap();
+        let frame_control = reader.info().frame_control().unwrap();
+        assert_eq!(frame_control.delay_num, 400);
+        assert_eq!(frame_control.delay_den, 10000);
+        assert_eq!(buffer, expected.data);
+    }
+}
+
+#[test]
+fn first_frame_is_the_default_image() {
+    let frames = vec![frame(2, 2, 1), frame(2, 2, 2)];
+    let apng = encode_apng(&frames, 16.0).unwrap();
+    assert_eq!(Image::from_png(&apng).unwrap(), frames[0]);
+}
+
+#[test]
+fn frames_of_different_sizes_are_rejected() {
+    let frames = vec![frame(2, 2, 0), frame(3, 2, 0)];
+    assert!(matches!(
+        encode_apng(&frames, 16.0),
+        Err(ImageError::EncodingFailed(_))
+    ));
+    assert!(encode_apng(&[], 16.0).is_err());
+}
\ No newline at end of file
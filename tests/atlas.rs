@@ -0,0 +1,133 @@
+//! Packs rectangles and images into atlases, checking where they land.
+
+use md::model::{Atlas, AtlasBuilder, AtlasPacker, Image, Rect};
+
+fn overlap(a: &Rect, b: &Rect) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+fn solid(width: u32, height: u32, value: u8) -> Image {
+    Image::from_vec(vec![value; (width * height * 4) as usize], width, height)
+}
+
+#[test]
+fn packed_rectangles_do_not_overlap() {
+    let mut packer = AtlasPacker::new(256, 256);
+    let sizes = [(30, 10), (10, 40), (25, 25), (64, 8), (8, 64), (17, 33), (40, 12), (5, 5)];
+    let mut rects: Vec<Rect> = vec![];
+    for _ in 0..3 {
+        for &(width, height) in &sizes {
+            let rect = packer.pack(width, height).unwrap();
+            assert_eq!((rect.width, rect.height), (width, height));
+            assert!(rect.x + rect.width <= 256 && rect.y + rect.height <= 256);
+            for other in &rects {
+                assert!(!overlap(&rect, other), "{:?} overlaps {:?}", rect, other);
+            }
+            rects.push(rect);
+        }
+    }
+}
+
+#[test]
+fn packer_fills_the_bottom_first() {
+    let mut packer = AtlasPacker::new(64, 64);
+    assert_eq!(packer.pack(32, 16).unwrap(), Rect { x: 0, y: 0, width: 32, height: 16 });
+    assert_eq!(packer.pack(32, 8).unwrap(), Rect { x: 32, y: 0, width: 32, height: 8 });
+    // The lowest spot is now above the shorter rectangle
+    assert_eq!(packer.pack(32, 8).unwrap(), Rect { x: 32, y: 8, width: 32, height: 8 });
+}
+
+#[test]
+fn packer_rejects_what_does_not_fit() {
+    let mut packer = AtlasPacker::new(16, 16);
+    assert!(packer.pack(17, 1).is_none());
+    assert!(packer.pack(16, 16).is_some());
+    assert!(packer.pack(1, 1).is_none());
+}
+
+#[test]
+fn images_are_padded() {
+    let mut builder = AtlasBuilder::new();
+    builder.padding = 2;
+    for i in 0..6 {
+        builder.add(&format!("image{}", i), solid(6, 6, 100 + i as u8));
+    }
+    let atlas = builder.build().unwrap();
+
+    let rects: Vec<Rect> = (0..6).map(|i| atlas.get_rect(&format!("image{}", i)).unwrap()).collect();
+    for (i, a) in rects.iter().enumerate() {
+        let padded = Rect {
+            width: a.width + 2,
+            height: a.height + 2,
+            ..*a
+        };
+        for b in &rects[i + 1..] {
+            assert!(!overlap(&padded, b), "{:?} is closer than the padding to {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn images_are_copied_to_their_rect() {
+    let mut builder = AtlasBuilder::new();
+    builder.add("a", solid(3, 2, 10));
+    builder.add("b", solid(2, 5, 20));
+    let atlas = builder.build().unwrap();
+
+    for (name, value) in [("a", 10), ("b", 20)] {
+        let rect = atlas.get_rect(name).unwrap();
+        let image = atlas.image.crop(rect.x, rect.y, rect.width, rect.height).unwrap();
+        assert!(image.data.iter().all(|&byte| byte == value), "{}", name);
+    }
+
+    let uv = atlas.get_uv("a").unwrap();
+    let rect = atlas.get_rect("a").unwrap();
+    assert_eq!(uv.min[0], rect.x as f32 / atlas.image.width as f32);
+    assert_eq!(uv.max[1], (rect.y + rect.height) as f32 / atlas.image.height as f32);
+}
+
+#[test]
+fn atlas_grows_in_powers_of_two() {
+    let mut builder = AtlasBuilder::new();
+    builder.add("first", solid(20, 20, 1));
+    let atlas = builder.build().unwrap();
+    assert_eq!((atlas.image.width, atlas.image.height), (32, 32));
+
+    for i in 0..8 {
+        builder.add(&format!("more{}", i), solid(20, 20, 1));
+    }
+    let atlas = builder.build().unwrap();
+    assert!(atlas.image.width.is_power_of_two() && atlas.image.height.is_power_of_two());
+    assert!(atlas.image.width * atlas.image.height >= 9 * 21 * 21);
+    assert!(atlas.image.width > 32 || atlas.image.height > 32);
+}
+
+#[test]
+fn adding_a_name_again_replaces_the_image() {
+    let mut builder = AtlasBuilder::new();
+    builder.add("icon", solid(4, 4, 1));
+    builder.add("icon", solid(8, 2, 2));
+    let atlas = builder.build().unwrap();
+
+    assert_eq!(atlas.names().count(), 1);
+    let rect = atlas.get_rect("icon").unwrap();
+    assert_eq!((rect.width, rect.height), (8, 2));
+}
+
+#[test]
+fn too_large_images_are_an_error() {
+    let mut builder = AtlasBuilder::new();
+    builder.padding = 0;
+    // A single row is enough to exceed the maximum width
+    let wide = Atlas::MAX_SIZE + 1;
+    builder.add("wide", Image::from_vec(vec![0; (wide * 4) as usize], wide, 1));
+    assert!(builder.build().is_err());
+}
+
+#[test]
+fn empty_builder_gives_a_tiny_atlas() {
+    let atlas = AtlasBuilder::new().build().unwrap();
+    assert_eq!((atlas.image.width, atlas.image.height), (1, 1));
+    assert_eq!(atlas.names().count(), 0);
+    assert!(atlas.get_uv("missing").is_none());
+}
\ No newline at end of file
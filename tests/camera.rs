@@ -0,0 +1,49 @@
+//! Checks the projection parameters before nalgebra builds the matrix.
+
+use md::model::{Camera, Projection};
+use std::f32::consts::PI;
+
+#[test]
+fn valid_projections_are_accepted() {
+    let mut camera = Camera::new(640, 480);
+    assert!(camera.set_projection(Projection::Perspective { fov: 1.0 }, 0.1, 100.0).is_ok());
+    assert!(camera.set_projection(Projection::Orthographic { height: 2.0 }, 0.1, 100.0).is_ok());
+    assert_eq!(camera.projection, Projection::Orthographic { height: 2.0 });
+    assert!(camera.proj().iter().all(|x| x.is_finite()));
+}
+
+#[test]
+fn invalid_depth_ranges_are_rejected() {
+    let fov = Projection::Perspective { fov: 1.0 };
+    for &(near, far) in &[(0.0, 10.0), (-1.0, 10.0), (1.0, 1.0), (2.0, 1.0), (1.0, f32::INFINITY), (f32::NAN, 10.0)] {
+        assert!(fov.validate(near, far).is_err(), "{} {}", near, far);
+    }
+}
+
+#[test]
+fn invalid_sizes_are_rejected() {
+    for &fov in &[0.0, -1.0, PI, 4.0, f32::NAN] {
+        assert!(Projection::Perspective { fov }.validate(0.1, 10.0).is_err(), "{}", fov);
+    }
+    for &height in &[0.0, -1.0, f32::INFINITY, f32::NAN] {
+        assert!(Projection::Orthographic { height }.validate(0.1, 10.0).is_err(), "{}", height);
+    }
+}
+
+#[test]
+fn rejected_projection_is_not_applied() {
+    let mut camera = Camera::new(640, 480);
+    let before = camera.proj();
+    assert!(camera.set_projection(Projection::Perspective { fov: 1.0 }, 5.0, 5.0).is_err());
+    assert_eq!(camera.near, Camera::DEFAULT_NEAR);
+    assert_eq!(camera.proj(), before);
+}
+
+#[test]
+fn zero_aspect_does_not_panic() {
+    let mut camera = Camera::new(640, 480);
+    camera.aspect = 0.0;
+    assert!(camera.proj().iter().all(|x| x.is_finite()));
+    camera.set_projection(Projection::Orthographic { height: 2.0 }, 0.1, 10.0).unwrap();
+    assert!(camera.proj().iter().all(|x| x.is_finite()));
+}
\ No newline at end of file
@@ -0,0 +1,100 @@
+//! Drives the animation timer with a manual clock, so every frame time is known.
+
+use md::model::{ManualClock, OrbitCamera, Timer};
+use nalgebra::{Point3, Vector2};
+
+fn timer(step: f64) -> Timer {
+    Timer::new(Box::new(ManualClock::new(0.0, step)))
+}
+
+#[test]
+fn fixed_step_advances_every_frame() {
+    let mut timer = timer(20.0);
+    for frame in 1..=5 {
+        assert_eq!(timer.tick(), 20.0);
+        assert_eq!(timer.elapsed(), frame as f64 * 20.0);
+    }
+}
+
+#[test]
+fn scale_slows_down_animations() {
+    let mut timer = timer(20.0);
+    timer.scale = 0.25;
+    timer.tick();
+    timer.tick();
+    assert_eq!(timer.delta(), 5.0);
+    assert_eq!(timer.real_delta(), 20.0);
+    assert_eq!(timer.elapsed(), 10.0);
+}
+
+#[test]
+fn pause_freezes_until_stepped() {
+    let mut timer = timer(20.0);
+    timer.step = 10.0;
+    timer.tick();
+    timer.pause();
+    timer.tick();
+    timer.tick();
+    assert_eq!(timer.elapsed(), 20.0);
+    assert_eq!(timer.real_delta(), 20.0);
+
+    timer.step_frame();
+    timer.step_frame();
+    assert_eq!(timer.tick(), 10.0);
+    assert_eq!(timer.tick(), 10.0);
+    assert_eq!(timer.tick(), 0.0);
+    assert_eq!(timer.elapsed(), 40.0);
+    assert!(timer.is_paused());
+
+    timer.resume();
+    timer.tick();
+    assert_eq!(timer.elapsed(), 60.0);
+}
+
+#[test]
+fn long_frames_are_clamped() {
+    let mut timer = timer(500.0);
+    timer.max_delta = 50.0;
+    assert_eq!(timer.tick(), 50.0);
+    assert_eq!(timer.elapsed(), 50.0);
+}
+
+#[test]
+fn orbit_motion_does_not_depend_on_frame_rate() {
+    let run = |frames: u32| {
+        let mut camera = OrbitCamera::new(Point3::origin(), 3.0);
+        camera.rotate(Vector2::new(40.0, 10.0));
+        for _ in 0..frames {
+            camera.update(2.0 / frames as f32);
+        }
+        (camera.yaw, camera.pitch)
+    };
+
+    let (yaw_slow, pitch_slow) = run(60);
+    let (yaw_fast, pitch_fast) = run(288);
+    assert!((yaw_slow - yaw_fast).abs() < 1e-4);
+    assert!((pitch_slow - pitch_fast).abs() < 1e-4);
+    assert!(yaw_slow < 0.0);
+}
+
+#[test]
+fn orbit_does_not_move_without_time() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 3.0);
+    camera.rotate(Vector2::new(40.0, 10.0));
+    camera.update(0.0);
+    assert_eq!(camera.yaw, 0.0);
+    camera.update(1.0 / 60.0);
+    assert!((camera.yaw + 0.4).abs() < 1e-6);
+}
+
+#[test]
+fn full_damping_still_stops() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 3.0);
+    camera.damping = 1.0;
+    camera.rotate(Vector2::new(40.0, 0.0));
+    camera.update(60.0);
+    let yaw = camera.yaw;
+    assert!(yaw.is_finite() && yaw < 0.0);
+    camera.update(1.0);
+    assert!((camera.yaw - yaw).abs() < 1e-4, "{} {}", camera.yaw, yaw);
+}
\ No newline at end of file
@@ -0,0 +1,146 @@
+//! Checks the directions of the cubemap faces and the sampling of panoramas.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use md::model::cubemap::sample_equirectangular;
+use md::model::{Cubemap, Image};
+use nalgebra::Vector3;
+
+fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+    assert!((actual - expected).norm() < 1e-5, "{:?} != {:?}", actual, expected);
+}
+
+#[test]
+fn face_centers_follow_the_axes() {
+    let axes = [
+        Vector3::x(),
+        -Vector3::x(),
+        Vector3::y(),
+        -Vector3::y(),
+        Vector3::z(),
+        -Vector3::z(),
+    ];
+    for (face, axis) in axes.iter().enumerate() {
+        assert_close(Cubemap::face_direction(face, 0.0, 0.0), *axis);
+    }
+}
+
+#[test]
+fn rows_go_down_on_side_faces() {
+    // Images start with the top row, so `t` grows downwards
+    for face in [0, 1, 4, 5] {
+        let top = Cubemap::face_direction(face, 0.0, -1.0);
+        let bottom = Cubemap::face_direction(face, 0.0, 1.0);
+        assert!(top.y > bottom.y, "face {}", face);
+    }
+}
+
+#[test]
+fn faces_meet_at_the_seams() {
+    // Directions on the edges are shared by two faces, or three at corners
+    let steps = 8;
+    let mut owners: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+    for face in 0..6 {
+        for i in 0..=steps {
+            for j in 0..=steps {
+                let s = 2.0 * i as f32 / steps as f32 - 1.0;
+                let t = 2.0 * j as f32 / steps as f32 - 1.0;
+                let d = Cubemap::face_direction(face, s, t);
+                let key = [d.x, d.y, d.z].map(|c| (c * steps as f32).round() as i32);
+                owners.entry(key).or_default().push(face);
+            }
+        }
+    }
+
+    for (key, faces) in &owners {
+        let on_edge = key.iter().filter(|c| c.abs() == steps).count();
+        assert_eq!(faces.len(), on_edge, "{:?} is on faces {:?}", key, faces);
+    }
+}
+
+#[test]
+fn faces_are_oriented_consistently() {
+    // As specified by OpenGL, on every face the right and up image directions
+    // make the same turn around the face axis
+    for face in 0..6 {
+        let center = Cubemap::face_direction(face, 0.0, 0.0);
+        let right = Cubemap::face_direction(face, 1.0, 0.0) - center;
+        let down = Cubemap::face_direction(face, 0.0, 1.0) - center;
+        assert_close(right.cross(&-down), center);
+    }
+}
+
+/// Panorama where each texel stores its own coordinates
+fn coordinate_panorama(width: u32, height: u32) -> Image {
+    let mut data = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            data.extend_from_slice(&[x as u8, y as u8, 0, 255]);
+        }
+    }
+    Image::from_vec(data, width, height)
+}
+
+/// Direction through the center of a texel of the panorama
+fn texel_direction(x: u32, y: u32, width: u32, height: u32) -> Vector3<f32> {
+    let longitude = ((x as f32 + 0.5) / width as f32 - 0.5) * 2.0 * PI;
+    let polar = (y as f32 + 0.5) / height as f32 * PI;
+    Vector3::new(
+        polar.sin() * longitude.sin(),
+        polar.cos(),
+        -polar.sin() * longitude.cos(),
+    )
+}
+
+#[test]
+fn texel_centers_round_trip() {
+    let (width, height) = (16, 8);
+    let panorama = coordinate_panorama(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = sample_equirectangular(&panorama, &texel_direction(x, y, width, height));
+            assert_eq!(color, [x as u8, y as u8, 0, 255], "texel {}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn known_directions() {
+    let (width, height) = (16, 8);
+    let panorama = coordinate_panorama(width, height);
+    // The center of the panorama looks towards -Z, between the two middle columns
+    let forward = sample_equirectangular(&panorama, &-Vector3::z());
+    assert_eq!(forward[0], 8);
+    assert_eq!(forward[1], 4);
+    // Straight up samples the top row
+    assert_eq!(sample_equirectangular(&panorama, &Vector3::y())[1], 0);
+    assert_eq!(sample_equirectangular(&panorama, &-Vector3::y())[1], height as u8 - 1);
+}
+
+#[test]
+fn horizontal_seam_wraps_around() {
+    let (width, height) = (4, 2);
+    // Only the first column is white
+    let mut data = vec![0u8; (width * height * 4) as usize];
+    for y in 0..height {
+        let i = (y * width * 4) as usize;
+        data[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+    }
+    let panorama = Image::from_vec(data, width, height);
+
+    // Looking towards +Z is the left and right edges, halfway between the first and last columns
+    let behind = sample_equirectangular(&panorama, &Vector3::new(0.0, 0.0, 1.0));
+    assert!((126..=129).contains(&behind[0]), "{:?}", behind);
+}
+
+#[test]
+fn face_sizes_are_checked() {
+    assert_eq!(Cubemap::face_len(256, 4096), Ok(256 * 256 * 4));
+    assert_eq!(Cubemap::face_len(4096, 4096), Ok(4096 * 4096 * 4));
+    assert!(Cubemap::face_len(0, 4096).is_err());
+    assert!(Cubemap::face_len(4097, 4096).is_err());
+    // No cubemap fits when the limit could not be read
+    assert!(Cubemap::face_len(1, 0).is_err());
+    // Overflows even a 64-bit length instead of wrapping
+    assert!(Cubemap::face_len(u32::MAX, u32::MAX).is_err());
+}
\ No newline at end of file
@@ -0,0 +1,142 @@
+//! Checks the bounds math frustum culling relies on.
+
+use std::f32::consts::FRAC_PI_2;
+use md::model::node::{merge_bounds, ChildBounds};
+use md::model::{Aabb, Frustum};
+use nalgebra::{Isometry3, Perspective3, Point3, Translation3, UnitQuaternion, Vector3};
+
+fn unit_box() -> Aabb {
+    Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+}
+
+fn assert_close(actual: &Aabb, expected: &Aabb) {
+    let close = (actual.min - expected.min).norm() < 1e-5 && (actual.max - expected.max).norm() < 1e-5;
+    assert!(close, "{:?} != {:?}", actual, expected);
+}
+
+/// Camera at the origin looking down -Z with a 90 degrees field of view
+fn frustum() -> Frustum {
+    let proj = Perspective3::new(1.0, FRAC_PI_2, 1.0, 100.0).to_homogeneous();
+    Frustum::from_matrix(&proj)
+}
+
+fn at(x: f32, y: f32, z: f32) -> Aabb {
+    let half = Vector3::new(0.5, 0.5, 0.5);
+    let center = Point3::new(x, y, z);
+    Aabb::new(center - half, center + half)
+}
+
+fn child(bounds: Option<Aabb>, translation: Vector3<f32>, has_geometry: bool) -> ChildBounds {
+    ChildBounds {
+        bounds,
+        transform: Isometry3::from_parts(Translation3::from(translation), UnitQuaternion::identity()),
+        has_geometry,
+    }
+}
+
+#[test]
+fn translation_moves_the_box() {
+    let transform = Isometry3::translation(1.0, 2.0, 3.0);
+    let expected = Aabb::new(Point3::new(0.0, 1.0, 2.0), Point3::new(2.0, 3.0, 4.0));
+    assert_close(&unit_box().transform(&transform), &expected);
+}
+
+#[test]
+fn rotation_encloses_the_rotated_box() {
+    // A quarter turn swaps the axes, an eighth of a turn widens the box by √2
+    let quarter = Isometry3::rotation(Vector3::y() * FRAC_PI_2);
+    let long = Aabb::new(Point3::new(-2.0, -1.0, -1.0), Point3::new(2.0, 1.0, 1.0));
+    let swapped = Aabb::new(Point3::new(-1.0, -1.0, -2.0), Point3::new(1.0, 1.0, 2.0));
+    assert_close(&long.transform(&quarter), &swapped);
+
+    let eighth = Isometry3::rotation(Vector3::z() * FRAC_PI_2 / 2.0);
+    let s = 2f32.sqrt();
+    let widened = Aabb::new(Point3::new(-s, -s, -1.0), Point3::new(s, s, 1.0));
+    assert_close(&unit_box().transform(&eighth), &widened);
+}
+
+#[test]
+fn transformed_box_contains_the_transformed_corners() {
+    let transform = Isometry3::new(Vector3::new(3.0, -1.0, 2.0), Vector3::new(0.3, 1.1, -0.7));
+    let aabb = Aabb::new(Point3::new(-1.0, 0.0, -2.0), Point3::new(2.0, 1.0, 0.5));
+    let moved = aabb.transform(&transform);
+    for i in 0..8 {
+        let corner = Point3::new(
+            if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+            if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+            if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+        );
+        let corner = transform * corner;
+        assert!((0..3).all(|axis| {
+            corner[axis] >= moved.min[axis] - 1e-5 && corner[axis] <= moved.max[axis] + 1e-5
+        }));
+    }
+}
+
+#[test]
+fn frustum_keeps_boxes_in_view() {
+    let frustum = frustum();
+    assert!(frustum.intersects(&at(0.0, 0.0, -10.0)));
+    // Straddling the near plane and the left side
+    assert!(frustum.intersects(&at(0.0, 0.0, -1.0)));
+    assert!(frustum.intersects(&at(-10.0, 0.0, -10.0)));
+    // A box containing the whole frustum
+    let huge = Aabb::new(Point3::new(-500.0, -500.0, -500.0), Point3::new(500.0, 500.0, 500.0));
+    assert!(frustum.intersects(&huge));
+}
+
+#[test]
+fn frustum_culls_boxes_out_of_view() {
+    let frustum = frustum();
+    // Behind, before the near plane and past the far one
+    assert!(!frustum.intersects(&at(0.0, 0.0, 10.0)));
+    assert!(!frustum.intersects(&at(0.0, 0.0, -0.2)));
+    assert!(!frustum.intersects(&at(0.0, 0.0, -110.0)));
+    // Outside each side of the 90 degrees field of view
+    assert!(!frustum.intersects(&at(-12.0, 0.0, -10.0)));
+    assert!(!frustum.intersects(&at(12.0, 0.0, -10.0)));
+    assert!(!frustum.intersects(&at(0.0, -12.0, -10.0)));
+    assert!(!frustum.intersects(&at(0.0, 12.0, -10.0)));
+}
+
+#[test]
+fn frustum_follows_the_view() {
+    // Turned to look down +X, which the default frustum does not see
+    let view = Isometry3::look_at_rh(&Point3::origin(), &Point3::new(1.0, 0.0, 0.0), &Vector3::y());
+    let proj = Perspective3::new(1.0, FRAC_PI_2, 1.0, 100.0).to_homogeneous();
+    let frustum = Frustum::from_matrix(&(proj * view.to_homogeneous()));
+    assert!(frustum.intersects(&at(10.0, 0.0, 0.0)));
+    assert!(!frustum.intersects(&at(0.0, 0.0, -10.0)));
+}
+
+#[test]
+fn bounds_merge_the_children() {
+    let own = Some(Some(unit_box()));
+    let children = [child(Some(unit_box()), Vector3::new(4.0, 0.0, 0.0), true)];
+    let expected = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(5.0, 1.0, 1.0));
+    assert_close(&merge_bounds(own, &children).unwrap(), &expected);
+
+    // Group nodes take the bounds of their children alone
+    let expected = Aabb::new(Point3::new(3.0, -1.0, -1.0), Point3::new(5.0, 1.0, 1.0));
+    assert_close(&merge_bounds(None, &children).unwrap(), &expected);
+}
+
+#[test]
+fn unknown_geometry_bounds_make_the_subtree_unbounded() {
+    let children = [child(Some(unit_box()), Vector3::zeros(), true)];
+    assert_eq!(merge_bounds(Some(None), &children), None);
+
+    let children = [
+        child(Some(unit_box()), Vector3::zeros(), true),
+        child(None, Vector3::zeros(), true),
+    ];
+    assert_eq!(merge_bounds(Some(Some(unit_box())), &children), None);
+}
+
+#[test]
+fn empty_children_are_ignored() {
+    let children = [child(None, Vector3::new(100.0, 0.0, 0.0), false)];
+    assert_eq!(merge_bounds(Some(Some(unit_box())), &children), Some(unit_box()));
+    assert_eq!(merge_bounds(None, &children), None);
+    assert_eq!(merge_bounds(None, &[]), None);
+}
\ No newline at end of file
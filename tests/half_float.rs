@@ -0,0 +1,64 @@
+//! Converts floats to half floats, checking the bits of each class of values.
+
+use md::model::f32_to_f16;
+
+#[test]
+fn exact_values() {
+    assert_eq!(f32_to_f16(0.0), 0x0000);
+    assert_eq!(f32_to_f16(-0.0), 0x8000);
+    assert_eq!(f32_to_f16(1.0), 0x3c00);
+    assert_eq!(f32_to_f16(-2.0), 0xc000);
+    assert_eq!(f32_to_f16(0.5), 0x3800);
+    assert_eq!(f32_to_f16(65504.0), 0x7bff);
+    // Smallest normal
+    assert_eq!(f32_to_f16(2f32.powi(-14)), 0x0400);
+}
+
+#[test]
+fn rounding_to_nearest() {
+    let ulp = 2f32.powi(-10);
+    // Below, above and exactly at half a unit in the last place of 1.0
+    assert_eq!(f32_to_f16(1.0 + ulp * 0.25), 0x3c00);
+    assert_eq!(f32_to_f16(1.0 + ulp * 0.75), 0x3c01);
+    assert_eq!(f32_to_f16(1.0 + ulp * 0.5), 0x3c01);
+    assert_eq!(f32_to_f16(-(1.0 + ulp * 0.75)), 0xbc01);
+    // Rounding up the largest mantissa carries into the exponent
+    assert_eq!(f32_to_f16(2.0 - ulp * 0.25), 0x4000);
+}
+
+#[test]
+fn subnormals() {
+    assert_eq!(f32_to_f16(2f32.powi(-15)), 0x0200);
+    // Smallest subnormal
+    assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+    assert_eq!(f32_to_f16(-(2f32.powi(-24))), 0x8001);
+    assert_eq!(f32_to_f16(3.0 * 2f32.powi(-24)), 0x0003);
+    // Rounding the largest subnormal up gives the smallest normal
+    assert_eq!(f32_to_f16(2f32.powi(-14) - 2f32.powi(-26)), 0x0400);
+}
+
+#[test]
+fn underflow_to_zero() {
+    assert_eq!(f32_to_f16(2f32.powi(-26)), 0x0000);
+    assert_eq!(f32_to_f16(-(2f32.powi(-30))), 0x8000);
+    assert_eq!(f32_to_f16(f32::MIN_POSITIVE), 0x0000);
+}
+
+#[test]
+fn overflow_to_infinity() {
+    // Halfway between the largest half float and the next power of two rounds up
+    assert_eq!(f32_to_f16(65520.0), 0x7c00);
+    assert_eq!(f32_to_f16(65519.0), 0x7bff);
+    assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+    assert_eq!(f32_to_f16(-f32::MAX), 0xfc00);
+}
+
+#[test]
+fn infinity_and_nan() {
+    assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+    assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+
+    let nan = f32_to_f16(f32::NAN);
+    assert_eq!(nan & 0x7c00, 0x7c00);
+    assert_ne!(nan & 0x03ff, 0);
+}
\ No newline at end of file
@@ -0,0 +1,522 @@
+//! Decodes JPEG, BMP, TGA, PPM and PGM images written by small encoders,
+//! and detects their formats.
+
+use md::model::{Image, ImageError, ImageFormat};
+
+fn pixel(image: &Image, x: u32, y: u32) -> [u8; 4] {
+    let i = ((y * image.width + x) * 4) as usize;
+    [image.data[i], image.data[i + 1], image.data[i + 2], image.data[i + 3]]
+}
+
+/// Smooth RGBA pattern, which lossy compression keeps close
+fn gradient(x: u32, y: u32) -> [u8; 4] {
+    [(x * 8) as u8, (y * 8) as u8, (255 - x * 4 - y * 4) as u8, (x * 16 + y) as u8]
+}
+
+/// Pattern exercising every bit of the channels
+fn noise(x: u32, y: u32) -> [u8; 4] {
+    let v = x.wrapping_mul(7919).wrapping_add(y.wrapping_mul(104_729));
+    [v as u8, (v >> 3) as u8, (v >> 7) as u8, (v >> 11) as u8]
+}
+
+fn assert_pixels(image: &Image, width: u32, height: u32, expected: impl Fn(u32, u32) -> [u8; 4]) {
+    assert_eq!((image.width, image.height), (width, height));
+    assert_eq!(image.data.len() as u32, width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            assert_eq!(pixel(image, x, y), expected(x, y), "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+mod jpeg {
+    use super::*;
+    use jpeg_encoder::{ColorType, Encoder};
+
+    fn encode(data: &[u8], width: u16, height: u16, color_type: ColorType, progressive: bool) -> Vec<u8> {
+        let mut out = vec![];
+        let mut encoder = Encoder::new(&mut out, 100);
+        encoder.set_progressive(progressive);
+        encoder.encode(data, width, height, color_type).unwrap();
+        out
+    }
+
+    fn assert_close(image: &Image, width: u32, height: u32, expected: impl Fn(u32, u32) -> [u8; 4]) {
+        assert_eq!((image.width, image.height), (width, height));
+        for y in 0..height {
+            for x in 0..width {
+                let actual = pixel(image, x, y);
+                let expected = expected(x, y);
+                for c in 0..4 {
+                    let difference = (actual[c] as i32 - expected[c] as i32).abs();
+                    assert!(difference <= 8, "pixel ({}, {}): {:?} instead of {:?}", x, y, actual, expected);
+                }
+            }
+        }
+    }
+
+    fn rgb(width: u32, height: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| gradient(x, y)[..3].to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn huge_size_is_rejected_before_decoding() {
+        let mut data = encode(&rgb(8, 8), 8, 8, ColorType::Rgb, false);
+        let sof = data.windows(2).position(|marker| marker == [0xff, 0xc0]).unwrap();
+        // Height then width, after the segment length and sample precision
+        data[sof + 5..sof + 9].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        assert!(matches!(Image::from_jpeg(&data), Err(ImageError::Unsupported(_))));
+    }
+
+    #[test]
+    fn baseline_and_progressive_color() {
+        for &progressive in &[false, true] {
+            let data = encode(&rgb(29, 21), 29, 21, ColorType::Rgb, progressive);
+            assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::Jpeg));
+
+            let image = Image::decode(&data).unwrap();
+            assert_close(&image, 29, 21, |x, y| {
+                let [r, g, b, _] = gradient(x, y);
+                [r, g, b, 255]
+            });
+        }
+    }
+
+    #[test]
+    fn grayscale() {
+        for &progressive in &[false, true] {
+            let luma: Vec<u8> = (0..16 * 16).map(|i| (i % 16 * 8 + i / 16 * 4) as u8).collect();
+            let data = encode(&luma, 16, 16, ColorType::Luma, progressive);
+            let image = Image::from_jpeg(&data).unwrap();
+            assert_close(&image, 16, 16, |x, y| {
+                let l = (x * 8 + y * 4) as u8;
+                [l, l, l, 255]
+            });
+        }
+    }
+
+    #[test]
+    fn cmyk() {
+        let cmyk: Vec<u8> = (0..16 * 16).flat_map(|i| [(i % 16 * 8) as u8, 40, (i / 16 * 8) as u8, 20]).collect();
+        let data = encode(&cmyk, 16, 16, ColorType::Cmyk, false);
+        let image = Image::from_jpeg(&data).unwrap();
+        assert_close(&image, 16, 16, |x, y| {
+            let white = 255 - 20;
+            let channel = |ink: u32| ((255 - ink) * white / 255) as u8;
+            [channel(x * 8), channel(40), channel(y * 8), 255]
+        });
+    }
+
+    #[test]
+    fn corrupted() {
+        let data = encode(&rgb(16, 16), 16, 16, ColorType::Rgb, false);
+        assert!(Image::from_jpeg(&data[..data.len() / 3]).is_err());
+        assert!(Image::from_jpeg(&[0xFF, 0xD8, 0xFF]).is_err());
+    }
+}
+
+mod bmp {
+    use super::*;
+
+    /// Writes a BMP with an info header of the specified size, followed by extra data
+    /// such as bit masks or a palette, then the rows
+    fn write(header_size: u32, width: i32, height: i32, bit_count: u16, compression: u32, extra: &[u8], rows: &[u8]) -> Vec<u8> {
+        let offset = 14 + header_size + extra.len() as u32;
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&(offset + rows.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&offset.to_le_bytes());
+
+        let mut header = vec![];
+        header.extend_from_slice(&header_size.to_le_bytes());
+        if header_size == 12 {
+            header.extend_from_slice(&(width as u16).to_le_bytes());
+            header.extend_from_slice(&(height as u16).to_le_bytes());
+            header.extend_from_slice(&1u16.to_le_bytes());
+            header.extend_from_slice(&bit_count.to_le_bytes());
+        } else {
+            header.extend_from_slice(&width.to_le_bytes());
+            header.extend_from_slice(&height.to_le_bytes());
+            header.extend_from_slice(&1u16.to_le_bytes());
+            header.extend_from_slice(&bit_count.to_le_bytes());
+            header.extend_from_slice(&compression.to_le_bytes());
+            header.extend_from_slice(&(rows.len() as u32).to_le_bytes());
+            header.extend_from_slice(&[0; 16]);
+            header.resize(header_size as usize, 0);
+        }
+        data.extend_from_slice(&header);
+        data.extend_from_slice(extra);
+        data.extend_from_slice(rows);
+        data
+    }
+
+    /// Packs rows bottom-up, padded to 4 bytes
+    fn rows(width: u32, height: u32, pixel: impl Fn(u32, u32) -> Vec<u8>, bits: u32) -> Vec<u8> {
+        let row_size = ((bits * width).div_ceil(32) * 4) as usize;
+        let mut data = vec![];
+        for y in (0..height).rev() {
+            let mut row = vec![];
+            if bits < 8 {
+                let mut byte = 0u8;
+                for x in 0..width {
+                    let shift = 8 - bits - (x * bits) % 8;
+                    byte |= pixel(x, y)[0] << shift;
+                    if shift == 0 || x == width - 1 {
+                        row.push(byte);
+                        byte = 0;
+                    }
+                }
+            } else {
+                (0..width).for_each(|x| row.extend(pixel(x, y)));
+            }
+            row.resize(row_size, 0);
+            data.extend(row);
+        }
+        data
+    }
+
+    fn palette(count: u32) -> Vec<u8> {
+        (0..count).flat_map(|i| [(i * 3) as u8, (i * 5) as u8, (i * 7) as u8, 0]).collect()
+    }
+
+    fn palette_color(i: u32) -> [u8; 4] {
+        [(i * 7) as u8, (i * 5) as u8, (i * 3) as u8, 255]
+    }
+
+    #[test]
+    fn bgr_24_bits() {
+        let pixels = rows(13, 7, |x, y| { let [r, g, b, _] = noise(x, y); vec![b, g, r] }, 24);
+        let data = write(40, 13, 7, 24, 0, &[], &pixels);
+        assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::Bmp));
+        assert_pixels(&Image::decode(&data).unwrap(), 13, 7, |x, y| {
+            let [r, g, b, _] = noise(x, y);
+            [r, g, b, 255]
+        });
+    }
+
+    #[test]
+    fn top_down() {
+        let mut pixels = rows(5, 6, |x, y| { let [r, g, b, _] = noise(x, y); vec![b, g, r] }, 24);
+        // Same rows, stored in the opposite order
+        let row_size = 16;
+        let reversed: Vec<u8> = pixels.chunks(row_size).rev().flatten().copied().collect();
+        pixels.copy_from_slice(&reversed);
+        let data = write(40, 5, -6, 24, 0, &[], &pixels);
+        assert_pixels(&Image::from_bmp(&data).unwrap(), 5, 6, |x, y| {
+            let [r, g, b, _] = noise(x, y);
+            [r, g, b, 255]
+        });
+    }
+
+    #[test]
+    fn bgrx_32_bits_is_opaque() {
+        let pixels = rows(4, 4, |x, y| { let [r, g, b, _] = noise(x, y); vec![b, g, r, 0] }, 32);
+        let data = write(40, 4, 4, 32, 0, &[], &pixels);
+        assert_pixels(&Image::from_bmp(&data).unwrap(), 4, 4, |x, y| {
+            let [r, g, b, _] = noise(x, y);
+            [r, g, b, 255]
+        });
+    }
+
+    #[test]
+    fn alpha_bit_fields_v5() {
+        let pixels = rows(6, 3, |x, y| noise(x, y).to_vec(), 32);
+        let mut header_masks = vec![];
+        for mask in &[0x0000_00FFu32, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000] {
+            header_masks.extend_from_slice(&mask.to_le_bytes());
+        }
+        // Masks are part of the 124 bytes header, right after the 40 first bytes
+        let mut data = write(124, 6, 3, 32, 3, &[], &pixels);
+        data[14 + 40..14 + 56].copy_from_slice(&header_masks);
+        assert_pixels(&Image::from_bmp(&data).unwrap(), 6, 3, noise);
+    }
+
+    #[test]
+    fn rgb565_bit_fields() {
+        let mut masks = vec![];
+        for mask in &[0xF800u32, 0x07E0, 0x001F] {
+            masks.extend_from_slice(&mask.to_le_bytes());
+        }
+        let pixels = rows(7, 2, |x, y| ((x * 4 + y) << 11 | x << 5 | y).to_le_bytes()[..2].to_vec(), 16);
+        let data = write(40, 7, 2, 16, 3, &masks, &pixels);
+        let scale = |value: u32, max: u32| ((value * 255 + max / 2) / max) as u8;
+        assert_pixels(&Image::from_bmp(&data).unwrap(), 7, 2, |x, y| {
+            [scale(x * 4 + y, 31), scale(x, 63), scale(y, 31), 255]
+        });
+    }
+
+    #[test]
+    fn rgb555_16_bits() {
+        let pixels = rows(3, 3, |x, y| ((x * 9) << 10 | (y * 9) << 5 | 31).to_le_bytes()[..2].to_vec(), 16);
+        let data = write(40, 3, 3, 16, 0, &[], &pixels);
+        let scale = |value: u32| ((value * 255 + 15) / 31) as u8;
+        assert_pixels(&Image::from_bmp(&data).unwrap(), 3, 3, |x, y| [scale(x * 9), scale(y * 9), 255, 255]);
+    }
+
+    #[test]
+    fn palettes() {
+        for &bits in &[1u32, 2, 4, 8] {
+            let count = 1 << bits;
+            let index = move |x: u32, y: u32| (x + y * 3) % count;
+            let pixels = rows(11, 5, |x, y| vec![index(x, y) as u8], bits);
+            let data = write(40, 11, 5, bits as u16, 0, &palette(count), &pixels);
+            assert_pixels(&Image::from_bmp(&data).unwrap(), 11, 5, |x, y| palette_color(index(x, y)));
+        }
+    }
+
+    #[test]
+    fn os2_core_header() {
+        let palette: Vec<u8> = (0..256u32).flat_map(|i| [(i * 3) as u8, (i * 5) as u8, (i * 7) as u8]).collect();
+        let pixels = rows(4, 4, |x, y| vec![(x * 4 + y) as u8], 8);
+        let data = write(12, 4, 4, 8, 0, &palette, &pixels);
+        assert_pixels(&Image::from_bmp(&data).unwrap(), 4, 4, |x, y| palette_color(x * 4 + y));
+    }
+
+    #[test]
+    fn rle8() {
+        #[rustfmt::skip]
+        let encoded = [
+            // Bottom row: run of 3 times index 1, literal of 3 indices padded to 16 bits
+            3, 1, 0, 3, 2, 3, 4, 0,
+            // End of line
+            0, 0,
+            // Skip 2 pixels right and 1 row up
+            0, 2, 2, 1,
+            // Run of 2 times index 5, then end of bitmap
+            2, 5, 0, 1,
+        ];
+        let data = write(40, 6, 3, 8, 1, &palette(256), &encoded);
+        let image = Image::from_bmp(&data).unwrap();
+        let transparent = [0, 0, 0, 0];
+        let expected = |x: u32, y: u32| match (x, y) {
+            (0..=2, 2) => palette_color(1),
+            (3, 2) => palette_color(2),
+            (4, 2) => palette_color(3),
+            (5, 2) => palette_color(4),
+            (2..=3, 0) => palette_color(5),
+            _ => transparent,
+        };
+        assert_pixels(&image, 6, 3, expected);
+    }
+
+    #[test]
+    fn rle4() {
+        // Run of 5 alternating 1 and 2, literal of 3 indices 3, 4, 5, end of bitmap
+        let encoded = [5, 0x12, 0, 3, 0x34, 0x50, 0, 1];
+        let data = write(40, 8, 1, 4, 2, &palette(16), &encoded);
+        let image = Image::from_bmp(&data).unwrap();
+        let indices = [1, 2, 1, 2, 1, 3, 4, 5];
+        assert_pixels(&image, 8, 1, |x, _| palette_color(indices[x as usize]));
+    }
+
+    #[test]
+    fn corrupted() {
+        let pixels = rows(8, 8, |x, y| noise(x, y)[..3].to_vec(), 24);
+        let data = write(40, 8, 8, 24, 0, &[], &pixels);
+        assert!(matches!(Image::from_bmp(&data[..data.len() - 10]), Err(ImageError::InvalidData(_))));
+        assert!(Image::from_bmp(&data[..20]).is_err());
+        assert!(Image::from_bmp(b"BM").is_err());
+
+        // A huge size declared by a small file
+        let data = write(40, 1 << 30, 1 << 30, 24, 0, &[], &[]);
+        assert!(Image::from_bmp(&data).is_err());
+
+        let unterminated = write(40, 4, 4, 8, 1, &palette(256), &[4, 1]);
+        assert!(Image::from_bmp(&unterminated).is_err());
+    }
+}
+
+mod tga {
+    use super::*;
+
+    #[allow(clippy::too_many_arguments)]
+    fn write(image_type: u8, color_map: Option<(u16, u8, &[u8])>, width: u16, height: u16, depth: u8, descriptor: u8, pixels: &[u8]) -> Vec<u8> {
+        let id = b"id";
+        let mut data = vec![id.len() as u8, color_map.is_some() as u8, image_type];
+        let (first, entry_depth, entries) = color_map.unwrap_or((0, 0, &[]));
+        let entry_count = if entry_depth > 0 { entries.len() / (entry_depth as usize).div_ceil(8) } else { 0 };
+        data.extend_from_slice(&first.to_le_bytes());
+        data.extend_from_slice(&(entry_count as u16).to_le_bytes());
+        data.push(entry_depth);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.push(depth);
+        data.push(descriptor);
+        data.extend_from_slice(id);
+        data.extend_from_slice(entries);
+        data.extend_from_slice(pixels);
+        data
+    }
+
+    /// Pixels in file order, rows going up from the bottom unless `top_down`
+    fn pixels(width: u32, height: u32, top_down: bool, pixel: impl Fn(u32, u32) -> Vec<u8>) -> Vec<u8> {
+        let rows: Vec<u32> = if top_down { (0..height).collect() } else { (0..height).rev().collect() };
+        rows.into_iter().flat_map(|y| (0..width).flat_map(|x| pixel(x, y)).collect::<Vec<u8>>()).collect()
+    }
+
+    fn bgra(x: u32, y: u32) -> Vec<u8> {
+        let [r, g, b, a] = noise(x, y);
+        vec![b, g, r, a]
+    }
+
+    #[test]
+    fn true_color_bottom_left() {
+        let data = write(2, None, 9, 5, 24, 0, &pixels(9, 5, false, |x, y| bgra(x, y)[..3].to_vec()));
+        assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::Tga));
+        assert_pixels(&Image::decode(&data).unwrap(), 9, 5, |x, y| {
+            let [r, g, b, _] = noise(x, y);
+            [r, g, b, 255]
+        });
+    }
+
+    #[test]
+    fn true_color_alpha_top_left() {
+        let data = write(2, None, 6, 4, 32, 0x28, &pixels(6, 4, true, bgra));
+        assert_pixels(&Image::from_tga(&data).unwrap(), 6, 4, noise);
+    }
+
+    #[test]
+    fn right_to_left() {
+        let data = write(2, None, 3, 2, 32, 0x38, &pixels(3, 2, true, |x, y| bgra(2 - x, y)));
+        assert_pixels(&Image::from_tga(&data).unwrap(), 3, 2, noise);
+    }
+
+    #[test]
+    fn sixteen_bits_with_alpha_bit() {
+        let value = |x: u32, y: u32| ((x & 1) << 15 | (x * 3) << 10 | (y * 5) << 5 | 31) as u16;
+        let data = write(2, None, 4, 4, 16, 0x21, &pixels(4, 4, true, |x, y| value(x, y).to_le_bytes().to_vec()));
+        let expand = |v: u32| ((v << 3) | (v >> 2)) as u8;
+        assert_pixels(&Image::from_tga(&data).unwrap(), 4, 4, |x, y| {
+            [expand(x * 3), expand(y * 5), 255, if x & 1 == 1 { 255 } else { 0 }]
+        });
+    }
+
+    #[test]
+    fn grayscale() {
+        let data = write(3, None, 5, 5, 8, 0x20, &pixels(5, 5, true, |x, y| vec![noise(x, y)[0]]));
+        assert_pixels(&Image::from_tga(&data).unwrap(), 5, 5, |x, y| {
+            let l = noise(x, y)[0];
+            [l, l, l, 255]
+        });
+    }
+
+    #[test]
+    fn color_mapped() {
+        let entries: Vec<u8> = (0..16u32).flat_map(|i| [(i * 3) as u8, (i * 5) as u8, (i * 7) as u8]).collect();
+        // Indices start at the first entry of the map
+        let data = write(1, Some((10, 24, &entries)), 4, 4, 8, 0x20, &pixels(4, 4, true, |x, y| vec![(10 + x + y * 4) as u8]));
+        assert_pixels(&Image::from_tga(&data).unwrap(), 4, 4, |x, y| {
+            let i = x + y * 4;
+            [(i * 7) as u8, (i * 5) as u8, (i * 3) as u8, 255]
+        });
+
+        let out_of_range = write(1, Some((10, 24, &entries)), 1, 1, 8, 0, &[3]);
+        assert!(Image::from_tga(&out_of_range).is_err());
+    }
+
+    #[test]
+    fn run_length_encoded() {
+        // Top row: run of 3 red pixels then raw green and blue,
+        // bottom row: a run crossing the end of the image
+        let encoded = [
+            0x82, 0, 0, 255, 0x01, 0, 255, 0, 255, 0, 0, //
+            0x87, 9, 9, 9,
+        ];
+        let data = write(10, None, 5, 2, 24, 0x20, &encoded);
+        let image = Image::decode(&data).unwrap();
+        let expected = |x: u32, y: u32| match (x, y) {
+            (0..=2, 0) => [255, 0, 0, 255],
+            (3, 0) => [0, 255, 0, 255],
+            (4, 0) => [0, 0, 255, 255],
+            _ => [9, 9, 9, 255],
+        };
+        assert_pixels(&image, 5, 2, expected);
+    }
+
+    #[test]
+    fn corrupted() {
+        let data = write(2, None, 8, 8, 24, 0, &pixels(8, 8, false, |x, y| bgra(x, y)[..3].to_vec()));
+        assert!(Image::from_tga(&data[..data.len() - 1]).is_err());
+        assert!(Image::from_tga(&data[..10]).is_err());
+        assert!(Image::from_tga(&write(10, None, 8, 8, 24, 0, &[0xFF, 1])).is_err());
+        assert!(Image::from_tga(&write(7, None, 8, 8, 24, 0, &[])).is_err());
+        assert!(Image::from_tga(&write(2, None, 65535, 65535, 32, 0, &[0x80])).is_err());
+    }
+}
+
+mod pnm {
+    use super::*;
+
+    #[test]
+    fn binary_ppm() {
+        let mut data = b"P6\n# comment\n7 3\n255\n".to_vec();
+        for y in 0..3 {
+            for x in 0..7 {
+                data.extend_from_slice(&noise(x, y)[..3]);
+            }
+        }
+        assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::Pnm));
+        assert_pixels(&Image::decode(&data).unwrap(), 7, 3, |x, y| {
+            let [r, g, b, _] = noise(x, y);
+            [r, g, b, 255]
+        });
+    }
+
+    #[test]
+    fn binary_pgm_16_bits() {
+        let mut data = b"P5 4 2 65535 ".to_vec();
+        for i in 0..8u16 {
+            data.extend_from_slice(&(i * 8000).to_be_bytes());
+        }
+        assert_pixels(&Image::from_pnm(&data).unwrap(), 4, 2, |x, y| {
+            let l = (((x + y * 4) * 8000 * 255 + 32767) / 65535) as u8;
+            [l, l, l, 255]
+        });
+    }
+
+    #[test]
+    fn ascii_with_maximum_value() {
+        let data = b"P3\n2 2 # size\n15\n0 0 0  15 15 15\n# row\n15 0 0 0 5 10\n";
+        let scale = |v: u32| ((v * 255 + 7) / 15) as u8;
+        assert_pixels(&Image::decode(data).unwrap(), 2, 2, |x, y| match (x, y) {
+            (0, 0) => [0, 0, 0, 255],
+            (1, 0) => [255, 255, 255, 255],
+            (0, 1) => [255, 0, 0, 255],
+            _ => [0, scale(5), scale(10), 255],
+        });
+
+        let gray = b"P2 3 1 255 0 128 255";
+        assert_pixels(&Image::decode(gray).unwrap(), 3, 1, |x, _| {
+            let l = [0, 128, 255][x as usize];
+            [l, l, l, 255]
+        });
+    }
+
+    #[test]
+    fn corrupted() {
+        assert!(Image::from_pnm(b"P6 2 2 255 abc").is_err());
+        assert!(Image::from_pnm(b"P3 2 1 255 1 2 3 4 5").is_err());
+        assert!(Image::from_pnm(b"P2 1 1 10 11").is_err());
+        assert!(Image::from_pnm(b"P2 1 1 0 0").is_err());
+        assert!(Image::from_pnm(b"P5 x 1 255 0").is_err());
+        assert!(Image::from_pnm(b"P6 60000 60000 255 ").is_err());
+    }
+}
+
+#[test]
+fn unknown_format() {
+    assert_eq!(ImageFormat::detect(b"GIF89a"), None);
+    assert_eq!(ImageFormat::detect(&[]), None);
+    assert!(matches!(Image::decode(b"GIF89a......................."), Err(ImageError::Unsupported(_))));
+    assert!(Image::decode(&[]).is_err());
+}
+
+#[test]
+fn png_is_detected() {
+    let data = include_bytes!("../res/font/spd.png");
+    assert_eq!(ImageFormat::detect(data), Some(ImageFormat::Png));
+    assert!(Image::decode(data).is_ok());
+}
\ No newline at end of file
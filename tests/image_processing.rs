@@ -0,0 +1,241 @@
+//! Flips, crops, resizes and converts images of every pixel format.
+
+use md::model::{Image, PixelFormat};
+
+const FORMATS: [PixelFormat; 5] = [
+    PixelFormat::Rgba,
+    PixelFormat::Rgb,
+    PixelFormat::Luminance,
+    PixelFormat::LuminanceAlpha,
+    PixelFormat::Alpha,
+];
+
+/// Image whose every channel value is unique enough to track where it moved
+fn numbered(width: u32, height: u32, format: PixelFormat) -> Image {
+    let len = (width * height * format.channels()) as usize;
+    let data = (0..len).map(|i| (i * 7 % 251) as u8).collect();
+    Image::from_vec_with_format(data, width, height, format)
+}
+
+fn texel(image: &Image, x: u32, y: u32) -> &[u8] {
+    let channels = image.format.channels() as usize;
+    let i = (y * image.width + x) as usize * channels;
+    &image.data[i..i + channels]
+}
+
+fn uniform(width: u32, height: u32, pixel: &[u8], format: PixelFormat) -> Image {
+    let data = pixel.iter().copied().cycle().take((width * height) as usize * pixel.len()).collect();
+    Image::from_vec_with_format(data, width, height, format)
+}
+
+#[test]
+fn flip_vertical() {
+    for &format in &FORMATS {
+        for &(width, height) in &[(3, 4), (5, 5), (1, 1), (4, 1)] {
+            let original = numbered(width, height, format);
+            let mut flipped = original.clone();
+            flipped.flip_vertical();
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(texel(&flipped, x, y), texel(&original, x, height - 1 - y));
+                }
+            }
+            flipped.flip_vertical();
+            assert_eq!(flipped, original);
+        }
+    }
+}
+
+#[test]
+fn flip_horizontal() {
+    for &format in &FORMATS {
+        for &(width, height) in &[(3, 4), (4, 3), (1, 2)] {
+            let original = numbered(width, height, format);
+            let mut flipped = original.clone();
+            flipped.flip_horizontal();
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(texel(&flipped, x, y), texel(&original, width - 1 - x, y));
+                }
+            }
+            flipped.flip_horizontal();
+            assert_eq!(flipped, original);
+        }
+    }
+}
+
+#[test]
+fn crop() {
+    for &format in &FORMATS {
+        let image = numbered(6, 5, format);
+        let cropped = image.crop(2, 1, 3, 4).unwrap();
+        assert_eq!((cropped.width, cropped.height, cropped.format), (3, 4, format));
+        for y in 0..4 {
+            for x in 0..3 {
+                assert_eq!(texel(&cropped, x, y), texel(&image, x + 2, y + 1));
+            }
+        }
+
+        assert_eq!(image.crop(0, 0, 6, 5).unwrap(), image);
+        assert!(image.crop(4, 0, 3, 1).is_none());
+        assert!(image.crop(0, 5, 1, 1).is_none());
+        assert!(image.crop(u32::MAX, 0, 2, 1).is_none());
+    }
+}
+
+#[test]
+fn resize_keeps_uniform_colors() {
+    let pixel = [10, 200, 30, 128];
+    let image = uniform(7, 5, &pixel, PixelFormat::Rgba);
+    for &(width, height) in &[(16, 8), (3, 2), (1, 1), (7, 5), (20, 1)] {
+        for resized in &[image.resize_bilinear(width, height), image.resize_box(width, height)] {
+            assert_eq!((resized.width, resized.height), (width, height));
+            assert_eq!(resized, &uniform(width, height, &pixel, PixelFormat::Rgba));
+        }
+    }
+}
+
+#[test]
+fn resize_to_same_size_is_identity() {
+    for &format in &FORMATS {
+        let image = numbered(5, 3, format);
+        assert_eq!(image.resize_bilinear(5, 3), image);
+        assert_eq!(image.resize_box(5, 3), image);
+    }
+}
+
+#[test]
+fn box_filter_averages_blocks() {
+    // Each 2x2 block averages to its top-left value plus one and a half
+    let data = vec![
+        0, 1, 100, 101, //
+        2, 3, 102, 103, //
+        50, 51, 200, 201, //
+        52, 53, 202, 203,
+    ];
+    let image = Image::from_vec_with_format(data, 4, 4, PixelFormat::Luminance);
+    let half = image.resize_box(2, 2);
+    assert_eq!(half.data, vec![2, 102, 52, 202]);
+
+    // Non integer ratios weigh partially covered texels
+    let row = Image::from_vec_with_format(vec![0, 90, 180], 3, 1, PixelFormat::Luminance);
+    assert_eq!(row.resize_box(2, 1).data, vec![30, 150]);
+}
+
+#[test]
+fn bilinear_interpolates_between_centers() {
+    let row = Image::from_vec_with_format(vec![0, 100], 2, 1, PixelFormat::Luminance);
+    // Destination centers at 0.25 and 0.75 of the source texels, clamped at the edges
+    assert_eq!(row.resize_bilinear(4, 1).data, vec![0, 25, 75, 100]);
+    assert_eq!(row.resize_bilinear(1, 1).data, vec![50]);
+}
+
+#[test]
+fn power_of_two() {
+    let image = numbered(5, 3, PixelFormat::Rgb);
+    let pot = image.to_power_of_two();
+    assert_eq!((pot.width, pot.height, pot.format), (8, 4, PixelFormat::Rgb));
+
+    let already = numbered(16, 2, PixelFormat::Rgb);
+    assert_eq!(already.to_power_of_two(), already);
+}
+
+#[test]
+fn mipmaps() {
+    let image = uniform(16, 4, &[40, 80], PixelFormat::LuminanceAlpha);
+    let sizes: Vec<(u32, u32)> = image.mipmaps().iter().map(|level| (level.width, level.height)).collect();
+    assert_eq!(sizes, vec![(8, 2), (4, 1), (2, 1), (1, 1)]);
+    for level in image.mipmaps() {
+        assert_eq!(level, uniform(level.width, level.height, &[40, 80], PixelFormat::LuminanceAlpha));
+    }
+
+    // Non power of two sizes round down, the last level averaging the whole image
+    let image = numbered(5, 3, PixelFormat::Luminance);
+    let levels = image.mipmaps();
+    assert_eq!(levels.iter().map(|level| (level.width, level.height)).collect::<Vec<_>>(), vec![(2, 1), (1, 1)]);
+    let average = image.data.iter().map(|&v| v as f32).sum::<f32>() / 15.0;
+    assert!((levels[1].data[0] as f32 - average).abs() <= 1.0);
+
+    assert!(uniform(1, 1, &[0], PixelFormat::Alpha).mipmaps().is_empty());
+}
+
+#[test]
+fn premultiply_alpha() {
+    let mut image = Image::from_vec_with_format(
+        vec![255, 128, 0, 128, 200, 100, 50, 0, 10, 20, 30, 255],
+        3,
+        1,
+        PixelFormat::Rgba,
+    );
+    image.premultiply_alpha();
+    assert_eq!(image.data, vec![128, 64, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]);
+
+    image.unpremultiply_alpha();
+    assert_eq!(image.data, vec![255, 128, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]);
+
+    let mut gray = Image::from_vec_with_format(vec![100, 51], 1, 1, PixelFormat::LuminanceAlpha);
+    gray.premultiply_alpha();
+    assert_eq!(gray.data, vec![20, 51]);
+
+    // Nothing to do without both color and alpha
+    for &format in &[PixelFormat::Rgb, PixelFormat::Luminance, PixelFormat::Alpha] {
+        let mut image = numbered(3, 3, format);
+        let original = image.clone();
+        image.premultiply_alpha();
+        image.unpremultiply_alpha();
+        assert_eq!(image, original);
+    }
+}
+
+#[test]
+fn premultiply_round_trip_of_opaque_pixels() {
+    let mut image = numbered(8, 8, PixelFormat::Rgba);
+    for pixel in image.data.chunks_mut(4) {
+        pixel[3] = 255;
+    }
+    let original = image.clone();
+    image.premultiply_alpha();
+    assert_eq!(image, original);
+    image.unpremultiply_alpha();
+    assert_eq!(image, original);
+}
+
+#[test]
+fn srgb_linear_conversion() {
+    let mut image = Image::from_vec_with_format(vec![0, 188, 255, 188], 1, 1, PixelFormat::Rgba);
+    image.srgb_to_linear();
+    // Middle gray of sRGB is about half the linear intensity, alpha is untouched
+    assert_eq!(image.data, vec![0, 128, 255, 188]);
+    image.linear_to_srgb();
+    assert_eq!(image.data, vec![0, 188, 255, 188]);
+
+    // sRGB is more precise than linear values in dark tones, so going through it only rounds
+    let mut ramp = Image::from_vec_with_format((0..=255).collect(), 256, 1, PixelFormat::Luminance);
+    ramp.linear_to_srgb();
+    ramp.srgb_to_linear();
+    for (i, &value) in ramp.data.iter().enumerate() {
+        assert!((value as i32 - i as i32).abs() <= 1, "{} became {}", i, value);
+    }
+
+    let mut alpha = numbered(4, 4, PixelFormat::Alpha);
+    let original = alpha.clone();
+    alpha.srgb_to_linear();
+    assert_eq!(alpha, original);
+}
+
+#[test]
+fn empty_images_stay_empty() {
+    for &(width, height) in &[(0, 0), (0, 5), (3, 0)] {
+        let image = Image::from_vec_with_format(vec![], width, height, PixelFormat::Rgba);
+        assert!(image.is_empty());
+        assert_eq!(image.resize_bilinear(4, 4), image);
+        assert_eq!(image.resize_box(4, 4), image);
+        assert_eq!(image.to_power_of_two(), image);
+        assert!(image.mipmaps().is_empty());
+
+        let mut flipped = image.clone();
+        flipped.flip_vertical();
+        flipped.flip_horizontal();
+        assert_eq!(flipped, image);
+    }
+}
\ No newline at end of file
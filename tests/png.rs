@@ -0,0 +1,466 @@
+//! Decodes PNG images covering the cases of the PNG suite: every color type and bit depth,
+//! palettes, transparency chunks, gamma chunks and Adam7 interlacing.
+//! Most images are encoded by a minimal writer leaving rows unfiltered, so each test
+//! knows the exact pixels to expect. The files in `tests/data` were written by a separate
+//! encoder using every filter type, and are checked against pixels written out by hand.
+
+use md::model::{Image, ImageError};
+
+const GRAYSCALE: u8 = 0;
+const RGB: u8 = 2;
+const INDEXED: u8 = 3;
+const GRAYSCALE_ALPHA: u8 = 4;
+const RGBA: u8 = 6;
+
+/// Wraps data into a zlib stream
+fn zlib(data: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, 6)
+}
+
+fn push_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32fast::hash(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn channels(color_type: u8) -> usize {
+    match color_type {
+        GRAYSCALE | INDEXED => 1,
+        GRAYSCALE_ALPHA => 2,
+        RGB => 3,
+        _ => 4,
+    }
+}
+
+/// Packs rows of samples with the filter byte of each row set to none
+fn pack_rows(samples: &[Vec<u16>], width: usize, channels: usize, depth: u8, out: &mut Vec<u8>) {
+    for row in samples {
+        out.push(0);
+        match depth {
+            16 => row.iter().for_each(|s| out.extend_from_slice(&s.to_be_bytes())),
+            8 => row.iter().for_each(|&s| out.push(s as u8)),
+            _ => {
+                let mut byte = 0u8;
+                let mut bits = 0;
+                for &sample in &row[..width * channels] {
+                    byte |= (sample as u8) << (8 - depth - bits);
+                    bits += depth;
+                    if bits == 8 {
+                        out.push(byte);
+                        byte = 0;
+                        bits = 0;
+                    }
+                }
+                if bits > 0 {
+                    out.push(byte);
+                }
+            }
+        }
+    }
+}
+
+struct Png {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: u8,
+    interlaced: bool,
+    /// Extra chunks written between the header and the image data
+    chunks: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl Png {
+    fn new(width: usize, height: usize, depth: u8, color_type: u8) -> Self {
+        Self {
+            width,
+            height,
+            depth,
+            color_type,
+            interlaced: false,
+            chunks: vec![],
+        }
+    }
+
+    fn interlaced(self) -> Self {
+        Self { interlaced: true, ..self }
+    }
+
+    fn chunk(mut self, chunk_type: &[u8; 4], data: Vec<u8>) -> Self {
+        self.chunks.push((*chunk_type, data));
+        self
+    }
+
+    /// Sample of a channel of a pixel, within the range of the bit depth
+    fn sample(&self, x: usize, y: usize, channel: usize) -> u16 {
+        let max = (1u32 << self.depth) - 1;
+        ((x * 7919 + y * 104_729 + channel * 31337) as u32 % (max + 1)) as u16
+    }
+
+    fn row(&self, y: usize, xs: impl Iterator<Item = usize>) -> Vec<u16> {
+        let channels = channels(self.color_type);
+        xs.flat_map(|x| (0..channels).map(move |c| (x, c)))
+            .map(|(x, c)| self.sample(x, y, c))
+            .collect()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let channels = channels(self.color_type);
+        let mut raw = vec![];
+        if self.interlaced {
+            let passes = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+            for &(x0, y0, dx, dy) in &passes {
+                let xs: Vec<usize> = (x0..self.width).step_by(dx).collect();
+                if xs.is_empty() {
+                    continue;
+                }
+                let rows: Vec<Vec<u16>> = (y0..self.height)
+                    .step_by(dy)
+                    .map(|y| self.row(y, xs.iter().copied()))
+                    .collect();
+                pack_rows(&rows, xs.len(), channels, self.depth, &mut raw);
+            }
+        } else {
+            let rows: Vec<Vec<u16>> = (0..self.height).map(|y| self.row(y, 0..self.width)).collect();
+            pack_rows(&rows, self.width, channels, self.depth, &mut raw);
+        }
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+        let mut header = vec![];
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        header.extend_from_slice(&[self.depth, self.color_type, 0, 0, self.interlaced as u8]);
+        push_chunk(&mut png, b"IHDR", &header);
+        for (chunk_type, data) in &self.chunks {
+            push_chunk(&mut png, chunk_type, data);
+        }
+        push_chunk(&mut png, b"IDAT", &zlib(&raw));
+        push_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Sample scaled to 8 bits as the decoder expands it
+    fn sample8(&self, x: usize, y: usize, channel: usize) -> u8 {
+        let sample = self.sample(x, y, channel);
+        match self.depth {
+            16 => (sample >> 8) as u8,
+            8 => sample as u8,
+            depth => (sample * (255 / ((1 << depth) - 1))) as u8,
+        }
+    }
+
+    /// Expected RGBA pixel of images which are neither indexed nor with a tRNS chunk
+    fn expected(&self, x: usize, y: usize) -> [u8; 4] {
+        let s = |c| self.sample8(x, y, c);
+        match self.color_type {
+            GRAYSCALE => [s(0), s(0), s(0), 255],
+            GRAYSCALE_ALPHA => [s(0), s(0), s(0), s(1)],
+            RGB => [s(0), s(1), s(2), 255],
+            _ => [s(0), s(1), s(2), s(3)],
+        }
+    }
+}
+
+fn pixel(image: &Image, x: usize, y: usize) -> [u8; 4] {
+    let i = (y * image.width as usize + x) * 4;
+    [image.data[i], image.data[i + 1], image.data[i + 2], image.data[i + 3]]
+}
+
+fn assert_decodes(png: &Png) {
+    let image = Image::from_png(&png.encode()).unwrap();
+    assert_eq!((image.width as usize, image.height as usize), (png.width, png.height));
+    assert_eq!(image.data.len(), png.width * png.height * 4);
+    for y in 0..png.height {
+        for x in 0..png.width {
+            assert_eq!(
+                pixel(&image, x, y),
+                png.expected(x, y),
+                "pixel ({}, {}) of {} bit color type {} interlaced {}",
+                x, y, png.depth, png.color_type, png.interlaced
+            );
+        }
+    }
+}
+
+/// Color types with the bit depths the specification allows for them
+const DIRECT_FORMATS: [(u8, &[u8]); 4] = [
+    (GRAYSCALE, &[1, 2, 4, 8, 16]),
+    (GRAYSCALE_ALPHA, &[8, 16]),
+    (RGB, &[8, 16]),
+    (RGBA, &[8, 16]),
+];
+
+#[test]
+fn basn_direct_color() {
+    for &(color_type, depths) in &DIRECT_FORMATS {
+        for &depth in depths {
+            assert_decodes(&Png::new(32, 32, depth, color_type));
+        }
+    }
+}
+
+#[test]
+fn basi_direct_color() {
+    for &(color_type, depths) in &DIRECT_FORMATS {
+        for &depth in depths {
+            assert_decodes(&Png::new(32, 32, depth, color_type).interlaced());
+        }
+    }
+}
+
+#[test]
+fn s_odd_sizes_interlaced() {
+    // Sizes not multiple of 8 leave some Adam7 passes partial or empty
+    for &(width, height) in &[(1, 1), (2, 2), (3, 5), (7, 1), (9, 9), (37, 29)] {
+        assert_decodes(&Png::new(width, height, 8, RGBA).interlaced());
+        assert_decodes(&Png::new(width, height, 1, GRAYSCALE).interlaced());
+    }
+}
+
+fn palette(count: usize) -> Vec<u8> {
+    (0..count)
+        .flat_map(|i| [(i * 3) as u8, (i * 5 + 1) as u8, (i * 7 + 2) as u8])
+        .collect()
+}
+
+#[test]
+fn basn_basi_palette() {
+    for &depth in &[1, 2, 4, 8] {
+        for &interlaced in &[false, true] {
+            let count = 1 << depth;
+            let mut png = Png::new(32, 32, depth, INDEXED).chunk(b"PLTE", palette(count));
+            png.interlaced = interlaced;
+            let image = Image::from_png(&png.encode()).unwrap();
+
+            for y in 0..32 {
+                for x in 0..32 {
+                    let i = png.sample(x, y, 0) as usize;
+                    let expected = [(i * 3) as u8, (i * 5 + 1) as u8, (i * 7 + 2) as u8, 255];
+                    assert_eq!(pixel(&image, x, y), expected, "{} bit palette", depth);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn tbbn_palette_transparency() {
+    // Entries without an alpha in the tRNS chunk are opaque
+    let png = Png::new(16, 16, 4, INDEXED)
+        .chunk(b"PLTE", palette(16))
+        .chunk(b"tRNS", vec![0, 128, 255]);
+    let image = Image::from_png(&png.encode()).unwrap();
+
+    for y in 0..16 {
+        for x in 0..16 {
+            let expected = match png.sample(x, y, 0) {
+                0 => 0,
+                1 => 128,
+                _ => 255,
+            };
+            assert_eq!(pixel(&image, x, y)[3], expected);
+        }
+    }
+}
+
+#[test]
+fn tbrn_color_transparency() {
+    for &depth in &[8, 16] {
+        let png = Png::new(16, 16, depth, RGB);
+        let key: Vec<u16> = (0..3).map(|c| png.sample(5, 3, c)).collect();
+        let trns = key.iter().flat_map(|s| s.to_be_bytes()).collect();
+        let png = png.chunk(b"tRNS", trns);
+        let image = Image::from_png(&png.encode()).unwrap();
+
+        for y in 0..16 {
+            for x in 0..16 {
+                let is_key = (0..3).all(|c| png.sample(x, y, c) == key[c]);
+                let mut expected = png.expected(x, y);
+                expected[3] = if is_key { 0 } else { 255 };
+                assert_eq!(pixel(&image, x, y), expected, "{} bit", depth);
+            }
+        }
+    }
+}
+
+#[test]
+fn tbgn_gray_transparency() {
+    for &depth in &[2, 8, 16] {
+        let png = Png::new(16, 16, depth, GRAYSCALE);
+        let key = png.sample(1, 0, 0);
+        let png = png.chunk(b"tRNS", key.to_be_bytes().to_vec());
+        let image = Image::from_png(&png.encode()).unwrap();
+
+        for y in 0..16 {
+            for x in 0..16 {
+                let alpha = if png.sample(x, y, 0) == key { 0 } else { 255 };
+                assert_eq!(pixel(&image, x, y)[3], alpha, "{} bit", depth);
+                assert_eq!(pixel(&image, x, y)[0], png.sample8(x, y, 0), "{} bit", depth);
+            }
+        }
+    }
+}
+
+fn gamma_chunk(gamma: f32) -> Vec<u8> {
+    ((gamma * 100_000.0).round() as u32).to_be_bytes().to_vec()
+}
+
+#[test]
+fn g_gamma_is_converted_to_srgb() {
+    let png = Png::new(8, 8, 8, RGBA).chunk(b"gAMA", gamma_chunk(1.0));
+    let image = Image::from_png(&png.encode()).unwrap();
+
+    for y in 0..8 {
+        for x in 0..8 {
+            let expected = png.expected(x, y);
+            let actual = pixel(&image, x, y);
+            for c in 0..3 {
+                let converted = ((expected[c] as f32 / 255.0).powf(1.0 / 2.2) * 255.0).round() as u8;
+                assert_eq!(actual[c], converted);
+            }
+            // Alpha is always linear
+            assert_eq!(actual[3], expected[3]);
+        }
+    }
+}
+
+#[test]
+fn g_srgb_gamma_is_left_unchanged() {
+    assert_decodes(&Png::new(8, 8, 8, RGB).chunk(b"gAMA", gamma_chunk(1.0 / 2.2)));
+    assert_decodes(&Png::new(8, 8, 16, GRAYSCALE).chunk(b"gAMA", gamma_chunk(0.45455)));
+}
+
+#[test]
+fn srgb_chunk_takes_precedence_over_gamma() {
+    assert_decodes(
+        &Png::new(8, 8, 8, RGB)
+            .chunk(b"sRGB", vec![0])
+            .chunk(b"gAMA", gamma_chunk(1.0)),
+    );
+}
+
+#[test]
+fn embedded_font_decodes() {
+    let image = Image::from_png(include_bytes!("../res/font/spd.png")).unwrap();
+    assert_eq!(image.data.len() as u32, image.width * image.height * 4);
+}
+
+#[test]
+fn x_corrupted_data_is_an_error() {
+    let png = Png::new(8, 8, 8, RGBA).encode();
+
+    assert!(matches!(Image::from_png(&[]), Err(ImageError::InvalidData(_))));
+    assert!(matches!(Image::from_png(b"not a png"), Err(ImageError::InvalidData(_))));
+    // Truncated image data
+    assert!(Image::from_png(&png[..png.len() / 2]).is_err());
+
+    // Wrong signature
+    let mut bad_signature = png.clone();
+    bad_signature[1] = b'X';
+    assert!(Image::from_png(&bad_signature).is_err());
+
+    // Wrong checksum of the header
+    let mut bad_crc = png.clone();
+    bad_crc[29] ^= 0xFF;
+    assert!(Image::from_png(&bad_crc).is_err());
+
+    // Palette image without a palette
+    let no_palette = Png::new(8, 8, 8, INDEXED).encode();
+    assert!(Image::from_png(&no_palette).is_err());
+}
+
+#[test]
+fn encoded_images_decode_to_the_same_pixels() {
+    use md::model::PixelFormat;
+
+    for &format in &[
+        PixelFormat::Rgba,
+        PixelFormat::Rgb,
+        PixelFormat::Luminance,
+        PixelFormat::LuminanceAlpha,
+        PixelFormat::Alpha,
+    ] {
+        let len = (13 * 7 * format.channels()) as usize;
+        let data = (0..len).map(|i| (i * 31 % 256) as u8).collect();
+        let image = Image::from_vec_with_format(data, 13, 7, format);
+
+        let png = image.to_png().unwrap();
+        assert_eq!(Image::from_png(&png).unwrap(), image.to_rgba());
+    }
+}
+
+fn decode_file(name: &str) -> Image {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name);
+    Image::from_png(&std::fs::read(path).unwrap()).unwrap()
+}
+
+fn assert_pixels(image: &Image, width: u32, expected: &[[u8; 4]]) {
+    assert_eq!(image.width, width);
+    assert_eq!(image.height as usize, expected.len() / width as usize);
+    for (i, expected) in expected.iter().enumerate() {
+        let (x, y) = (i % width as usize, i / width as usize);
+        assert_eq!(&pixel(image, x, y), expected, "pixel ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn file_rgb_with_every_filter() {
+    // Rows filtered with none, sub, up, average and Paeth
+    #[rustfmt::skip]
+    let expected = [
+        [255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255],
+        [10, 20, 30, 255], [40, 50, 60, 255], [70, 80, 90, 255], [100, 110, 120, 255],
+        [200, 100, 50, 255], [201, 99, 52, 255], [180, 90, 40, 255], [0, 0, 0, 255],
+        [1, 2, 3, 255], [250, 251, 252, 255], [128, 64, 32, 255], [16, 8, 4, 255],
+        [90, 180, 45, 255], [135, 15, 240, 255], [60, 60, 60, 255], [255, 128, 0, 255],
+    ];
+    assert_pixels(&decode_file("rgb8_filters.png"), 4, &expected);
+}
+
+#[test]
+fn file_interlaced_two_bit_gray() {
+    #[rustfmt::skip]
+    let levels = [
+        0, 1, 2, 3, 0,
+        3, 2, 1, 0, 3,
+        1, 1, 2, 2, 3,
+        0, 3, 0, 3, 0,
+        2, 0, 1, 3, 2,
+    ];
+    let expected: Vec<[u8; 4]> = levels
+        .iter()
+        .map(|&level| [level * 85, level * 85, level * 85, 255])
+        .collect();
+    assert_pixels(&decode_file("gray2_interlaced.png"), 5, &expected);
+}
+
+#[test]
+fn file_palette_with_partial_transparency() {
+    let black = [0, 0, 0, 0];
+    let red = [255, 0, 0, 128];
+    let green = [0, 255, 0, 200];
+    let blue = [0, 0, 255, 255];
+    let yellow = [255, 255, 0, 255];
+    let navy = [12, 34, 56, 255];
+    #[rustfmt::skip]
+    let expected = [
+        black, red, green, blue,
+        yellow, navy, black, red,
+        navy, yellow, blue, green,
+    ];
+    assert_pixels(&decode_file("palette4_trns.png"), 4, &expected);
+}
+
+#[test]
+fn file_interlaced_sixteen_bit_rgba() {
+    // Samples keep their most significant byte
+    #[rustfmt::skip]
+    let expected = [
+        [0x12, 0xAB, 0xFF, 0xFF], [0x00, 0x00, 0x01, 0x80], [0xFF, 0x00, 0x7F, 0x00],
+        [0x80, 0x40, 0x20, 0xC0], [0x01, 0x03, 0x05, 0x07], [0xFE, 0xBA, 0x76, 0x32],
+        [0x00, 0xFF, 0x11, 0x22], [0x33, 0x44, 0x55, 0x66], [0x77, 0x88, 0x99, 0xAA],
+    ];
+    assert_pixels(&decode_file("rgba16_interlaced.png"), 3, &expected);
+}
\ No newline at end of file
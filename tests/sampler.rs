@@ -0,0 +1,86 @@
+//! Checks the fallback of samplers to what WebGL 1 supports for each texture size.
+
+use md::model::{Filter, Sampler, Wrap};
+
+const FILTERS: [Filter; 6] = [
+    Filter::Nearest,
+    Filter::Linear,
+    Filter::NearestMipmapNearest,
+    Filter::LinearMipmapNearest,
+    Filter::NearestMipmapLinear,
+    Filter::LinearMipmapLinear,
+];
+
+const WRAPS: [Wrap; 3] = [Wrap::ClampToEdge, Wrap::Repeat, Wrap::MirroredRepeat];
+
+fn samplers() -> Vec<Sampler> {
+    let mut samplers = vec![];
+    for &min_filter in &FILTERS {
+        for &mag_filter in &[Filter::Nearest, Filter::Linear] {
+            for &wrap_s in &WRAPS {
+                for &wrap_t in &WRAPS {
+                    samplers.push(Sampler {
+                        min_filter,
+                        mag_filter,
+                        wrap_s,
+                        wrap_t,
+                        anisotropy: 4.0,
+                    });
+                }
+            }
+        }
+    }
+    samplers
+}
+
+#[test]
+fn power_of_two_sizes_keep_the_sampler() {
+    for &(width, height) in &[(1, 1), (2, 256), (512, 512), (4096, 8)] {
+        for sampler in samplers() {
+            assert_eq!(sampler.supported_for(width, height), sampler);
+        }
+    }
+}
+
+#[test]
+fn non_power_of_two_sizes_clamp_without_mipmaps() {
+    for &(width, height) in &[(3, 4), (256, 100), (640, 480), (0, 0)] {
+        for sampler in samplers() {
+            let supported = sampler.supported_for(width, height);
+            assert_eq!(supported.wrap_s, Wrap::ClampToEdge);
+            assert_eq!(supported.wrap_t, Wrap::ClampToEdge);
+            assert!(!supported.min_filter.uses_mipmaps());
+            assert_eq!(supported.min_filter, sampler.min_filter.without_mipmaps());
+            assert_eq!(supported.mag_filter, sampler.mag_filter);
+            assert_eq!(supported.anisotropy, sampler.anisotropy);
+        }
+    }
+}
+
+#[test]
+fn mipmap_filters_fall_back_to_their_base_filter() {
+    let expected = [
+        Filter::Nearest,
+        Filter::Linear,
+        Filter::Nearest,
+        Filter::Linear,
+        Filter::Nearest,
+        Filter::Linear,
+    ];
+    for (filter, expected) in FILTERS.iter().zip(&expected) {
+        assert_eq!(filter.without_mipmaps(), *expected, "{:?}", filter);
+        assert_eq!(filter.uses_mipmaps(), filter != expected);
+    }
+}
+
+#[test]
+fn presets() {
+    let mipmapped = Sampler::mipmapped();
+    assert_eq!(mipmapped.supported_for(64, 64), mipmapped);
+    let npot = mipmapped.supported_for(60, 64);
+    assert_eq!(npot.min_filter, Filter::Linear);
+    assert_eq!((npot.wrap_s, npot.wrap_t), (Wrap::ClampToEdge, Wrap::ClampToEdge));
+
+    assert_eq!(Sampler::nearest().supported_for(7, 5), Sampler::nearest());
+    assert_eq!(Sampler::new().supported_for(7, 5), Sampler::new());
+}
\ No newline at end of file
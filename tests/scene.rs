@@ -0,0 +1,159 @@
+//! Saves and parses scene descriptions in both formats.
+
+use md::model::{
+    AlphaMode, Light, LightKind, Projection, Scene, SceneCamera, SceneLight, SceneMaterial,
+    SceneMesh, SceneNode, SceneTransform, SceneWindow,
+};
+use nalgebra::Vector3;
+
+fn transform(translation: [f32; 3]) -> SceneTransform {
+    SceneTransform {
+        translation,
+        rotation: [0.0, 0.0, 0.0, 1.0],
+    }
+}
+
+fn scene() -> Scene {
+    Scene {
+        version: Scene::VERSION,
+        meshes: vec![SceneMesh::Cube, SceneMesh::Quad],
+        materials: vec![SceneMaterial {
+            base_color: [1.0, 0.5, 0.25, 0.5],
+            emissive_factor: [0.0, 0.0, 0.0],
+            shininess: 16.0,
+            metallic: 0.5,
+            roughness: 0.25,
+            alpha_mode: AlphaMode::Mask { cutoff: 0.5 },
+            double_sided: true,
+        }],
+        nodes: vec![SceneNode {
+            transform: transform([0.0, 1.0, 0.0]),
+            mesh: Some(0),
+            material: Some(0),
+            children: vec![SceneNode {
+                transform: transform([2.0, 0.0, -1.0]),
+                mesh: Some(1),
+                material: None,
+                children: vec![],
+            }],
+        }],
+        lights: vec![SceneLight {
+            kind: LightKind::Spot,
+            position: [1.0, 2.0, 3.0],
+            direction: [0.0, -1.0, 0.0],
+            color: [1.0, 1.0, 0.5],
+            intensity: 2.0,
+            attenuation: [1.0, 0.1, 0.01],
+            cone: [0.2, 0.4],
+        }],
+        ambient: [0.2, 0.2, 0.3],
+        camera: SceneCamera {
+            eye: [0.0, 1.0, 5.0],
+            target: [0.0, 0.0, 0.0],
+            projection: Projection::Orthographic { height: 4.0 },
+            near: 0.5,
+            far: 50.0,
+        },
+        windows: vec![SceneWindow {
+            name: String::from("Info"),
+            pos: [10, 20],
+            width: 200,
+            height: 100,
+            text: Some(String::from("Hello")),
+        }],
+    }
+}
+
+#[test]
+fn json_round_trip() {
+    let scene = scene();
+    let json = scene.to_json().unwrap();
+    assert!(json.trim_start().starts_with('{'));
+    assert_eq!(Scene::parse(&json).unwrap(), scene);
+}
+
+#[test]
+fn ron_round_trip() {
+    let scene = scene();
+    let ron = scene.to_ron().unwrap();
+    assert!(!ron.trim_start().starts_with('{'));
+    assert_eq!(Scene::parse(&ron).unwrap(), scene);
+}
+
+#[test]
+fn out_of_range_mesh_is_rejected() {
+    let mut scene = scene();
+    scene.nodes[0].children[0].mesh = Some(2);
+    let error = Scene::parse(&scene.to_json().unwrap()).unwrap_err();
+    assert!(error.contains("mesh index"), "{}", error);
+}
+
+#[test]
+fn out_of_range_material_is_rejected() {
+    let mut scene = scene();
+    scene.nodes[0].material = Some(1);
+    assert!(Scene::parse(&scene.to_ron().unwrap()).is_err());
+}
+
+#[test]
+fn newer_version_is_rejected() {
+    let mut scene = scene();
+    scene.version = Scene::VERSION + 1;
+    let error = Scene::parse(&scene.to_json().unwrap()).unwrap_err();
+    assert!(error.contains("version"), "{}", error);
+}
+
+#[test]
+fn optional_fields_use_defaults() {
+    let json = r#"{
+        "version": 1,
+        "meshes": ["Triangle"],
+        "nodes": [{ "transform": { "translation": [0, 0, 0], "rotation": [0, 0, 0, 1] }, "mesh": 0 }],
+        "lights": [{ "color": [1, 1, 1] }],
+        "camera": { "eye": [0, 0, 3], "target": [0, 0, 0] }
+    }"#;
+    let scene = Scene::parse(json).unwrap();
+    assert_eq!(scene.ambient, [0.1, 0.1, 0.1]);
+    assert!(scene.materials.is_empty() && scene.windows.is_empty());
+    assert_eq!(scene.lights[0].kind, LightKind::Point);
+    assert_eq!(scene.camera.projection, Projection::default());
+}
+
+#[test]
+fn camera_without_position_is_rejected() {
+    let json = r#"{ "version": 1, "meshes": [], "nodes": [], "lights": [], "camera": { "eye": [0, 0, 1] } }"#;
+    let error = Scene::parse(json).unwrap_err();
+    assert!(error.contains("target"), "{}", error);
+}
+
+#[test]
+fn degenerate_camera_is_rejected() {
+    let mut scene = scene();
+    scene.camera.far = scene.camera.near;
+    let error = Scene::parse(&scene.to_json().unwrap()).unwrap_err();
+    assert!(error.contains("depth range"), "{}", error);
+
+    let mut scene = self::scene();
+    scene.camera.projection = Projection::Orthographic { height: 0.0 };
+    assert!(Scene::parse(&scene.to_ron().unwrap()).is_err());
+}
+
+#[test]
+fn zero_light_direction_is_rejected() {
+    let mut scene = scene();
+    scene.lights[0].direction = [0.0, 0.0, 0.0];
+    let error = Scene::parse(&scene.to_json().unwrap()).unwrap_err();
+    assert!(error.contains("light direction"), "{}", error);
+
+    // Point lights ignore their direction
+    scene.lights[0].kind = LightKind::Point;
+    assert!(Scene::parse(&scene.to_json().unwrap()).is_ok());
+}
+
+#[test]
+fn light_direction_is_normalized() {
+    let mut scene = scene();
+    scene.lights[0].direction = [0.0, -4.0, 3.0];
+    let light = Light::from(&Scene::parse(&scene.to_ron().unwrap()).unwrap().lights[0]);
+    assert!((light.direction - Vector3::new(0.0, -0.8, 0.6)).norm() < 1e-6);
+}
\ No newline at end of file
@@ -0,0 +1,151 @@
+//! Checks the shader sources against the identifiers GLSL ES 1.00 reserves,
+//! which WebGL compilers reject even when they are unused by the language,
+//! and the fragment uniforms of the default pipelines against their budget.
+
+use md::model::DefaultPipeline;
+use std::fs;
+use std::path::Path;
+
+/// Keywords reserved for future use, section 3.6 of the GLSL ES 1.00 specification
+const RESERVED: &[&str] = &[
+    "asm", "class", "union", "enum", "typedef", "template", "this", "packed", "goto", "switch",
+    "default", "inline", "noinline", "volatile", "public", "static", "extern", "external",
+    "interface", "flat", "long", "short", "double", "half", "fixed", "unsigned", "superp",
+    "input", "output", "hvec2", "hvec3", "hvec4", "dvec2", "dvec3", "dvec4", "fvec2", "fvec3",
+    "fvec4", "sampler1D", "sampler3D", "sampler1DShadow", "sampler2DShadow", "sampler2DRect",
+    "sampler3DRect", "sampler2DRectShadow", "sizeof", "cast", "namespace", "using",
+];
+
+fn strip_comments(source: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+            stripped.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap();
+            stripped.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    stripped
+}
+
+fn identifiers(source: &str) -> Vec<String> {
+    strip_comments(source)
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn shaders_use_no_reserved_identifiers() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/shader");
+    let mut checked = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "glsl") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        for identifier in identifiers(&source) {
+            assert!(
+                !RESERVED.contains(&identifier.as_str()),
+                "{} uses the reserved keyword `{}`",
+                path.display(),
+                identifier
+            );
+            assert!(
+                !identifier.contains("__"),
+                "{} uses the reserved identifier `{}`",
+                path.display(),
+                identifier
+            );
+        }
+        checked += 1;
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+fn comments_are_ignored() {
+    let words = identifiers("// packed\nvec4 rgba; /* class\n */ float x;");
+    assert_eq!(words, ["vec4", "rgba", "float", "x"]);
+}
+
+fn shader(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("res/shader").join(name)).unwrap()
+}
+
+/// Returns the uniform vectors declared outside and inside `MAX_LIGHTS` arrays,
+/// each scalar or vector taking a whole one and samplers none
+fn uniform_vectors(source: &str) -> (usize, usize) {
+    let (mut fixed, mut per_light) = (0, 0);
+    let source: Vec<&str> = source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect();
+    for statement in strip_comments(&source.join("\n")).split(';') {
+        // Precision qualifiers, spelled out or through a macro, do not change the size
+        let words: Vec<&str> = statement
+            .split_whitespace()
+            .filter(|word| {
+                !["lowp", "mediump", "highp"].contains(word)
+                    && !word.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+            })
+            .collect();
+        if words.first() != Some(&"uniform") || words[1].starts_with("sampler") {
+            continue;
+        }
+        let rows = match words[1] {
+            "mat2" => 2,
+            "mat3" => 3,
+            "mat4" => 4,
+            _ => 1,
+        };
+        let declarator = words[2..].concat();
+        match declarator.split_once('[') {
+            Some((_, "MAX_LIGHTS]")) => per_light += rows,
+            Some((_, count)) => fixed += rows * count.trim_end_matches(']').parse::<usize>().unwrap(),
+            None => fixed += rows,
+        }
+    }
+    (fixed, per_light)
+}
+
+#[test]
+fn pipeline_uniform_budget_matches_the_shaders() {
+    let lighting = uniform_vectors(&shader("lighting.glsl"));
+    assert_eq!(lighting.1, DefaultPipeline::LIGHT_UNIFORM_VECTORS);
+    for main in &["default.frag.glsl", "pbr.frag.glsl"] {
+        let (fixed, per_light) = uniform_vectors(&shader(main));
+        assert_eq!(per_light, 0, "{}", main);
+        assert!(lighting.0 + fixed <= DefaultPipeline::FIXED_UNIFORM_VECTORS, "{}", main);
+    }
+    let pbr = uniform_vectors(&shader("pbr.frag.glsl"));
+    assert_eq!(lighting.0 + pbr.0, DefaultPipeline::FIXED_UNIFORM_VECTORS);
+}
+
+#[test]
+fn lights_fit_in_the_remaining_uniforms() {
+    let fixed = DefaultPipeline::FIXED_UNIFORM_VECTORS;
+    assert_eq!(DefaultPipeline::lights_fitting(16), 0);
+    assert_eq!(DefaultPipeline::lights_fitting(fixed + 4), 0);
+    assert_eq!(DefaultPipeline::lights_fitting(fixed + 5), 1);
+    // Common desktop and mobile limits
+    assert_eq!(DefaultPipeline::lights_fitting(221), 38);
+    assert_eq!(DefaultPipeline::lights_fitting(1024), 199);
+}
+
+#[test]
+fn shadow_depth_is_unpacked_in_high_precision() {
+    let lighting = strip_comments(&shader("lighting.glsl"));
+    assert!(lighting.contains("#ifdef GL_FRAGMENT_PRECISION_HIGH"));
+    assert!(lighting.contains("#define SHADOW_PRECISION highp"));
+    assert!(lighting.contains("SHADOW_PRECISION float unpack_depth(SHADOW_PRECISION vec4"));
+    assert!(lighting.contains("SHADOW_PRECISION float depth = unpack_depth("));
+}
\ No newline at end of file
@@ -0,0 +1,111 @@
+//! Collects frame timings and counters without a WebGL context, feeding them directly.
+
+use md::model::{stats, Stats, TimeWindow};
+
+#[test]
+fn window_keeps_the_latest_samples() {
+    let mut window = TimeWindow::new(3);
+    for duration in [10.0, 20.0, 30.0, 40.0] {
+        window.push(duration);
+    }
+    assert_eq!(window.len(), 3);
+
+    let summary = window.summary();
+    assert_eq!(summary.samples, 3);
+    assert_eq!(summary.min, 20.0);
+    assert_eq!(summary.max, 40.0);
+    assert_eq!(summary.avg, 30.0);
+}
+
+#[test]
+fn percentiles_use_nearest_rank() {
+    let mut window = TimeWindow::new(100);
+    for duration in (1..=100).rev() {
+        window.push(duration as f64);
+    }
+    assert_eq!(window.percentile(50.0), Some(50.0));
+    assert_eq!(window.percentile(90.0), Some(90.0));
+    assert_eq!(window.percentile(99.0), Some(99.0));
+    assert_eq!(window.percentile(0.0), Some(1.0));
+    assert_eq!(window.percentile(100.0), Some(100.0));
+
+    let summary = window.summary();
+    assert_eq!((summary.p50, summary.p90, summary.p99), (50.0, 90.0, 99.0));
+}
+
+#[test]
+fn empty_window_summary_is_zero() {
+    let window = TimeWindow::new(8);
+    assert!(window.is_empty());
+    assert_eq!(window.percentile(50.0), None);
+    assert_eq!(window.summary().max, 0.0);
+}
+
+#[test]
+fn frames_take_the_render_counters() {
+    let mut stats = Stats::new(10);
+    stats::count_draw(12);
+    stats::count_draw(2);
+    stats::count_state_changes(5);
+    stats.end_frame(0.0, 4.0);
+
+    let report = stats.report();
+    assert_eq!(report.frames, 1);
+    assert_eq!(report.last_frame.draw_calls, 2);
+    assert_eq!(report.last_frame.triangles, 14);
+    assert_eq!(report.last_frame.state_changes, 5);
+    // A single frame has no interval yet
+    assert_eq!(report.frame_time.samples, 0);
+    assert_eq!(report.cpu_time.max, 4.0);
+
+    stats.end_frame(16.0, 18.0);
+    let report = stats.report();
+    assert_eq!(report.last_frame.draw_calls, 0);
+    assert_eq!(report.frame_time.avg, 16.0);
+    assert_eq!(report.cpu_time.min, 2.0);
+}
+
+#[test]
+fn calls_are_counted_per_method_and_frame() {
+    let mut stats = Stats::default();
+    stats.count_call("draw");
+    stats.count_call("set_light_color");
+    stats.count_call("set_light_color");
+    stats.end_frame(0.0, 1.0);
+    stats.count_call("draw");
+
+    let calls = stats.report().calls;
+    assert_eq!(calls.last_frame, 3);
+    assert_eq!(calls.total, 4);
+    assert_eq!(calls.by_method["draw"], 2);
+    assert_eq!(calls.by_method["set_light_color"], 2);
+
+    stats.reset();
+    assert_eq!(stats.report().calls.total, 0);
+}
+
+#[test]
+fn memory_follows_allocations() {
+    stats::allocate_buffer(300);
+    stats::allocate_texture(1024);
+    stats::free_buffer(100);
+    stats::free_texture(2048);
+
+    let memory = stats::gpu_memory();
+    assert_eq!(memory.buffer_bytes, 200);
+    assert_eq!(memory.texture_bytes, 0);
+}
+
+#[test]
+fn report_is_json() {
+    let mut stats = Stats::default();
+    stats.count_call("draw");
+    stats.end_frame(0.0, 3.5);
+
+    let json: serde_json::Value = serde_json::from_str(&stats.to_json().unwrap()).unwrap();
+    assert_eq!(json["frames"], 1);
+    assert_eq!(json["cpu_time"]["p99"], 3.5);
+    assert_eq!(json["calls"]["by_method"]["draw"], 1);
+    assert!(json["memory"]["texture_bytes"].is_u64());
+    assert!(json["last_frame"]["triangles"].is_u64());
+}
\ No newline at end of file
@@ -7,7 +7,46 @@ use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
+/// Returns a context drawing into a canvas added to the page
+fn context() -> md::Context {
+    let document = web_sys::window().unwrap().document().unwrap();
+    if document.get_element_by_id("rust-gl").is_none() {
+        let root = document.document_element().unwrap();
+        root.insert_adjacent_html("beforeend", r#"<canvas id="rust-gl"></canvas>"#)
+            .unwrap();
+    }
+    md::Context::new().unwrap()
+}
+
 #[wasm_bindgen_test]
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn loading_a_scene_removes_render_targets() {
+    let mut context = context();
+    let scene = context.export_scene().unwrap();
+    context.add_render_window("Minimap", 160, 160).unwrap();
+    assert_eq!(context.get_render_target_count(), 1);
+
+    context.load_scene(&scene).unwrap();
+    assert_eq!(context.get_render_target_count(), 0);
+    context.load_scene(&scene).unwrap();
+    assert_eq!(context.get_render_target_count(), 0);
+}
+
+#[wasm_bindgen_test]
+fn material_texture_slots_can_be_set() {
+    let mut context = context();
+    let texture = context.create_texture(4, 4).unwrap();
+    context
+        .set_node_material_texture(0, md::model::TextureSlot::Normal, Some(texture))
+        .unwrap();
+    context
+        .set_node_material_texture(0, md::model::TextureSlot::Normal, None)
+        .unwrap();
+    assert!(context
+        .set_node_material_texture(0, md::model::TextureSlot::Occlusion, Some(texture + 1))
+        .is_err());
+}
\ No newline at end of file